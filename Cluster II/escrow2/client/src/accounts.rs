@@ -0,0 +1,80 @@
+use anchor_lang::{AccountDeserialize, Result};
+use escrow2::state::{
+    Bundle, Config, CounterOffer, Escrow, MakerState, MarketPage, TraderStats, Vesting,
+};
+
+//Decodes an escrow account, checking its discriminator. Accounts in the legacy layout fail to decode until they are migrated.
+pub fn decode_escrow(data: &[u8]) -> Result<Escrow> {
+    Escrow::try_deserialize(&mut &data[..])
+}
+
+//Decodes a market index page. Its escrows field lists the open offers of the mint pair.
+pub fn decode_market_page(data: &[u8]) -> Result<MarketPage> {
+    MarketPage::try_deserialize(&mut &data[..])
+}
+
+//Decodes a bundle. Its offered and requested legs give the order of the remaining accounts of take_bundle and refund_bundle.
+pub fn decode_bundle(data: &[u8]) -> Result<Bundle> {
+    Bundle::try_deserialize(&mut &data[..])
+}
+
+//Decodes a counter-offer. Its remaining_a must still match the escrow for the maker to accept it.
+pub fn decode_counter_offer(data: &[u8]) -> Result<CounterOffer> {
+    CounterOffer::try_deserialize(&mut &data[..])
+}
+
+//Decodes a maker's seed allocator. A maker without one has made no escrow through it yet, its next seed is 0.
+pub fn decode_maker_state(data: &[u8]) -> Result<MakerState> {
+    MakerState::try_deserialize(&mut &data[..])
+}
+
+//Decodes a wallet's trading history. Its volume lists the first MAX_STATS_MINTS mints the wallet traded.
+pub fn decode_trader_stats(data: &[u8]) -> Result<TraderStats> {
+    TraderStats::try_deserialize(&mut &data[..])
+}
+
+//Decodes a vesting. claimed against total tells how much is still locked.
+pub fn decode_vesting(data: &[u8]) -> Result<Vesting> {
+    Vesting::try_deserialize(&mut &data[..])
+}
+
+//Decodes the program wide config account.
+pub fn decode_config(data: &[u8]) -> Result<Config> {
+    Config::try_deserialize(&mut &data[..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::{prelude::Pubkey, AccountSerialize};
+
+    #[test]
+    fn decode_escrow_round_trips_and_checks_discriminator() {
+        let escrow = Escrow {
+            maker: Pubkey::new_unique(),
+            mint_a: Pubkey::new_unique(),
+            mint_b: Pubkey::new_unique(),
+            offer_amount: 50,
+            seed: 7,
+            escrow_bump: 254,
+            remaining_a: 100,
+            remaining_b: 50,
+            expires_at: i64::MAX,
+            allowed_taker: None,
+            market_page: Some(0),
+            nft: false,
+            oracle: None,
+            auction: None,
+            vesting: None,
+            arbitration: None,
+            status: escrow2::state::EscrowStatus::Open,
+        };
+        let mut data = Vec::new();
+        escrow.try_serialize(&mut data).unwrap();
+
+        let decoded = decode_escrow(&data).unwrap();
+        assert_eq!(decoded.maker, escrow.maker);
+        assert_eq!(decoded.remaining_a, 100);
+        assert!(decode_config(&data).is_err());
+    }
+}
@@ -0,0 +1,1103 @@
+use anchor_lang::{
+    prelude::Pubkey,
+    solana_program::{
+        instruction::{AccountMeta, Instruction},
+        system_program,
+    },
+    InstructionData, ToAccountMetas,
+};
+use anchor_spl::{
+    associated_token::{self, get_associated_token_address_with_program_id},
+    token::spl_token,
+};
+use escrow2::{
+    state::{
+        ArbiterTerms, AuctionTerms, BundleLeg, CounterOffer, Escrow, MakeArgs, OfferKind,
+        OraclePeg, Vesting, VestingTerms,
+    },
+    utils::is_native_mint,
+};
+
+use crate::pda::{
+    bundle_address, config_address, counter_address, escrow_address, maker_state_address,
+    market_page_address, metadata_address, program_data_address, stats_address, vault_address,
+    vesting_address,
+};
+
+//Keys that identify an offer. Every other account the escrow instructions need is derived from them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EscrowKeys {
+    pub maker: Pubkey,
+    pub seed: u64,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub token_program_a: Pubkey, //Token program that owns mint_a, SPL Token or Token-2022.
+    pub token_program_b: Pubkey, //Token program that owns mint_b.
+    pub market_page: Option<u32>, //Market index page the escrow is listed on. None only for migrated escrows.
+    pub price_feed: Option<Pubkey>, //Price feed of an offer pegged to an oracle, passed to take. None for fixed price offers.
+}
+
+impl EscrowKeys {
+    //Keys of an escrow read from chain. The account does not store the token programs, they are the owners of the two mints.
+    pub fn from_escrow(escrow: &Escrow, token_program_a: Pubkey, token_program_b: Pubkey) -> Self {
+        Self {
+            maker: escrow.maker,
+            seed: escrow.seed,
+            mint_a: escrow.mint_a,
+            mint_b: escrow.mint_b,
+            token_program_a,
+            token_program_b,
+            market_page: escrow.market_page,
+            price_feed: escrow.oracle.map(|oracle| oracle.price_feed),
+        }
+    }
+
+    pub fn escrow(&self) -> Pubkey {
+        escrow_address(&self.maker, self.seed).0
+    }
+
+    pub fn vault(&self) -> Pubkey {
+        vault_address(&self.escrow(), &self.mint_a, &self.token_program_a)
+    }
+
+    pub fn market_page(&self) -> Option<Pubkey> {
+        self.market_page
+            .map(|page| market_page_address(&self.mint_a, &self.mint_b, page).0)
+    }
+
+    fn ata_a(&self, owner: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(owner, &self.mint_a, &self.token_program_a)
+    }
+
+    //The counter-offer of `proposer` on this escrow and its vault, which always holds mint_b in a token account.
+    pub fn counter(&self, proposer: &Pubkey) -> (Pubkey, Pubkey) {
+        let counter = counter_address(&self.escrow(), proposer).0;
+        let counter_vault = vault_address(&counter, &self.mint_b, &self.token_program_b);
+        (counter, counter_vault)
+    }
+
+    //Token accounts for mint_b, left out when mint_b is wrapped SOL so the leg is paid in native SOL.
+    fn ata_b(&self, owner: &Pubkey) -> Option<Pubkey> {
+        (!is_native_mint(&self.mint_b)).then(|| {
+            get_associated_token_address_with_program_id(owner, &self.mint_b, &self.token_program_b)
+        })
+    }
+}
+
+fn instruction(accounts: impl ToAccountMetas, args: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: escrow2::ID,
+        accounts: accounts.to_account_metas(None),
+        data: args.data(),
+    }
+}
+
+fn make_args(
+    keys: &EscrowKeys,
+    deposit: u64,
+    receive: u64,
+    expires_at: i64,
+    allowed_taker: Option<Pubkey>,
+) -> MakeArgs {
+    MakeArgs {
+        seed: keys.seed,
+        deposit,
+        receive,
+        expires_at,
+        allowed_taker,
+        page: keys.market_page.unwrap_or(0),
+        collection: None,
+        kind: OfferKind::Fixed,
+        vesting: None,
+    }
+}
+
+fn make_instruction(keys: &EscrowKeys, args: MakeArgs, metadata: Option<Pubkey>) -> Instruction {
+    let accounts = escrow2::accounts::Make {
+        maker: keys.maker,
+        mint_a: keys.mint_a,
+        mint_b: keys.mint_b,
+        maker_ata_a: (!is_native_mint(&keys.mint_a)).then(|| keys.ata_a(&keys.maker)),
+        maker_state: maker_state_address(&keys.maker).0,
+        maker_stats: stats_address(&keys.maker).0,
+        escrow: keys.escrow(),
+        vault: keys.vault(),
+        market_page: market_page_address(&keys.mint_a, &keys.mint_b, args.page).0,
+        metadata,
+        associated_token_program: associated_token::ID,
+        token_program: keys.token_program_a,
+        system_program: system_program::ID,
+    };
+    instruction(accounts, escrow2::instruction::Make { args })
+}
+
+//Creates the offer and lists it on keys.market_page, page 0 if it is not set. A wrapped SOL deposit is taken from the maker's lamports.
+pub fn make(
+    keys: &EscrowKeys,
+    deposit: u64,
+    receive: u64,
+    expires_at: i64,
+    allowed_taker: Option<Pubkey>,
+) -> Instruction {
+    let args = make_args(keys, deposit, receive, expires_at, allowed_taker);
+    make_instruction(keys, args, None)
+}
+
+//Offers the NFT keys.mint_a. With a collection the NFT must be a verified member of it. Takers pay the creators' royalties.
+pub fn make_nft(
+    keys: &EscrowKeys,
+    receive: u64,
+    expires_at: i64,
+    allowed_taker: Option<Pubkey>,
+    collection: Option<Pubkey>,
+) -> Instruction {
+    let args = MakeArgs {
+        collection,
+        ..make_args(keys, 1, receive, expires_at, allowed_taker)
+    };
+    make_instruction(keys, args, Some(metadata_address(&keys.mint_a)))
+}
+
+//Auctions the whole deposit, its price falling from auction.start_price to auction.end_price. The start price is recorded as the starting ask.
+pub fn make_auction(
+    keys: &EscrowKeys,
+    deposit: u64,
+    expires_at: i64,
+    auction: AuctionTerms,
+) -> Instruction {
+    let args = MakeArgs {
+        kind: OfferKind::Auction(auction),
+        ..make_args(keys, deposit, auction.start_price, expires_at, None)
+    };
+    make_instruction(keys, args, None)
+}
+
+//Creates an offer priced from `oracle` on every take. `receive` is only recorded as the maker's starting ask.
+pub fn make_pegged(
+    keys: &EscrowKeys,
+    deposit: u64,
+    receive: u64,
+    expires_at: i64,
+    oracle: OraclePeg,
+) -> Instruction {
+    let args = MakeArgs {
+        kind: OfferKind::Pegged(oracle),
+        ..make_args(keys, deposit, receive, expires_at, None)
+    };
+    make_instruction(keys, args, None)
+}
+
+//Creates an offer whose takers receive mint_a through a vesting vault, unlocking over `vesting` from the time they take it.
+pub fn make_vested(
+    keys: &EscrowKeys,
+    deposit: u64,
+    receive: u64,
+    expires_at: i64,
+    vesting: VestingTerms,
+) -> Instruction {
+    let args = MakeArgs {
+        vesting: Some(vesting),
+        ..make_args(keys, deposit, receive, expires_at, None)
+    };
+    make_instruction(keys, args, None)
+}
+
+//Funds an escrow that `arbitration.arbiter` later releases to `arbitration.recipient` or refunds to the maker. It is not listed and cannot be taken.
+//The maker can reclaim it after `expires_at` unless it was disputed.
+pub fn make_arbitrated(
+    keys: &EscrowKeys,
+    deposit: u64,
+    arbitration: ArbiterTerms,
+    expires_at: i64,
+) -> Instruction {
+    let accounts = escrow2::accounts::MakeArbitrated {
+        maker: keys.maker,
+        mint_a: keys.mint_a,
+        maker_ata_a: keys.ata_a(&keys.maker),
+        maker_state: maker_state_address(&keys.maker).0,
+        escrow: keys.escrow(),
+        vault: keys.vault(),
+        associated_token_program: associated_token::ID,
+        token_program: keys.token_program_a,
+        system_program: system_program::ID,
+    };
+    let args = escrow2::instruction::MakeArbitrated {
+        seed: keys.seed,
+        deposit,
+        terms: arbitration,
+        expires_at,
+    };
+    instruction(accounts, args)
+}
+
+fn take_accounts(keys: &EscrowKeys, taker: &Pubkey, treasury: &Pubkey) -> escrow2::accounts::Take {
+    escrow2::accounts::Take {
+        taker: *taker,
+        maker: keys.maker,
+        mint_a: keys.mint_a,
+        mint_b: keys.mint_b,
+        taker_ata_a: keys.ata_a(taker),
+        taker_ata_b: keys.ata_b(taker),
+        maker_ata_b: keys.ata_b(&keys.maker),
+        maker_ata_a: (!is_native_mint(&keys.mint_a)).then(|| keys.ata_a(&keys.maker)),
+        maker_stats: stats_address(&keys.maker).0,
+        taker_stats: stats_address(taker).0,
+        config: config_address().0,
+        treasury: *treasury,
+        treasury_ata_b: keys.ata_b(treasury),
+        escrow: keys.escrow(),
+        vault: keys.vault(),
+        vesting: None,
+        vesting_vault: None,
+        market_page: keys.market_page(),
+        metadata: None,
+        price_feed: keys.price_feed,
+        system_program: system_program::ID,
+        token_program_a: keys.token_program_a,
+        token_program_b: keys.token_program_b,
+        associated_token_program: associated_token::ID,
+    }
+}
+
+//Fills the whole offer. `treasury` must be the treasury stored in the config.
+//The fill fails unless the taker receives at least `expected_receive_a` and pays at most `max_pay_b`, transfer fees included.
+pub fn take(
+    keys: &EscrowKeys,
+    taker: &Pubkey,
+    treasury: &Pubkey,
+    expected_receive_a: u64,
+    max_pay_b: u64,
+) -> Instruction {
+    instruction(
+        take_accounts(keys, taker, treasury),
+        escrow2::instruction::Take {
+            expected_receive_a,
+            max_pay_b,
+        },
+    )
+}
+
+//Fills an NFT offer. `creators` are the creators with a share in the NFT's metadata, in metadata order, who are paid their royalty.
+pub fn take_nft(
+    keys: &EscrowKeys,
+    taker: &Pubkey,
+    treasury: &Pubkey,
+    creators: &[Pubkey],
+    expected_receive_a: u64,
+    max_pay_b: u64,
+) -> Instruction {
+    let accounts = escrow2::accounts::Take {
+        metadata: Some(metadata_address(&keys.mint_a)),
+        ..take_accounts(keys, taker, treasury)
+    };
+    let args = escrow2::instruction::Take {
+        expected_receive_a,
+        max_pay_b,
+    };
+    let mut ix = instruction(accounts, args);
+    for creator in creators {
+        ix.accounts.push(AccountMeta::new(*creator, false)); //Paid directly when mint_b is native SOL.
+        if let Some(creator_ata_b) = keys.ata_b(creator) {
+            ix.accounts.push(AccountMeta::new(creator_ata_b, false));
+        }
+    }
+    ix
+}
+
+//Fills a vested offer. mint_a is locked in the taker's vesting vault and released by claim_vested.
+pub fn take_vested(
+    keys: &EscrowKeys,
+    taker: &Pubkey,
+    treasury: &Pubkey,
+    expected_receive_a: u64,
+    max_pay_b: u64,
+) -> Instruction {
+    let vesting = vesting_address(&keys.escrow(), taker).0;
+    let accounts = escrow2::accounts::Take {
+        vesting: Some(vesting),
+        vesting_vault: Some(vault_address(&vesting, &keys.mint_a, &keys.token_program_a)),
+        ..take_accounts(keys, taker, treasury)
+    };
+    let args = escrow2::instruction::Take {
+        expected_receive_a,
+        max_pay_b,
+    };
+    instruction(accounts, args)
+}
+
+//Fills amount_b of the offer, failing unless the taker receives at least min_receive_a of mint_a and pays at most max_pay_b.
+pub fn take_partial(
+    keys: &EscrowKeys,
+    taker: &Pubkey,
+    treasury: &Pubkey,
+    amount_b: u64,
+    min_receive_a: u64,
+    max_pay_b: u64,
+) -> Instruction {
+    let args = escrow2::instruction::TakePartial {
+        amount_b,
+        min_receive_a,
+        max_pay_b,
+    };
+    instruction(take_accounts(keys, taker, treasury), args)
+}
+
+pub fn update(keys: &EscrowKeys, receive: u64, deposit: u64) -> Instruction {
+    let accounts = escrow2::accounts::Update {
+        maker: keys.maker,
+        mint_a: keys.mint_a,
+        maker_ata_a: keys.ata_a(&keys.maker),
+        escrow: keys.escrow(),
+        vault: keys.vault(),
+        system_program: system_program::ID,
+        token_program: keys.token_program_a,
+        associated_token_program: associated_token::ID,
+    };
+    instruction(accounts, escrow2::instruction::Update { receive, deposit })
+}
+
+//Returns the deposit to the maker. Wrapped SOL comes back as native SOL.
+pub fn refund(keys: &EscrowKeys) -> Instruction {
+    let accounts = escrow2::accounts::Refund {
+        maker: keys.maker,
+        mint_a: keys.mint_a,
+        maker_ata_a: (!is_native_mint(&keys.mint_a)).then(|| keys.ata_a(&keys.maker)),
+        escrow: keys.escrow(),
+        vault: keys.vault(),
+        market_page: keys.market_page(),
+        maker_stats: stats_address(&keys.maker).0,
+        system_program: system_program::ID,
+        token_program: keys.token_program_a,
+        associated_token_program: associated_token::ID,
+    };
+    instruction(accounts, escrow2::instruction::Refund {})
+}
+
+//Returns an expired offer to the maker. Anyone can send it, `payer` covers the maker's token account if it is missing.
+pub fn refund_expired(keys: &EscrowKeys, payer: &Pubkey) -> Instruction {
+    let accounts = escrow2::accounts::RefundExpired {
+        payer: *payer,
+        maker: keys.maker,
+        mint_a: keys.mint_a,
+        maker_ata_a: (!is_native_mint(&keys.mint_a)).then(|| keys.ata_a(&keys.maker)),
+        escrow: keys.escrow(),
+        vault: keys.vault(),
+        market_page: keys.market_page(),
+        maker_stats: stats_address(&keys.maker).0,
+        system_program: system_program::ID,
+        token_program: keys.token_program_a,
+        associated_token_program: associated_token::ID,
+    };
+    instruction(accounts, escrow2::instruction::RefundExpired {})
+}
+
+//Refunds several escrows of `maker` in one transaction. Escrows already closed are skipped on chain, and wrapped SOL stays wrapped.
+pub fn refund_many(maker: &Pubkey, escrows: &[EscrowKeys]) -> Instruction {
+    let accounts = escrow2::accounts::RefundMany {
+        maker: *maker,
+        maker_stats: stats_address(maker).0,
+        system_program: system_program::ID,
+        associated_token_program: associated_token::ID,
+    };
+    let mut ix = instruction(accounts, escrow2::instruction::RefundMany {});
+    for keys in escrows {
+        ix.accounts.extend([
+            AccountMeta::new(keys.escrow(), false),
+            AccountMeta::new(keys.vault(), false),
+            AccountMeta::new(keys.mint_a, false), //Writable so withheld Token-2022 fees can be harvested from the vault.
+            AccountMeta::new_readonly(keys.token_program_a, false),
+            AccountMeta::new(keys.ata_a(maker), false),
+            AccountMeta::new(keys.market_page().unwrap_or(escrow2::ID), false), //Ignored for migrated escrows.
+        ]);
+    }
+    ix
+}
+
+//Proposes `receive` of mint_b for everything left in the offer. Wrapped SOL is paid from the proposer's wrapped SOL account.
+pub fn counter_offer(keys: &EscrowKeys, proposer: &Pubkey, receive: u64) -> Instruction {
+    let (counter_offer, counter_vault) = keys.counter(proposer);
+    let accounts = escrow2::accounts::ProposeCounter {
+        proposer: *proposer,
+        mint_b: keys.mint_b,
+        proposer_ata_b: get_associated_token_address_with_program_id(
+            proposer,
+            &keys.mint_b,
+            &keys.token_program_b,
+        ),
+        escrow: keys.escrow(),
+        counter_offer,
+        counter_vault,
+        token_program: keys.token_program_b,
+        associated_token_program: associated_token::ID,
+        system_program: system_program::ID,
+    };
+    instruction(accounts, escrow2::instruction::CounterOffer { receive })
+}
+
+//Accepts the counter-offer of `proposer`, sent by the maker. `treasury` must be the treasury stored in the config.
+pub fn accept_counter(keys: &EscrowKeys, proposer: &Pubkey, treasury: &Pubkey) -> Instruction {
+    let (counter_offer, counter_vault) = keys.counter(proposer);
+    let accounts = escrow2::accounts::AcceptCounter {
+        maker: keys.maker,
+        proposer: *proposer,
+        mint_a: keys.mint_a,
+        mint_b: keys.mint_b,
+        proposer_ata_a: keys.ata_a(proposer),
+        maker_ata_b: get_associated_token_address_with_program_id(
+            &keys.maker,
+            &keys.mint_b,
+            &keys.token_program_b,
+        ),
+        maker_ata_a: (!is_native_mint(&keys.mint_a)).then(|| keys.ata_a(&keys.maker)),
+        escrow: keys.escrow(),
+        vault: keys.vault(),
+        counter_offer,
+        counter_vault,
+        config: config_address().0,
+        treasury: *treasury,
+        treasury_ata_b: Some(get_associated_token_address_with_program_id(
+            treasury,
+            &keys.mint_b,
+            &keys.token_program_b,
+        )),
+        market_page: keys.market_page(),
+        maker_stats: stats_address(&keys.maker).0,
+        proposer_stats: stats_address(proposer).0,
+        token_program_a: keys.token_program_a,
+        token_program_b: keys.token_program_b,
+        associated_token_program: associated_token::ID,
+        system_program: system_program::ID,
+    };
+    instruction(accounts, escrow2::instruction::AcceptCounter {})
+}
+
+//Withdraws a counter-offer read from chain. The escrow it was made on does not need to exist anymore.
+pub fn cancel_counter(counter: &CounterOffer, token_program_b: &Pubkey) -> Instruction {
+    let counter_offer = counter_address(&counter.escrow, &counter.proposer).0;
+    let accounts = escrow2::accounts::CancelCounter {
+        proposer: counter.proposer,
+        mint_b: counter.mint_b,
+        proposer_ata_b: get_associated_token_address_with_program_id(
+            &counter.proposer,
+            &counter.mint_b,
+            token_program_b,
+        ),
+        counter_offer,
+        counter_vault: vault_address(&counter_offer, &counter.mint_b, token_program_b),
+        token_program: *token_program_b,
+        associated_token_program: associated_token::ID,
+        system_program: system_program::ID,
+    };
+    instruction(accounts, escrow2::instruction::CancelCounter {})
+}
+
+//Claims what has unlocked of a vesting read from chain. `token_program` is the owner of vesting.mint.
+pub fn claim_vested(vesting: &Vesting, token_program: &Pubkey) -> Instruction {
+    let address = vesting_address(&vesting.escrow, &vesting.beneficiary).0;
+    let accounts = escrow2::accounts::ClaimVested {
+        beneficiary: vesting.beneficiary,
+        mint: vesting.mint,
+        beneficiary_ata: get_associated_token_address_with_program_id(
+            &vesting.beneficiary,
+            &vesting.mint,
+            token_program,
+        ),
+        vesting: address,
+        vesting_vault: vault_address(&address, &vesting.mint, token_program),
+        token_program: *token_program,
+        associated_token_program: associated_token::ID,
+        system_program: system_program::ID,
+    };
+    instruction(accounts, escrow2::instruction::ClaimVested {})
+}
+
+//Creates the config. `admin` must be the upgrade authority of the program.
+pub fn initialize_config(admin: &Pubkey, fee_bps: u16, treasury: &Pubkey) -> Instruction {
+    let accounts = escrow2::accounts::InitializeConfig {
+        admin: *admin,
+        program_data: program_data_address(),
+        config: config_address().0,
+        system_program: system_program::ID,
+    };
+    let args = escrow2::instruction::InitializeConfig {
+        fee_bps,
+        treasury: *treasury,
+    };
+    instruction(accounts, args)
+}
+
+pub fn update_config(
+    admin: &Pubkey,
+    new_admin: &Pubkey,
+    fee_bps: u16,
+    treasury: &Pubkey,
+) -> Instruction {
+    let accounts = escrow2::accounts::UpdateConfig {
+        admin: *admin,
+        config: config_address().0,
+    };
+    let args = escrow2::instruction::UpdateConfig {
+        admin: *new_admin,
+        fee_bps,
+        treasury: *treasury,
+    };
+    instruction(accounts, args)
+}
+
+//Rewrites a legacy escrow, which the maker signs with the amount it deposited. Legacy escrows only held SPL Token mints, so the vault is derived with the legacy token program.
+pub fn migrate(
+    payer: &Pubkey,
+    maker: &Pubkey,
+    seed: u64,
+    mint_a: &Pubkey,
+    deposit: u64,
+) -> Instruction {
+    let escrow = escrow_address(maker, seed).0;
+    let accounts = escrow2::accounts::Migrate {
+        payer: *payer,
+        maker: *maker,
+        escrow,
+        vault: vault_address(&escrow, mint_a, &spl_token::ID),
+        system_program: system_program::ID,
+    };
+    instruction(accounts, escrow2::instruction::Migrate { seed, deposit })
+}
+
+//A mint of a bundle with the token program that owns it, which the bundle account does not store.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BundleMint {
+    pub mint: Pubkey,
+    pub token_program: Pubkey,
+}
+
+impl BundleMint {
+    fn ata(&self, owner: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(owner, &self.mint, &self.token_program)
+    }
+
+    //Mint, token program, vault and the receiver's token account, the remaining accounts of one offered leg.
+    fn offered_leg(&self, bundle: &Pubkey, receiver: &Pubkey) -> [AccountMeta; 4] {
+        [
+            AccountMeta::new(self.mint, false), //Writable so withheld Token-2022 fees can be harvested from the vault.
+            AccountMeta::new_readonly(self.token_program, false),
+            AccountMeta::new(self.ata(bundle), false),
+            AccountMeta::new(self.ata(receiver), false),
+        ]
+    }
+
+    //The offered leg accounts of take_bundle, followed by the maker's token account that receives anything sent to the vault after make_bundle.
+    fn taken_leg(&self, bundle: &Pubkey, taker: &Pubkey, maker: &Pubkey) -> [AccountMeta; 5] {
+        let [mint, token_program, vault, taker_ata] = self.offered_leg(bundle, taker);
+        [
+            mint,
+            token_program,
+            vault,
+            taker_ata,
+            AccountMeta::new(self.ata(maker), false),
+        ]
+    }
+}
+
+fn bundle_instruction(
+    accounts: impl ToAccountMetas,
+    args: impl InstructionData,
+    legs: impl IntoIterator<Item = AccountMeta>,
+) -> Instruction {
+    let mut ix = instruction(accounts, args);
+    ix.accounts.extend(legs);
+    ix
+}
+
+//Creates a bundle. `offered` lists each offered mint with the amount to deposit, all paid from the maker's associated token accounts.
+pub fn make_bundle(
+    maker: &Pubkey,
+    seed: u64,
+    offered: &[(BundleMint, u64)],
+    requested: &[BundleLeg],
+    expires_at: i64,
+    allowed_taker: Option<Pubkey>,
+) -> Instruction {
+    let bundle = bundle_address(maker, seed).0;
+    let accounts = escrow2::accounts::MakeBundle {
+        maker: *maker,
+        bundle,
+        associated_token_program: associated_token::ID,
+        system_program: system_program::ID,
+    };
+    let args = escrow2::instruction::MakeBundle {
+        seed,
+        deposits: offered.iter().map(|(_, deposit)| *deposit).collect(),
+        requested: requested.to_vec(),
+        expires_at,
+        allowed_taker,
+    };
+    let legs = offered
+        .iter()
+        .flat_map(|(mint, _)| mint.offered_leg(&bundle, maker));
+    bundle_instruction(accounts, args, legs)
+}
+
+//Fills a whole bundle. Both slices must be in the order of the bundle's offered and requested legs.
+pub fn take_bundle(
+    maker: &Pubkey,
+    seed: u64,
+    taker: &Pubkey,
+    treasury: &Pubkey,
+    offered: &[BundleMint],
+    requested: &[BundleMint],
+) -> Instruction {
+    let bundle = bundle_address(maker, seed).0;
+    let accounts = escrow2::accounts::TakeBundle {
+        taker: *taker,
+        maker: *maker,
+        config: config_address().0,
+        treasury: *treasury,
+        bundle,
+        system_program: system_program::ID,
+        associated_token_program: associated_token::ID,
+    };
+    let offered_legs = offered
+        .iter()
+        .flat_map(|mint| mint.taken_leg(&bundle, taker, maker));
+    let requested_legs = requested.iter().flat_map(|mint| {
+        [
+            AccountMeta::new_readonly(mint.mint, false),
+            AccountMeta::new_readonly(mint.token_program, false),
+            AccountMeta::new(mint.ata(taker), false),
+            AccountMeta::new(mint.ata(maker), false),
+            AccountMeta::new(mint.ata(treasury), false),
+        ]
+    });
+    bundle_instruction(
+        accounts,
+        escrow2::instruction::TakeBundle {},
+        offered_legs.chain(requested_legs),
+    )
+}
+
+//Returns a bundle to the maker. `offered` must be in the order of the bundle's offered legs.
+pub fn refund_bundle(maker: &Pubkey, seed: u64, offered: &[BundleMint]) -> Instruction {
+    let bundle = bundle_address(maker, seed).0;
+    let accounts = escrow2::accounts::RefundBundle {
+        maker: *maker,
+        bundle,
+        system_program: system_program::ID,
+        associated_token_program: associated_token::ID,
+    };
+    let legs = offered
+        .iter()
+        .flat_map(|mint| mint.offered_leg(&bundle, maker));
+    bundle_instruction(accounts, escrow2::instruction::RefundBundle {}, legs)
+}
+
+//Returns an expired bundle to the maker. Anyone can send it, `payer` covers the maker's token accounts that are missing.
+pub fn refund_bundle_expired(
+    maker: &Pubkey,
+    payer: &Pubkey,
+    seed: u64,
+    offered: &[BundleMint],
+) -> Instruction {
+    let bundle = bundle_address(maker, seed).0;
+    let accounts = escrow2::accounts::RefundBundleExpired {
+        payer: *payer,
+        maker: *maker,
+        bundle,
+        system_program: system_program::ID,
+        associated_token_program: associated_token::ID,
+    };
+    let legs = offered
+        .iter()
+        .flat_map(|mint| mint.offered_leg(&bundle, maker));
+    bundle_instruction(accounts, escrow2::instruction::RefundBundleExpired {}, legs)
+}
+
+//Flags an arbitrated escrow as disputed. `party` is its maker or its recipient.
+pub fn raise_dispute(keys: &EscrowKeys, party: &Pubkey) -> Instruction {
+    let accounts = escrow2::accounts::RaiseDispute {
+        party: *party,
+        escrow: keys.escrow(),
+    };
+    instruction(accounts, escrow2::instruction::RaiseDispute {})
+}
+
+//Accounts of release and arbiter_refund. The maker's token account is always passed since it receives anything sent to the vault after the make.
+fn arbitrate_accounts(
+    keys: &EscrowKeys,
+    arbitration: &ArbiterTerms,
+    release: bool,
+) -> escrow2::accounts::Arbitrate {
+    escrow2::accounts::Arbitrate {
+        arbiter: arbitration.arbiter,
+        maker: keys.maker,
+        recipient: arbitration.recipient,
+        mint_a: keys.mint_a,
+        recipient_ata_a: release.then(|| keys.ata_a(&arbitration.recipient)),
+        maker_ata_a: Some(keys.ata_a(&keys.maker)),
+        escrow: keys.escrow(),
+        vault: keys.vault(),
+        token_program: keys.token_program_a,
+        associated_token_program: associated_token::ID,
+        system_program: system_program::ID,
+    }
+}
+
+//Sends the deposit of an arbitrated escrow to its recipient. Signed by the arbiter.
+pub fn release(keys: &EscrowKeys, arbitration: &ArbiterTerms) -> Instruction {
+    let accounts = arbitrate_accounts(keys, arbitration, true);
+    instruction(accounts, escrow2::instruction::Release {})
+}
+
+//Sends the deposit of an arbitrated escrow back to its maker. Signed by the arbiter.
+pub fn arbiter_refund(keys: &EscrowKeys, arbitration: &ArbiterTerms) -> Instruction {
+    let accounts = arbitrate_accounts(keys, arbitration, false);
+    instruction(accounts, escrow2::instruction::ArbiterRefund {})
+}
+
+//Takes back an undisputed arbitrated escrow its arbiter did not settle by its deadline. Wrapped SOL is unwrapped to the maker.
+pub fn reclaim(keys: &EscrowKeys) -> Instruction {
+    let accounts = escrow2::accounts::Reclaim {
+        maker: keys.maker,
+        mint_a: keys.mint_a,
+        maker_ata_a: (!is_native_mint(&keys.mint_a)).then(|| keys.ata_a(&keys.maker)),
+        escrow: keys.escrow(),
+        vault: keys.vault(),
+        system_program: system_program::ID,
+        token_program: keys.token_program_a,
+        associated_token_program: associated_token::ID,
+    };
+    instruction(accounts, escrow2::instruction::Reclaim {})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_spl::token_2022::spl_token_2022;
+
+    fn keys(mint_a: Pubkey, mint_b: Pubkey) -> EscrowKeys {
+        EscrowKeys {
+            maker: Pubkey::new_unique(),
+            seed: 42,
+            mint_a,
+            mint_b,
+            token_program_a: spl_token::ID,
+            token_program_b: spl_token_2022::ID,
+            market_page: Some(0),
+            price_feed: None,
+        }
+    }
+
+    fn contains(ix: &Instruction, key: &Pubkey) -> bool {
+        ix.accounts.iter().any(|meta| meta.pubkey == *key)
+    }
+
+    //Optional accounts that are left out are passed as the program id.
+    fn left_out(ix: &Instruction) -> usize {
+        ix.accounts
+            .iter()
+            .filter(|meta| meta.pubkey == escrow2::ID)
+            .count()
+    }
+
+    #[test]
+    fn make_derives_escrow_and_vault() {
+        let keys = keys(Pubkey::new_unique(), Pubkey::new_unique());
+        let ix = make(&keys, 100, 50, i64::MAX, None);
+
+        let escrow = Pubkey::find_program_address(
+            &[b"escrow", keys.maker.as_ref(), &42u64.to_le_bytes()],
+            &escrow2::ID,
+        )
+        .0;
+        assert_eq!(ix.program_id, escrow2::ID);
+        assert!(contains(&ix, &escrow));
+        assert!(contains(
+            &ix,
+            &get_associated_token_address_with_program_id(&escrow, &keys.mint_a, &spl_token::ID)
+        ));
+        assert!(contains(
+            &ix,
+            &get_associated_token_address_with_program_id(
+                &keys.maker,
+                &keys.mint_a,
+                &spl_token::ID
+            )
+        ));
+        assert_eq!(left_out(&ix), 1); //Only the metadata account of NFT offers.
+    }
+
+    #[test]
+    fn make_allocates_seeds_through_the_maker_state() {
+        let keys = EscrowKeys {
+            seed: 1,
+            ..keys(Pubkey::new_unique(), Pubkey::new_unique())
+        };
+        let ix = make(&keys, 100, 50, i64::MAX, None);
+        assert!(contains(&ix, &maker_state_address(&keys.maker).0));
+
+        let listed: Vec<_> = crate::pda::maker_escrow_addresses(&keys.maker, 2).collect();
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[1], (1, keys.escrow()));
+    }
+
+    #[test]
+    fn fills_update_the_stats_of_both_parties() {
+        let keys = keys(Pubkey::new_unique(), Pubkey::new_unique());
+        let taker = Pubkey::new_unique();
+        let ix = take_partial(&keys, &taker, &Pubkey::new_unique(), 10, 0, u64::MAX);
+        assert!(contains(&ix, &stats_address(&keys.maker).0));
+        assert!(contains(&ix, &stats_address(&taker).0));
+
+        assert!(contains(&refund(&keys), &stats_address(&keys.maker).0));
+        assert!(contains(
+            &refund_expired(&keys, &taker),
+            &stats_address(&keys.maker).0
+        ));
+    }
+
+    #[test]
+    fn take_vested_locks_mint_a_in_the_vesting_vault() {
+        let keys = keys(Pubkey::new_unique(), Pubkey::new_unique());
+        let taker = Pubkey::new_unique();
+        let ix = take_vested(&keys, &taker, &Pubkey::new_unique(), 0, u64::MAX);
+
+        let vesting = vesting_address(&keys.escrow(), &taker).0;
+        assert!(contains(&ix, &vesting));
+        assert!(contains(
+            &ix,
+            &vault_address(&vesting, &keys.mint_a, &spl_token::ID)
+        ));
+        assert_eq!(
+            left_out(&take(&keys, &taker, &Pubkey::new_unique(), 0, u64::MAX)) - left_out(&ix),
+            2
+        );
+    }
+
+    #[test]
+    fn arbiters_return_the_surplus_to_the_maker() {
+        let keys = keys(Pubkey::new_unique(), Pubkey::new_unique());
+        let terms = ArbiterTerms {
+            arbiter: Pubkey::new_unique(),
+            recipient: Pubkey::new_unique(),
+        };
+        let recipient_ata_a = keys.ata_a(&terms.recipient);
+        let maker_ata_a = keys.ata_a(&keys.maker);
+
+        let ix = make_arbitrated(&keys, 100, terms, i64::MAX);
+        assert!(contains(&ix, &keys.vault()));
+        assert!(!contains(&ix, &keys.mint_b)); //Nothing is asked in return, so nothing is listed either.
+
+        let ix = release(&keys, &terms);
+        assert!(contains(&ix, &recipient_ata_a));
+        assert!(contains(&ix, &maker_ata_a)); //Anything sent to the vault after the make goes back to the maker.
+        assert_eq!(left_out(&ix), 0);
+
+        let ix = arbiter_refund(&keys, &terms);
+        assert!(contains(&ix, &maker_ata_a));
+        assert!(!contains(&ix, &recipient_ata_a));
+        assert_ne!(ix.data, release(&keys, &terms).data);
+
+        assert!(contains(
+            &raise_dispute(&keys, &terms.recipient),
+            &keys.escrow()
+        ));
+        assert!(contains(&reclaim(&keys), &maker_ata_a));
+    }
+
+    #[test]
+    fn native_legs_leave_token_accounts_out() {
+        let keys = keys(spl_token::native_mint::ID, Pubkey::new_unique());
+        assert_eq!(left_out(&make(&keys, 100, 50, i64::MAX, None)), 2);
+        assert_eq!(left_out(&refund(&keys)), 1);
+        assert_eq!(
+            left_out(&take(
+                &keys,
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                0,
+                u64::MAX
+            )),
+            5
+        ); //maker_ata_a, vesting, vesting_vault, metadata and price_feed.
+
+        let keys = EscrowKeys {
+            mint_b: spl_token_2022::native_mint::ID,
+            ..keys
+        };
+        let ix = take(
+            &keys,
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            0,
+            u64::MAX,
+        );
+        assert_eq!(left_out(&ix), 8); //maker_ata_a, taker_ata_b, maker_ata_b, treasury_ata_b, vesting, vesting_vault, metadata and price_feed.
+    }
+
+    #[test]
+    fn refund_many_passes_six_accounts_per_escrow() {
+        let first = keys(Pubkey::new_unique(), Pubkey::new_unique());
+        let second = EscrowKeys {
+            seed: 43,
+            market_page: None,
+            ..first
+        };
+        let ix = refund_many(&first.maker, &[first, second]);
+
+        assert_eq!(ix.accounts.len(), 4 + 2 * 6);
+        assert!(contains(&ix, &first.escrow()));
+        assert!(contains(&ix, &second.vault()));
+        assert_eq!(left_out(&ix), 1); //The market page of the migrated escrow.
+    }
+
+    #[test]
+    fn take_and_take_partial_share_accounts() {
+        let keys = keys(Pubkey::new_unique(), Pubkey::new_unique());
+        let taker = Pubkey::new_unique();
+        let treasury = Pubkey::new_unique();
+
+        let full = take(&keys, &taker, &treasury, 0, u64::MAX);
+        let partial = take_partial(&keys, &taker, &treasury, 10, 0, u64::MAX);
+        assert_eq!(full.accounts, partial.accounts);
+        assert_ne!(full.data, partial.data);
+        assert!(contains(&full, &config_address().0));
+    }
+
+    #[test]
+    fn pegged_offers_pass_their_price_feed_to_take() {
+        let price_feed = Pubkey::new_unique();
+        let oracle = OraclePeg {
+            price_feed,
+            spread_bps: 50,
+            max_staleness: 60,
+            max_confidence_bps: 100,
+        };
+        let escrow = Escrow {
+            maker: Pubkey::new_unique(),
+            mint_a: Pubkey::new_unique(),
+            mint_b: Pubkey::new_unique(),
+            offer_amount: 50,
+            seed: 7,
+            escrow_bump: 254,
+            remaining_a: 100,
+            remaining_b: 50,
+            expires_at: i64::MAX,
+            allowed_taker: None,
+            market_page: Some(0),
+            nft: false,
+            oracle: Some(oracle),
+            auction: None,
+            vesting: None,
+            arbitration: None,
+            status: escrow2::state::EscrowStatus::Open,
+        };
+        let keys = EscrowKeys::from_escrow(&escrow, spl_token::ID, spl_token::ID);
+        assert_eq!(keys.price_feed, Some(price_feed));
+        assert!(contains(
+            &take(
+                &keys,
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                0,
+                u64::MAX
+            ),
+            &price_feed
+        ));
+
+        let plain = make(&keys, 100, 50, i64::MAX, None);
+        let pegged = make_pegged(&keys, 100, 50, i64::MAX, oracle);
+        assert_eq!(plain.accounts, pegged.accounts); //The feed is only read on take.
+        assert_ne!(plain.data, pegged.data);
+    }
+
+    #[test]
+    fn nft_offers_pass_metadata_and_creators() {
+        let keys = keys(Pubkey::new_unique(), Pubkey::new_unique());
+        let metadata = metadata_address(&keys.mint_a);
+        assert!(contains(
+            &make_nft(&keys, 50, i64::MAX, None, None),
+            &metadata
+        ));
+
+        let taker = Pubkey::new_unique();
+        let treasury = Pubkey::new_unique();
+        let creators = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let plain = take(&keys, &taker, &treasury, 0, u64::MAX);
+        let ix = take_nft(&keys, &taker, &treasury, &creators, 0, u64::MAX);
+        assert!(contains(&ix, &metadata));
+        assert_eq!(ix.accounts.len(), plain.accounts.len() + 4); //Wallet and mint_b account of each creator.
+
+        let keys = EscrowKeys {
+            mint_b: spl_token::native_mint::ID,
+            ..keys
+        };
+        let plain = take(&keys, &taker, &treasury, 0, u64::MAX);
+        let ix = take_nft(&keys, &taker, &treasury, &creators, 0, u64::MAX);
+        assert_eq!(ix.accounts.len(), plain.accounts.len() + 2); //Native SOL royalties go straight to the wallets.
+    }
+
+    #[test]
+    fn counter_offers_share_the_counter_accounts() {
+        let keys = keys(Pubkey::new_unique(), spl_token::native_mint::ID);
+        let proposer = Pubkey::new_unique();
+        let (counter, counter_vault) = keys.counter(&proposer);
+        assert_eq!(
+            counter,
+            Pubkey::find_program_address(
+                &[b"counter", keys.escrow().as_ref(), proposer.as_ref()],
+                &escrow2::ID
+            )
+            .0
+        );
+
+        let propose = counter_offer(&keys, &proposer, 10);
+        let accept = accept_counter(&keys, &proposer, &Pubkey::new_unique());
+        let stored = CounterOffer {
+            escrow: keys.escrow(),
+            proposer,
+            mint_b: keys.mint_b,
+            receive: 10,
+            remaining_a: 100,
+            counter_bump: 255,
+        };
+        let cancel = cancel_counter(&stored, &keys.token_program_b);
+        for ix in [&propose, &accept, &cancel] {
+            assert!(contains(ix, &counter));
+            assert!(contains(ix, &counter_vault)); //Wrapped SOL counter-offers still sit in a token account.
+        }
+        assert!(!contains(&cancel, &keys.escrow()));
+        assert!(contains(&accept, &stats_address(&proposer).0));
+    }
+
+    #[test]
+    fn bundle_legs_follow_the_fixed_accounts() {
+        let maker = Pubkey::new_unique();
+        let mint = |token_program| BundleMint {
+            mint: Pubkey::new_unique(),
+            token_program,
+        };
+        let offered = [mint(spl_token::ID), mint(spl_token_2022::ID)];
+        let requested = [mint(spl_token::ID)];
+
+        let ix = take_bundle(
+            &maker,
+            3,
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &offered,
+            &requested,
+        );
+        assert_eq!(ix.accounts.len(), 7 + 2 * 5 + 5);
+        assert_eq!(ix.accounts[7].pubkey, offered[0].mint);
+        assert_eq!(ix.accounts[17].pubkey, requested[0].mint);
+        assert!(contains(&ix, &offered[0].ata(&maker)));
+
+        let bundle = bundle_address(&maker, 3).0;
+        let ix = refund_bundle(&maker, 3, &offered);
+        assert!(contains(&ix, &offered[1].ata(&bundle)));
+        assert!(contains(&ix, &offered[1].ata(&maker)));
+        let crank = Pubkey::new_unique();
+        let expired = refund_bundle_expired(&maker, &crank, 3, &offered);
+        assert_eq!(expired.accounts.len(), ix.accounts.len() + 1);
+        assert!(contains(&expired, &crank));
+    }
+}
@@ -0,0 +1,22 @@
+//Rust client for the escrow2 program. Account lists and instruction data come from the structs Anchor generates for the
+//program itself, so a change to an instruction breaks this crate at compile time instead of on-chain.
+
+pub mod accounts;
+pub mod instructions;
+pub mod pda;
+
+pub use accounts::*;
+pub use instructions::*;
+pub use pda::*;
+
+pub use escrow2::{
+    error::EscrowError,
+    events,
+    state::{
+        ArbiterTerms, AuctionTerms, Bundle, BundleLeg, Config, CounterOffer, Escrow, EscrowStatus,
+        MakeArgs, MakerState, MarketPage, MintVolume, OfferKind, OraclePeg, Settlement,
+        TraderStats, Vesting, VestingTerms,
+    },
+    utils::is_native_mint,
+    ID,
+};
@@ -0,0 +1,94 @@
+use anchor_lang::{prelude::Pubkey, solana_program::bpf_loader_upgradeable};
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use escrow2::constants::TOKEN_METADATA_PROGRAM_ID;
+
+//The program wide config, derived from b"config".
+pub fn config_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"config"], &escrow2::ID)
+}
+
+//The ProgramData account of escrow2, whose upgrade authority is the only key that can initialize the config.
+pub fn program_data_address() -> Pubkey {
+    Pubkey::find_program_address(&[escrow2::ID.as_ref()], &bpf_loader_upgradeable::ID).0
+}
+
+//The escrow of `maker` created with `seed`, derived the same way as in Make.
+pub fn escrow_address(maker: &Pubkey, seed: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"escrow", maker.as_ref(), &seed.to_le_bytes()],
+        &escrow2::ID,
+    )
+}
+
+//The seed allocator of `maker`. Its next_seed is the seed to pass to the maker's next make.
+pub fn maker_state_address(maker: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"maker", maker.as_ref()], &escrow2::ID)
+}
+
+//Escrows `maker` made through its maker state, by seed. Seeds that were skipped, filled or refunded have no account.
+pub fn maker_escrow_addresses(
+    maker: &Pubkey,
+    next_seed: u64,
+) -> impl Iterator<Item = (u64, Pubkey)> + '_ {
+    (0..next_seed).map(move |seed| (seed, escrow_address(maker, seed).0))
+}
+
+//The trading history of `wallet`, updated by its makes, takes and refunds. Wallets that never traded have no account.
+pub fn stats_address(wallet: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"stats", wallet.as_ref()], &escrow2::ID)
+}
+
+//The bundle of `maker` created with `seed`. Bundles and escrows can share a seed, they use different prefixes.
+pub fn bundle_address(maker: &Pubkey, seed: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"bundle", maker.as_ref(), &seed.to_le_bytes()],
+        &escrow2::ID,
+    )
+}
+
+//The counter-offer of `proposer` on `escrow`. A proposer has at most one open counter-offer per escrow.
+pub fn counter_address(escrow: &Pubkey, proposer: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"counter", escrow.as_ref(), proposer.as_ref()],
+        &escrow2::ID,
+    )
+}
+
+//The vesting of `beneficiary` for what it bought from the vested offer `escrow`. Its vault holds the locked mint_a.
+pub fn vesting_address(escrow: &Pubkey, beneficiary: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"vesting", escrow.as_ref(), beneficiary.as_ref()],
+        &escrow2::ID,
+    )
+}
+
+//The vault is the escrow's associated token account for mint_a. Bundle, counter-offer and vesting vaults are derived the same way from their own address.
+pub fn vault_address(escrow: &Pubkey, mint_a: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(escrow, mint_a, token_program)
+}
+
+//Page `page` of the index of open escrows offering mint_a for mint_b.
+pub fn market_page_address(mint_a: &Pubkey, mint_b: &Pubkey, page: u32) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            b"market",
+            mint_a.as_ref(),
+            mint_b.as_ref(),
+            &page.to_le_bytes(),
+        ],
+        &escrow2::ID,
+    )
+}
+
+//The Metaplex Token Metadata account of `mint`, passed to make and take for NFT offers.
+pub fn metadata_address(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"metadata",
+            TOKEN_METADATA_PROGRAM_ID.as_ref(),
+            mint.as_ref(),
+        ],
+        &TOKEN_METADATA_PROGRAM_ID,
+    )
+    .0
+}
@@ -1,4 +1,38 @@
+use anchor_lang::prelude::Pubkey;
+
 pub const ANCHOR_DISCRIMINATOR_BYTES: usize = 8;
 pub const U8_BYTES: usize = 1;
+pub const I16_BYTES: usize = 2;
+pub const U16_BYTES: usize = 2;
+pub const U32_BYTES: usize = 4;
 pub const U64_BYTES: usize = 8;
-pub const PUBKEY_BYTES: usize = 32;
\ No newline at end of file
+pub const I64_BYTES: usize = 8;
+pub const PUBKEY_BYTES: usize = 32;
+
+pub const BPS_DENOMINATOR: u16 = 10_000; // 100% expressed in basis points
+pub const MAX_FEE_BPS: u16 = 1_000; // Highest protocol fee the config accepts, 10% of the mint_b leg of a fill
+pub const MARKET_PAGE_CAPACITY: usize = 32; // Open escrows listed on a single market page
+pub const MAX_BUNDLE_LEGS: usize = 4; // Most mints a bundle can offer, and most it can ask for
+pub const BUNDLE_OFFERED_LEG_ACCOUNTS: usize = 4; // Remaining accounts per offered leg: mint, token program, vault and the receiver's token account
+pub const BUNDLE_TAKEN_LEG_ACCOUNTS: usize = 5; // Remaining accounts per offered leg of take_bundle: the offered leg accounts and the maker's token account for tokens sent to the vault after make_bundle
+pub const BUNDLE_REQUESTED_LEG_ACCOUNTS: usize = 5; // Remaining accounts per requested leg: mint, token program, taker, maker and treasury token accounts
+pub const MAX_STATS_MINTS: usize = 8; // Mints a trader's volume is kept for, fills in later mints still count towards the offer and fill totals
+pub const REFUND_MANY_ESCROW_ACCOUNTS: usize = 6; // Remaining accounts per escrow refunded by refund_many: escrow, vault, mint_a, token program, the maker's token account and the market page
+
+pub const TOKEN_METADATA_PROGRAM_ID: Pubkey = Pubkey::new_from_array([
+    11, 112, 101, 177, 227, 209, 124, 69, 56, 157, 82, 127, 107, 4, 195, 205, 88, 184, 108, 115, 26, 160, 253, 181, 73, 182, 209, 188, 3, 248, 41, 70,
+]); // Metaplex Token Metadata program, metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s
+pub const METADATA_V1_KEY: u8 = 4; // First byte of a Token Metadata account holding NFT metadata
+pub const CREATOR_SHARES_TOTAL: u64 = 100; // Metaplex creator shares are percentages
+
+pub const PYTH_ORACLE_PROGRAM_ID: Pubkey = Pubkey::new_from_array([
+    220, 229, 235, 225, 228, 156, 59, 159, 17, 76, 181, 84, 76, 80, 169, 158, 192, 214, 146, 214, 63, 86, 121, 90, 224, 41, 172, 131, 217, 234, 139, 226,
+]); // Pyth oracle program, FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH
+pub const PYTH_MAGIC: u32 = 0xa1b2c3d4; // First field of every Pyth account
+pub const PYTH_VERSION: u32 = 2; // Account layout version read by the program
+pub const PYTH_PRICE_ACCOUNT_TYPE: u32 = 3; // Account type of Pyth price accounts
+pub const PYTH_STATUS_TRADING: u32 = 1; // Aggregate status of a price that can be used
+pub const PYTH_EXPONENT_OFFSET: usize = 20; // Offset of the i32 price exponent in a Pyth price account
+pub const PYTH_PUBLISH_TIME_OFFSET: usize = 96; // Offset of the i64 unix timestamp of the aggregate price
+pub const PYTH_AGGREGATE_OFFSET: usize = 208; // Offset of the aggregate price: i64 price, u64 confidence, u32 status
+pub const PYTH_PRICE_ACCOUNT_MIN_LEN: usize = 240; // Bytes of a Pyth price account the program reads
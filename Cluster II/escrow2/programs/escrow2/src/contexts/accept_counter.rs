@@ -0,0 +1,287 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    error::EscrowError,
+    events::CounterAccepted,
+    state::{Config, CounterOffer, Escrow, MarketPage, TraderStats},
+    utils::{close_vault, is_native_mint, protocol_fee},
+};
+
+#[derive(Accounts)] // Anchor macro that prepares the struct to hold account information for the accept_counter instruction.
+pub struct AcceptCounter<'info> {
+    #[account(mut)] //Pays for missing token accounts and receives the rent of the escrow and its vault.
+    pub maker: Signer<'info>,
+
+    #[account(mut)] //Receives the rent of the counter-offer and its vault.
+    pub proposer: SystemAccount<'info>,
+
+    #[account(
+        mut, //See harvest_withheld_fees.
+        mint::token_program = token_program_a,
+    )]
+    pub mint_a: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut, //See harvest_withheld_fees.
+        mint::token_program = token_program_b,
+    )]
+    pub mint_b: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = maker, //The maker accepts, so the maker pays for the proposer's account.
+        associated_token::mint = mint_a,
+        associated_token::authority = proposer,
+        associated_token::token_program = token_program_a,
+    )]
+    pub proposer_ata_a: InterfaceAccount<'info, TokenAccount>, //Receives everything left in the vault. Wrapped SOL stays wrapped since the proposer does not sign.
+
+    #[account(
+        init_if_needed,
+        payer = maker,
+        associated_token::mint = mint_b,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program_b,
+    )]
+    pub maker_ata_b: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = maker,
+        associated_token::mint = mint_a,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program_a,
+    )]
+    pub maker_ata_a: Option<InterfaceAccount<'info, TokenAccount>>, //Receives tokens sent to the vault on top of the deposit. Left out when mint_a is wrapped SOL, the surplus is then unwrapped to the maker with the vault.
+
+    #[account(
+        mut,
+        close = maker,
+        has_one = maker, //Only the maker can accept a counter-offer on its escrow.
+        has_one = mint_a,
+        has_one = mint_b,
+        seeds = [b"escrow", maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.escrow_bump
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program_a,
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        close = proposer,
+        has_one = escrow, //The counter-offer must have been made on this escrow.
+        has_one = proposer,
+        seeds = [b"counter", escrow.key().as_ref(), proposer.key().as_ref()],
+        bump = counter_offer.counter_bump,
+    )]
+    pub counter_offer: Account<'info, CounterOffer>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = counter_offer,
+        associated_token::token_program = token_program_b,
+    )]
+    pub counter_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"config"], //The program wide config holding the protocol fee.
+        bump,
+    )]
+    /// CHECK: The address is checked above, Config::fee_bps checks the owner and the discriminator before reading it.
+    pub config: UncheckedAccount<'info>, //Not created until the admin runs initialize_config, fills are free until then.
+
+    /// CHECK: Compared against config.treasury by Config::fee_bps and used as a token account authority.
+    pub treasury: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed, //Created on the first fill that pays a fee in this mint.
+        payer = maker,
+        associated_token::mint = mint_b,
+        associated_token::authority = treasury,
+        associated_token::token_program = token_program_b,
+    )]
+    pub treasury_ata_b: Option<InterfaceAccount<'info, TokenAccount>>, //Receives the protocol fee. Can be left out when the fee is zero.
+
+    #[account(
+        mut, //The escrow is unlisted once the counter-offer fills it.
+        seeds = [b"market", mint_a.key().as_ref(), mint_b.key().as_ref(), market_page.page.to_le_bytes().as_ref()],
+        bump = market_page.page_bump,
+    )]
+    pub market_page: Option<Account<'info, MarketPage>>, //Index page listing the escrow. Left out for migrated escrows, which were never listed.
+
+    #[account(
+        init_if_needed,
+        payer = maker,
+        space = TraderStats::LEN,
+        seeds = [b"stats", maker.key().as_ref()],
+        bump,
+    )]
+    pub maker_stats: Account<'info, TraderStats>, //Records the filled offer on the maker's trading history.
+
+    #[account(
+        init_if_needed,
+        payer = maker, //The proposer does not sign, so the maker pays for the proposer's stats like for its token account.
+        space = TraderStats::LEN,
+        seeds = [b"stats", proposer.key().as_ref()],
+        bump,
+    )]
+    pub proposer_stats: Account<'info, TraderStats>, //Records the fill on the proposer's trading history. The same account as maker_stats when makers accept their own counter-offer.
+
+    pub token_program_a: Interface<'info, TokenInterface>,
+    pub token_program_b: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> AcceptCounter<'info> {
+    //Pays the maker from the counter vault, less the protocol fee, and closes the counter vault to the proposer. Returns the amount released and the fee.
+    pub fn settle_counter(&mut self) -> Result<(u64, u64)> {
+        require!(
+            self.escrow.remaining_a == self.counter_offer.remaining_a,
+            EscrowError::StaleCounterOffer
+        ); //A fill or an update since the counter-offer changed what the proposer would get.
+
+        let amount_b = self.counter_vault.amount;
+        let fee = protocol_fee(amount_b, Config::fee_bps(&self.config, &self.treasury.key())?).ok_or(EscrowError::InvalidFee)?;
+        let to_maker = amount_b.checked_sub(fee).ok_or(EscrowError::ArithmeticOverflow)?;
+
+        self.transfer_b(self.maker_ata_b.to_account_info(), to_maker)?;
+        if fee > 0 {
+            let treasury_ata_b = self
+                .treasury_ata_b
+                .as_ref()
+                .ok_or(EscrowError::TreasuryAccountRequired)?;
+            self.transfer_b(treasury_ata_b.to_account_info(), fee)?;
+        }
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"counter",
+            self.counter_offer.escrow.as_ref(),
+            self.proposer.to_account_info().key.as_ref(),
+            &[self.counter_offer.counter_bump],
+        ]];
+        close_vault(
+            &self.counter_offer.to_account_info(),
+            &signer_seeds,
+            &self.mint_b.to_account_info(),
+            &self.counter_vault.to_account_info(),
+            &self.proposer.to_account_info(),
+            &self.token_program_b.to_account_info(),
+        )?;
+
+        Ok((amount_b, fee))
+    }
+
+    //Moves mint_b out of the counter vault, signed by the counter-offer PDA.
+    fn transfer_b(&self, to: AccountInfo<'info>, amount: u64) -> Result<()> {
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"counter",
+            self.counter_offer.escrow.as_ref(),
+            self.proposer.to_account_info().key.as_ref(),
+            &[self.counter_offer.counter_bump],
+        ]];
+
+        let transfer_accounts = TransferChecked {
+            from: self.counter_vault.to_account_info(),
+            mint: self.mint_b.to_account_info(),
+            to,
+            authority: self.counter_offer.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(self.token_program_b.to_account_info(), transfer_accounts, &signer_seeds);
+        transfer_checked(cpi_ctx, amount, self.mint_b.decimals)
+            .map_err(|_| error!(EscrowError::DepositFailed))
+    }
+
+    //Sends what is left of the deposit to the proposer, returns any surplus to the maker and closes the vault to the maker. Returns the amount sent.
+    pub fn release_vault(&mut self) -> Result<u64> {
+        let amount_a = self.escrow.remaining_a; //Tokens sent to the vault after make are not part of the offer.
+        let surplus = self.vault.amount.checked_sub(amount_a).ok_or(EscrowError::ArithmeticOverflow)?;
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"escrow",
+            self.maker.to_account_info().key.as_ref(),
+            &self.escrow.seed.to_le_bytes()[..],
+            &[self.escrow.escrow_bump],
+        ]];
+
+        let transfer_accounts = TransferChecked {
+            from: self.vault.to_account_info(),
+            mint: self.mint_a.to_account_info(),
+            to: self.proposer_ata_a.to_account_info(),
+            authority: self.escrow.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(self.token_program_a.to_account_info(), transfer_accounts, &signer_seeds);
+        transfer_checked(cpi_ctx, amount_a, self.mint_a.decimals)
+            .map_err(|_| error!(EscrowError::WithdrawFailed))?;
+
+        if surplus > 0 {
+            match &self.maker_ata_a {
+                Some(maker_ata_a) => {
+                    let transfer_accounts = TransferChecked {
+                        from: self.vault.to_account_info(),
+                        mint: self.mint_a.to_account_info(),
+                        to: maker_ata_a.to_account_info(),
+                        authority: self.escrow.to_account_info(),
+                    };
+                    let cpi_ctx = CpiContext::new_with_signer(self.token_program_a.to_account_info(), transfer_accounts, &signer_seeds);
+                    transfer_checked(cpi_ctx, surplus, self.mint_a.decimals)
+                        .map_err(|_| error!(EscrowError::RefundFailed))?;
+                }
+                None => require!(is_native_mint(&self.mint_a.key()), EscrowError::NativeMintRequired), //Unwrapped to the maker when the vault is closed.
+            }
+        }
+
+        close_vault(
+            &self.escrow.to_account_info(),
+            &signer_seeds,
+            &self.mint_a.to_account_info(),
+            &self.vault.to_account_info(),
+            &self.maker.to_account_info(),
+            &self.token_program_a.to_account_info(),
+        )?;
+
+        Ok(amount_a)
+    }
+
+    //Adds the accepted counter-offer to the trading history of both parties, as a fill that leaves nothing of the offer.
+    pub fn record_fill(&mut self, amount_a: u64, amount_b: u64, maker_bump: u8, proposer_bump: u8) -> Result<()> {
+        self.maker_stats.open(self.maker.key(), maker_bump);
+        self.maker_stats.offers_filled = self.maker_stats.offers_filled.checked_add(1).ok_or(EscrowError::ArithmeticOverflow)?;
+        self.maker_stats.add_volume(self.mint_a.key(), amount_a);
+        self.maker_stats.add_volume(self.mint_b.key(), amount_b);
+
+        if self.proposer_stats.key() == self.maker_stats.key() {
+            *self.proposer_stats = (*self.maker_stats).clone(); //Both are written back on exit, proposer_stats last, so it has to carry the maker's side too.
+        }
+        self.proposer_stats.open(self.proposer.key(), proposer_bump);
+        self.proposer_stats.fills_taken = self.proposer_stats.fills_taken.checked_add(1).ok_or(EscrowError::ArithmeticOverflow)?;
+        self.proposer_stats.add_volume(self.mint_a.key(), amount_a);
+        self.proposer_stats.add_volume(self.mint_b.key(), amount_b);
+        Ok(())
+    }
+
+    pub fn emit_accepted(&self, amount_a: u64, amount_b: u64, fee: u64) -> Result<()> {
+        emit!(CounterAccepted {
+            counter_offer: self.counter_offer.key(),
+            escrow: self.escrow.key(),
+            maker: self.maker.key(),
+            proposer: self.proposer.key(),
+            amount_a,
+            amount_b,
+            fee,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,163 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    error::EscrowError,
+    events::EscrowSettled,
+    state::{Escrow, EscrowStatus, Settlement},
+    utils::close_vault,
+};
+
+#[derive(Accounts)] // Anchor macro that prepares the struct to hold account information for the release and arbiter_refund instructions.
+pub struct Arbitrate<'info> {
+    #[account(mut)] //Pays for the token account of whoever the escrow is settled to if it does not exist yet.
+    pub arbiter: Signer<'info>,
+
+    #[account(mut)] //Receives the rent of the escrow and its vault whichever way it is settled.
+    pub maker: SystemAccount<'info>,
+
+    pub recipient: SystemAccount<'info>, //The recipient named at make.
+
+    #[account(
+        mut, //See harvest_withheld_fees.
+        mint::token_program = token_program, //mint_a must belong to the token program passed in.
+    )]
+    pub mint_a: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed, //The recipient may never have held mint_a.
+        payer = arbiter,
+        associated_token::mint = mint_a,
+        associated_token::authority = recipient,
+        associated_token::token_program = token_program,
+    )]
+    pub recipient_ata_a: Option<InterfaceAccount<'info, TokenAccount>>, //Receives the vault on release. Can be left out for arbiter_refund.
+
+    #[account(
+        init_if_needed, //The maker may have closed it since the make.
+        payer = arbiter,
+        associated_token::mint = mint_a,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program,
+    )]
+    pub maker_ata_a: Option<InterfaceAccount<'info, TokenAccount>>, //Receives the deposit on arbiter_refund and anything sent to the vault after the make. Can be left out for release when there is nothing extra.
+
+    #[account(
+        mut,
+        close = maker, //Rent goes back to the maker who funded it, not the arbiter. A closed escrow fails to load, so it can never be settled twice.
+        has_one = maker, //Ensures the maker passed in is the one stored in the escrow account.
+        has_one = mint_a, //Ensures the escrow account is associated with the mint_a token mint.
+        seeds = [b"escrow", maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()], //Used for deriving the account's address using the PDA mechanism.
+        bump = escrow.escrow_bump,
+        constraint = escrow.arbitration.map(|terms| terms.arbiter) == Some(arbiter.key()) @ EscrowError::NotArbiter, //Swaps have no arbiter at all.
+        constraint = escrow.arbitration.map(|terms| terms.recipient) == Some(recipient.key()) @ EscrowError::RecipientMismatch,
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(
+        mut, //Indicates mutability.
+        associated_token::mint = mint_a, //Links the vault to the mint_a token mint.
+        associated_token::authority = escrow, //Sets the escrow account as the authority of the vault.
+        associated_token::token_program = token_program, //The vault belongs to the token program that owns mint_a.
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>, //The vault account where the maker's tokens are held.
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> Arbitrate<'info> {
+    //Sends the deposit to the recipient when `settlement` is Released, or back to the maker when it is Refunded.
+    pub fn settle(&mut self, settlement: Settlement) -> Result<()> {
+        let disputed = self.escrow.status == EscrowStatus::Disputed;
+
+        let amount = self.escrow.remaining_a; //What reached the vault at make. Anything sent to it since goes back to the maker in close_vault.
+        emit!(EscrowSettled {
+            escrow: self.escrow.key(),
+            arbiter: self.arbiter.key(),
+            maker: self.maker.key(),
+            recipient: self.recipient.key(),
+            settlement,
+            disputed,
+            mint_a: self.mint_a.key(),
+            amount,
+            seed: self.escrow.seed,
+            slot: Clock::get()?.slot,
+        });
+
+        let destination = match settlement {
+            Settlement::Released => self.recipient_ata_a.as_ref(),
+            Settlement::Refunded => self.maker_ata_a.as_ref(),
+        }
+        .ok_or(EscrowError::SettlementAccountRequired)?; //Wrapped SOL is settled as wrapped SOL too.
+
+        let transfer_accounts = TransferChecked {
+            from: self.vault.to_account_info(),
+            mint: self.mint_a.to_account_info(),
+            to: destination.to_account_info(),
+            authority: self.escrow.to_account_info(),
+        };
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"escrow",
+            self.maker.to_account_info().key.as_ref(),
+            &self.escrow.seed.to_le_bytes()[..],
+            &[self.escrow.escrow_bump],
+        ]];
+
+        let cpi_ctx = CpiContext::new_with_signer(self.token_program.to_account_info(), transfer_accounts, &signer_seeds);
+        transfer_checked(cpi_ctx, amount, self.mint_a.decimals).map_err(|_| error!(EscrowError::WithdrawFailed))?;
+        Ok(())
+    }
+
+    //Returns what is left in the vault to the maker and closes it, sending its rent to the maker.
+    pub fn close_vault(&mut self) -> Result<()> {
+        self.vault.reload()?; //What is left after settle was sent to the vault on top of the deposit.
+        let surplus = self.vault.amount;
+        if surplus > 0 {
+            self.sweep_surplus(surplus)?;
+        }
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"escrow",
+            self.maker.to_account_info().key.as_ref(),
+            &self.escrow.seed.to_le_bytes()[..],
+            &[self.escrow.escrow_bump],
+        ]];
+
+        close_vault(
+            &self.escrow.to_account_info(),
+            &signer_seeds,
+            &self.mint_a.to_account_info(),
+            &self.vault.to_account_info(),
+            &self.maker.to_account_info(),
+            &self.token_program.to_account_info(),
+        )
+    }
+
+    //Sends tokens sent to the vault after the make to the maker, like take does.
+    fn sweep_surplus(&mut self, surplus: u64) -> Result<()> {
+        let maker_ata_a = self.maker_ata_a.as_ref().ok_or(EscrowError::SettlementAccountRequired)?;
+
+        let transfer_accounts = TransferChecked {
+            from: self.vault.to_account_info(),
+            mint: self.mint_a.to_account_info(),
+            to: maker_ata_a.to_account_info(),
+            authority: self.escrow.to_account_info(),
+        };
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"escrow",
+            self.maker.to_account_info().key.as_ref(),
+            &self.escrow.seed.to_le_bytes()[..],
+            &[self.escrow.escrow_bump],
+        ]];
+
+        let cpi_ctx = CpiContext::new_with_signer(self.token_program.to_account_info(), transfer_accounts, &signer_seeds);
+        transfer_checked(cpi_ctx, surplus, self.mint_a.decimals).map_err(|_| error!(EscrowError::RefundFailed))
+    }
+}
@@ -0,0 +1,94 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    error::EscrowError,
+    events::CounterCancelled,
+    state::CounterOffer,
+    utils::close_vault,
+};
+
+#[derive(Accounts)] // Anchor macro that prepares the struct to hold account information for the cancel_counter instruction.
+pub struct CancelCounter<'info> { //The escrow is not passed, so a counter-offer can be cancelled after its escrow was filled or refunded.
+    #[account(mut)] //Receives the deposit and the rent of the counter-offer and its vault.
+    pub proposer: Signer<'info>,
+
+    #[account(
+        mut, //See harvest_withheld_fees.
+        mint::token_program = token_program,
+    )]
+    pub mint_b: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed, //The proposer may have closed it since making the counter-offer.
+        payer = proposer,
+        associated_token::mint = mint_b,
+        associated_token::authority = proposer,
+        associated_token::token_program = token_program,
+    )]
+    pub proposer_ata_b: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        close = proposer,
+        has_one = proposer, //Only the proposer can take the deposit back.
+        has_one = mint_b,
+        seeds = [b"counter", counter_offer.escrow.as_ref(), proposer.key().as_ref()],
+        bump = counter_offer.counter_bump,
+    )]
+    pub counter_offer: Account<'info, CounterOffer>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = counter_offer,
+        associated_token::token_program = token_program,
+    )]
+    pub counter_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CancelCounter<'info> {
+    //Returns the whole counter vault to the proposer and closes it.
+    pub fn cancel(&mut self) -> Result<()> {
+        emit!(CounterCancelled {
+            counter_offer: self.counter_offer.key(),
+            escrow: self.counter_offer.escrow,
+            proposer: self.proposer.key(),
+            amount: self.counter_vault.amount,
+            slot: Clock::get()?.slot,
+        });
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"counter",
+            self.counter_offer.escrow.as_ref(),
+            self.proposer.to_account_info().key.as_ref(),
+            &[self.counter_offer.counter_bump],
+        ]];
+
+        let transfer_accounts = TransferChecked {
+            from: self.counter_vault.to_account_info(),
+            mint: self.mint_b.to_account_info(),
+            to: self.proposer_ata_b.to_account_info(),
+            authority: self.counter_offer.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(self.token_program.to_account_info(), transfer_accounts, &signer_seeds);
+        transfer_checked(cpi_ctx, self.counter_vault.amount, self.mint_b.decimals)
+            .map_err(|_| error!(EscrowError::RefundFailed))?;
+
+        close_vault(
+            &self.counter_offer.to_account_info(),
+            &signer_seeds,
+            &self.mint_b.to_account_info(),
+            &self.counter_vault.to_account_info(),
+            &self.proposer.to_account_info(),
+            &self.token_program.to_account_info(),
+        )
+    }
+}
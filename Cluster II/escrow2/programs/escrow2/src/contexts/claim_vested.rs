@@ -0,0 +1,115 @@
+use anchor_lang::{prelude::*, AccountsClose};
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    error::EscrowError,
+    events::VestedClaimed,
+    state::Vesting,
+    utils::close_vault,
+};
+
+#[derive(Accounts)] // Anchor macro that prepares the struct to hold account information for the claim_vested instruction.
+pub struct ClaimVested<'info> { //The escrow is not passed, it was closed by the take that started the vesting.
+    #[account(mut)] //Receives the tokens, and the rent of the vesting and its vault once everything is claimed.
+    pub beneficiary: Signer<'info>,
+
+    #[account(
+        mut, //See harvest_withheld_fees.
+        mint::token_program = token_program,
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed, //The beneficiary may have closed it since the take.
+        payer = beneficiary,
+        associated_token::mint = mint,
+        associated_token::authority = beneficiary,
+        associated_token::token_program = token_program,
+    )]
+    pub beneficiary_ata: InterfaceAccount<'info, TokenAccount>, //Receives the unlocked tokens. Wrapped SOL stays wrapped.
+
+    #[account(
+        mut,
+        has_one = beneficiary, //Only the taker can claim its tokens.
+        has_one = mint,
+        seeds = [b"vesting", vesting.escrow.as_ref(), beneficiary.key().as_ref()],
+        bump = vesting.vesting_bump,
+    )]
+    pub vesting: Account<'info, Vesting>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = vesting,
+        associated_token::token_program = token_program,
+    )]
+    pub vesting_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ClaimVested<'info> {
+    //Sends what unlocked since the last claim to the beneficiary. Once everything has unlocked, the whole vault is sent and closed with the vesting.
+    pub fn claim(&mut self) -> Result<()> {
+        let unlocked = self
+            .vesting
+            .unlocked(Clock::get()?.unix_timestamp)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        let finished = unlocked == self.vesting.total;
+        let amount = if finished {
+            self.vesting_vault.amount //Tokens sent to the vault after the take go to the beneficiary with the last claim.
+        } else {
+            unlocked.checked_sub(self.vesting.claimed).ok_or(EscrowError::ArithmeticOverflow)?
+        };
+        require!(amount > 0 || finished, EscrowError::NothingToClaim);
+
+        self.vesting.claimed = unlocked;
+        emit!(VestedClaimed {
+            vesting: self.vesting.key(),
+            beneficiary: self.beneficiary.key(),
+            mint: self.mint.key(),
+            amount,
+            claimed: self.vesting.claimed,
+            total: self.vesting.total,
+            slot: Clock::get()?.slot,
+        });
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"vesting",
+            self.vesting.escrow.as_ref(),
+            self.beneficiary.to_account_info().key.as_ref(),
+            &[self.vesting.vesting_bump],
+        ]];
+
+        if amount > 0 {
+            let transfer_accounts = TransferChecked {
+                from: self.vesting_vault.to_account_info(),
+                mint: self.mint.to_account_info(),
+                to: self.beneficiary_ata.to_account_info(),
+                authority: self.vesting.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new_with_signer(self.token_program.to_account_info(), transfer_accounts, &signer_seeds);
+            transfer_checked(cpi_ctx, amount, self.mint.decimals)
+                .map_err(|_| error!(EscrowError::WithdrawFailed))?;
+        }
+        if !finished {
+            return Ok(());
+        }
+
+        close_vault(
+            &self.vesting.to_account_info(),
+            &signer_seeds,
+            &self.mint.to_account_info(),
+            &self.vesting_vault.to_account_info(),
+            &self.beneficiary.to_account_info(),
+            &self.token_program.to_account_info(),
+        )?;
+
+        self.vesting.close(self.beneficiary.to_account_info()) //Closed after every CPI, like Anchor does for close constraints.
+    }
+}
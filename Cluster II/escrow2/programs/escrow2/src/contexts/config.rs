@@ -0,0 +1,66 @@
+use anchor_lang::{prelude::*, solana_program::bpf_loader_upgradeable};
+
+use crate::{constants::MAX_FEE_BPS, error::EscrowError, state::Config};
+
+#[derive(Accounts)] // Anchor macro that prepares the struct to hold account information for the initialize_config instruction.
+pub struct InitializeConfig<'info> {
+    #[account(mut)] //Pays for the config account and becomes its admin.
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [crate::ID.as_ref()], //Written by the upgradeable loader when the program is deployed.
+        bump,
+        seeds::program = bpf_loader_upgradeable::ID,
+        constraint = program_data.upgrade_authority_address == Some(admin.key()) @ EscrowError::NotUpgradeAuthority, //Otherwise whoever initialized the config first would set the fee and the treasury.
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    #[account(
+        init, //The config can only be created once, later changes go through update_config.
+        payer = admin,
+        space = Config::LEN,
+        seeds = [b"config"], //A single config for the whole program.
+        bump
+    )]
+    pub config: Account<'info, Config>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitializeConfig<'info> {
+    pub fn initialize_config(&mut self, fee_bps: u16, treasury: Pubkey, bump: u8) -> Result<()> {
+        require!(fee_bps <= MAX_FEE_BPS, EscrowError::InvalidFee); //The fee also applies to offers made before it was set, so it is capped well below the whole fill.
+
+        self.config.set_inner(Config {
+            admin: self.admin.key(),
+            fee_bps,
+            treasury,
+            config_bump: bump,
+        });
+        Ok(())
+    }
+}
+
+#[derive(Accounts)] // Anchor macro that prepares the struct to hold account information for the update_config instruction.
+pub struct UpdateConfig<'info> {
+    pub admin: Signer<'info>, //Must be the current admin stored in the config.
+
+    #[account(
+        mut,
+        has_one = admin, //Only the admin can change the fee, the treasury or hand over the admin role.
+        seeds = [b"config"],
+        bump = config.config_bump
+    )]
+    pub config: Account<'info, Config>,
+}
+
+impl<'info> UpdateConfig<'info> {
+    pub fn update_config(&mut self, admin: Pubkey, fee_bps: u16, treasury: Pubkey) -> Result<()> {
+        require!(fee_bps <= MAX_FEE_BPS, EscrowError::InvalidFee);
+
+        self.config.admin = admin;
+        self.config.fee_bps = fee_bps;
+        self.config.treasury = treasury;
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    error::EscrowError,
+    events::CounterOffered,
+    state::{CounterOffer, Escrow},
+};
+
+#[derive(Accounts)] // Anchor macro that prepares the struct to hold account information for the counter_offer instruction.
+#[instruction(receive: u64)] //Amount of mint_b the proposer offers for everything left in the escrow.
+pub struct ProposeCounter<'info> {
+    #[account(
+        mut, //Pays for the counter-offer and its vault, and funds the vault.
+        constraint = receive > 0 @ EscrowError::ZeroAmount, //A counter-offer must offer something.
+    )]
+    pub proposer: Signer<'info>,
+
+    #[account(mint::token_program = token_program)] //mint_b must belong to the token program passed in.
+    pub mint_b: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = proposer,
+        associated_token::token_program = token_program,
+        constraint = proposer_ata_b.amount >= receive @ EscrowError::InsufficientBalance, //The proposer must hold the amount before anything is created.
+    )]
+    pub proposer_ata_b: InterfaceAccount<'info, TokenAccount>, //Where the counter-offer is paid from. Wrapped SOL counter-offers are paid from a wrapped SOL account too.
+
+    #[account(
+        seeds = [b"escrow", escrow.maker.as_ref(), escrow.seed.to_le_bytes().as_ref()], //Any live escrow, the maker does not take part yet.
+        bump = escrow.escrow_bump,
+        has_one = mint_b, //The counter-offer is paid in the mint the maker asks for.
+        constraint = !escrow.nft @ EscrowError::NftCounterOffer, //Accepting would skip the creators' royalties.
+        constraint = escrow.vesting.is_none() @ EscrowError::VestedCounterOffer, //Accepting would release mint_a without its vesting schedule.
+        constraint = escrow.arbitration.is_none() @ EscrowError::ArbitratedEscrow, //Accepting would pay the maker instead of the recipient.
+        constraint = escrow.allowed_taker.unwrap_or(proposer.key()) == proposer.key() @ EscrowError::UnauthorizedTaker, //Private offers only take counter-offers from the designated taker.
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(
+        init,
+        payer = proposer,
+        space = CounterOffer::LEN,
+        seeds = [b"counter", escrow.key().as_ref(), proposer.key().as_ref()], //One open counter-offer per proposer and escrow.
+        bump
+    )]
+    pub counter_offer: Account<'info, CounterOffer>,
+
+    #[account(
+        init,
+        payer = proposer,
+        associated_token::mint = mint_b,
+        associated_token::authority = counter_offer, //Only the program can release the deposit, to the maker or back to the proposer.
+        associated_token::token_program = token_program,
+    )]
+    pub counter_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>, //The token program that owns mint_b.
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ProposeCounter<'info> {
+    //Locks the proposer's deposit in the counter vault and returns what arrived, which is less than receive for Token-2022 mints with a transfer fee.
+    pub fn deposit(&mut self, receive: u64) -> Result<u64> {
+        require!(
+            Clock::get()?.unix_timestamp <= self.escrow.expires_at,
+            EscrowError::OfferExpired
+        ); //An expired offer can only be refunded.
+
+        let cpi_accounts = TransferChecked {
+            from: self.proposer_ata_b.to_account_info(),
+            mint: self.mint_b.to_account_info(),
+            to: self.counter_vault.to_account_info(),
+            authority: self.proposer.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+        transfer_checked(cpi_ctx, receive, self.mint_b.decimals)
+            .map_err(|_| error!(EscrowError::DepositFailed))?;
+
+        self.counter_vault.reload()?; //Re-reads the vault so the amount reflects the transfer above.
+        Ok(self.counter_vault.amount)
+    }
+
+    pub fn save_counter(&mut self, receive: u64, bump: u8) -> Result<()> {
+        self.counter_offer.set_inner(CounterOffer {
+            escrow: self.escrow.key(),
+            proposer: self.proposer.key(),
+            mint_b: self.mint_b.key(),
+            receive, //Only what reached the counter vault is offered to the maker.
+            remaining_a: self.escrow.remaining_a, //accept_counter fails if a fill or an update changed what the proposer gets.
+            counter_bump: bump,
+        });
+
+        emit!(CounterOffered {
+            counter_offer: self.counter_offer.key(),
+            escrow: self.escrow.key(),
+            proposer: self.proposer.key(),
+            receive,
+            remaining_a: self.escrow.remaining_a,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+}
@@ -1,32 +1,58 @@
-use anchor_lang::prelude::*;
-use anchor_spl::{token::{Mint, TokenAccount, Token, Transfer, transfer}, associated_token::AssociatedToken};
+use anchor_lang::{prelude::*, system_program::{transfer, Transfer}};
+use anchor_spl::{token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked, SyncNative, sync_native}, associated_token::AssociatedToken};
 
-use crate::state::Escrow;
+use crate::{constants::{BPS_DENOMINATOR, TOKEN_METADATA_PROGRAM_ID}, error::EscrowError, events::EscrowCreated, state::{AuctionTerms, DutchAuction, Escrow, EscrowStatus, MakeArgs, MakerState, MarketPage, OfferKind, OraclePeg, TraderStats, VestingTerms}, utils::{is_native_mint, read_token_metadata}};
 
 
 
 #[derive(Accounts)] // Macro that will prepare the struct to hold account information for the solana instruction
-#[instruction(seed: u64)] //This is a custom instruction parameter. It's used as part of the seed for generating a unique address for the escrow account.
-//This line indicates that the Make instruction takes an additional parameter seed of type u64. This seed is used in the account initialization process.
+#[instruction(args: MakeArgs)] //The offer terms. The seed is used for generating a unique address for the escrow account.
+//The seed and page are used in the account initialization process and the amounts are validated before any account is created.
 pub struct Make<'info> { //Defines a Rust struct named Make, which is generic over a lifetime 'info. This lifetime is used to tie the accounts to the duration of the instruction call.
-    #[account(mut)] //This annotation specifies that the maker account is mutable, meaning it can be modified during the instruction execution.
+    #[account(
+        mut, //This annotation specifies that the maker account is mutable, meaning it can be modified during the instruction execution.
+        constraint = args.deposit > 0 && args.receive > 0 @ EscrowError::ZeroAmount, //An offer must both give and ask for something.
+    )]
    pub maker: Signer<'info>, //Declares an account maker, which must sign the transaction. The Signer type ensures that the account has signed the transaction.
    
-   pub mint_a : Account<'info, Mint>, //This line declares an account mint_a, which represents a token mint. The Mint type is part of the SPL Token program.
-   pub mint_b : Account<'info, Mint>, //This line declares an account mint_b, which represents a token mint. The Mint type is part of the SPL Token program.
+    #[account(mint::token_program = token_program)] //mint_a must belong to the token program passed in, either legacy SPL Token or Token-2022.
+   pub mint_a : InterfaceAccount<'info, Mint>, //This line declares an account mint_a, which represents a token mint. The interface Mint accepts both SPL Token and Token-2022 mints.
+    #[account(constraint = mint_b.key() != mint_a.key() @ EscrowError::SameMint)] //Swapping a token for itself is not an offer.
+   pub mint_b : InterfaceAccount<'info, Mint>, //This line declares an account mint_b, which represents a token mint. It can belong to a different token program than mint_a.
    
     #[account( //This complex annotation sets up constraints and requirements for the maker_ata_a account, which is an associated token account for mint_a and maker. The constraints ensure that this account is associated with the correct mint and authority.
         mut,
         associated_token::mint = mint_a, 
-        associated_token::authority = maker
+        associated_token::authority = maker,
+        associated_token::token_program = token_program,
+        constraint = maker_ata_a.amount >= args.deposit @ EscrowError::InsufficientBalance, //The maker must hold the deposit before the escrow and vault are created.
     )]
-   pub maker_ata_a : Account<'info, TokenAccount>, //Declares the token account of the maker for the token maker_mint_token_a
+   pub maker_ata_a : Option<InterfaceAccount<'info, TokenAccount>>, //Declares the token account of the maker for the token maker_mint_token_a. Left out when mint_a is wrapped SOL and the maker deposits native SOL.
      
+    #[account(
+        init_if_needed, //Created by the maker's first make.
+        payer = maker,
+        space = MakerState::LEN,
+        seeds = [b"maker", maker.key().as_ref()],
+        bump,
+        constraint = args.seed >= maker_state.next_seed @ EscrowError::SeedAlreadyUsed, //Checked before the escrow is created, so a reused seed gets a clear error.
+    )]
+    pub maker_state: Account<'info, MakerState>, //Hands out the maker's seeds in order, so clients can list a maker's escrows by index.
+
+    #[account(
+        init_if_needed, //Created by the wallet's first make, take or refund.
+        payer = maker,
+        space = TraderStats::LEN,
+        seeds = [b"stats", maker.key().as_ref()],
+        bump,
+    )]
+    pub maker_stats: Account<'info, TraderStats>, //Counts the offer the maker creates here.
+
      #[account( //This annotation initializes the escrow account with specific parameters like space allocation and seeds for generating its address.
         init,
         payer = maker,
         space = Escrow::LEN,
-        seeds = [b"escrow",maker.key().as_ref(), seed.to_le_bytes().as_ref()],
+        seeds = [b"escrow",maker.key().as_ref(), args.seed.to_le_bytes().as_ref()],
         bump
      )]
    pub escrow : Account<'info, Escrow>, //Declares the escrow account, which holds the state of the escrow transaction.
@@ -36,11 +62,30 @@ pub struct Make<'info> { //Defines a Rust struct named Make, which is generic ov
         payer = maker,
         associated_token::mint = mint_a,
         associated_token::authority = escrow, // gives the autority to the escrow account to the vault account ***********
+        associated_token::token_program = token_program,
     )]
-    pub vault : Account<'info, TokenAccount>, //Declares the vault account for holding tokens during the escrow.
+    pub vault : InterfaceAccount<'info, TokenAccount>, //Declares the vault account for holding tokens during the escrow.
+
+    #[account(
+        init_if_needed, //The first maker listing on a page creates it.
+        payer = maker,
+        space = MarketPage::LEN,
+        seeds = [b"market", mint_a.key().as_ref(), mint_b.key().as_ref(), args.page.to_le_bytes().as_ref()], //One index per mint pair, split into pages.
+        bump
+    )]
+    pub market_page: Account<'info, MarketPage>, //Index page the new escrow is listed on, so clients can find offers for a mint pair.
+
+    #[account(
+        seeds = [b"metadata", TOKEN_METADATA_PROGRAM_ID.as_ref(), mint_a.key().as_ref()], //The Metaplex metadata PDA of mint_a.
+        seeds::program = TOKEN_METADATA_PROGRAM_ID,
+        bump,
+        owner = TOKEN_METADATA_PROGRAM_ID, //Only the Metaplex program can write metadata accounts.
+    )]
+    /// CHECK: Address and owner are checked above, the data is parsed by read_token_metadata.
+    pub metadata: Option<UncheckedAccount<'info>>, //Passing the Token Metadata account of mint_a turns the offer into an NFT offer. Left out for fungible offers.
     
     pub associated_token_program : Program<'info, AssociatedToken>, //This is a reference to the Solana System Program, used for creating accounts and other system-level operations.
-    pub token_program : Program<'info, Token>, //A reference to the SPL Token Program, used for token-related operations.
+    pub token_program : Interface<'info, TokenInterface>, //The token program that owns mint_a, either SPL Token or Token-2022.
     pub system_program: Program<'info, System> //A reference to the Associated Token Program, used for operations related to associated token accounts.
 }
 
@@ -51,35 +96,185 @@ impl<'info> Make<'info> { //This line begins the implementation block for method
     //Indicates that the function will modify the Make struct.
         //The function takes three parameters - seed, offer_amount, and bump, all of which are used to set up the escrow account.
         // The function returns a Result type, which is a common Rust pattern for error handling. () signifies that it returns no value upon success.
-    pub fn save_escrow(&mut self,seed: u64,offer_amount: u64, bump : u8 ) -> Result<()> { 
+    pub fn save_escrow(&mut self,seed: u64,deposit: u64,offer_amount: u64,expires_at: i64,allowed_taker: Option<Pubkey>, bump : u8 ) -> Result<()> { 
+        require!(expires_at > Clock::get()?.unix_timestamp, EscrowError::InvalidExpiry); //An offer that is already expired could only ever be refunded.
+
         //self.escrow: Refers to the escrow account within the Make struct.
         //set_inner: A method provided by Anchor to set the data of an account. It's used here to initialize the Escrow struct with specific values.
         //Escrow{ ... }: Creates a new instance of the Escrow struct with the provided values.
         self.escrow.set_inner(Escrow{
+            maker: self.maker.key(), //Sets the maker field to the public key of the maker, so the counterparty can be read without re-deriving the seeds.
             mint_a: self.mint_a.key(), //Sets the maker_mint_token_a field to the public key of maker_mint_token_a, identifying the token type the maker is offering.
             mint_b: self.mint_b.key(), //Sets the taker_mint_token_b field to the public key of taker_mint_token_b, identifying the token type the taker will provide.
-            offer_amount: offer_amount, //Sets the offer_amount field to the amount of maker_mint_token_a being offered in the escrow.
+            offer_amount, //Sets the offer_amount field to the amount of maker_mint_token_a being offered in the escrow.
             seed, //Sets the seed field, used in generating the address of the escrow account.
             //escrow_bump: bumps.escrow
-            escrow_bump : bump //Sets the escrow_bump field, which is part of the seed used for the escrow account's address generation.
+            escrow_bump : bump, //Sets the escrow_bump field, which is part of the seed used for the escrow account's address generation.
+            remaining_a: deposit, //Nothing has been filled yet, so the whole deposit is available to takers.
+            remaining_b: offer_amount, //And the maker is still owed the full offer amount.
+            expires_at, //Sets the deadline after which takers are rejected and anyone can refund the maker.
+            allowed_taker, //Restricts the offer to a single taker for deals negotiated off-chain, None lets anyone take it.
+            market_page: None, //Set once the escrow is listed by add_to_market.
+            nft: false, //Set by check_nft when the metadata account is passed.
+            oracle: None, //Set by set_oracle for offers pegged to a price feed.
+            auction: None, //Set by set_auction for Dutch auctions.
+            vesting: None, //Set by set_vesting for vested offers.
+            arbitration: None, //Arbitrated escrows are created by make_arbitrated.
+            status: EscrowStatus::Open, //Funded, nothing has been settled yet.
+        });
 
+        emit!(EscrowCreated { //Announces the new offer so indexers don't have to parse token balance changes.
+            escrow: self.escrow.key(),
+            maker: self.maker.key(),
+            mint_a: self.mint_a.key(),
+            mint_b: self.mint_b.key(),
+            deposit,
+            receive: offer_amount,
+            seed,
+            expires_at,
+            allowed_taker,
+            slot: Clock::get()?.slot,
         });
         Ok(())
 
     }
+    //Moves the maker's next seed past the one just used.
+    pub fn allocate_seed(&mut self, seed: u64, bump: u8) -> Result<()> {
+        self.maker_state.allocate(self.maker.key(), seed, bump)
+    }
+
+    //Counts the new offer on the maker's trading history. make_bundle and make_arbitrated do not, so neither do their fills and refunds.
+    pub fn record_offer(&mut self, bump: u8) -> Result<()> {
+        self.maker_stats.open(self.maker.key(), bump);
+        self.maker_stats.offers_created = self.maker_stats.offers_created.checked_add(1).ok_or(EscrowError::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    //Checks an NFT offer when the metadata account is passed: mint_a must be a single token with no decimals and, if a
+    //collection is given, a verified member of it. Takers then pay the creators' royalties on top of the protocol fee.
+    pub fn check_nft(&mut self, collection: Option<Pubkey>) -> Result<()> {
+        let metadata = match &self.metadata {
+            Some(metadata) => metadata,
+            None => {
+                require!(collection.is_none(), EscrowError::NftMetadataRequired); //A collection can only be checked against the metadata.
+                return Ok(());
+            }
+        };
+        require!(self.mint_a.decimals == 0 && self.mint_a.supply == 1, EscrowError::NotAnNft);
+
+        let metadata = read_token_metadata(metadata, &self.mint_a.key())?;
+        if let Some(collection) = collection {
+            require!(
+                matches!(metadata.collection, Some(member) if member.verified && member.key == collection),
+                EscrowError::CollectionMismatch
+            ); //Anyone can put an unverified collection on their own NFT.
+        }
+        self.escrow.nft = true;
+        Ok(())
+    }
+
+    //Stores how takes are priced. Fixed price offers ask remaining_b, which save_escrow already set.
+    pub fn set_kind(&mut self, kind: OfferKind) -> Result<()> {
+        match kind {
+            OfferKind::Fixed => Ok(()),
+            OfferKind::Pegged(oracle) => self.set_oracle(oracle),
+            OfferKind::Auction(terms) => self.set_auction(terms),
+        }
+    }
+
+    //Pegs the offer to a price feed. Takes then pay the feed price with the maker's spread, and offer_amount only records what the maker first asked.
+    //The feed itself is only read on take, where a missing, stale or uncertain price is rejected.
+    fn set_oracle(&mut self, oracle: OraclePeg) -> Result<()> {
+        require!(
+            oracle.spread_bps > -(BPS_DENOMINATOR as i16)
+                && oracle.max_staleness > 0
+                && oracle.max_confidence_bps <= BPS_DENOMINATOR,
+            EscrowError::InvalidOracleTerms
+        );
+        self.escrow.oracle = Some(oracle);
+        Ok(())
+    }
+
+    //Turns the offer into a Dutch auction of the whole deposit. Its price is recomputed from the clock on every take.
+    fn set_auction(&mut self, terms: AuctionTerms) -> Result<()> {
+        let auction = DutchAuction {
+            amount_a: self.escrow.remaining_a, //What reached the vault, so the start price buys the whole deposit.
+            start_price: terms.start_price,
+            end_price: terms.end_price,
+            start_at: terms.start_at,
+            end_at: terms.end_at,
+        };
+        auction.check()?;
+        self.escrow.auction = Some(auction);
+        Ok(())
+    }
+
+    //Locks what takers buy in a vesting vault released by claim_vested, with a cliff and a linear schedule starting when the offer is taken.
+    pub fn set_vesting(&mut self, vesting: Option<VestingTerms>) -> Result<()> {
+        if let Some(terms) = vesting {
+            terms.check()?;
+        }
+        self.escrow.vesting = vesting;
+        Ok(())
+    }
+
+    //Lists the escrow on the market page. A page that was just created is stamped with its mint pair and number first.
+    pub fn add_to_market(&mut self, page: u32, bump: u8) -> Result<()> {
+        if self.market_page.escrows.is_empty() && self.market_page.mint_a == Pubkey::default() {
+            self.market_page.mint_a = self.mint_a.key();
+            self.market_page.mint_b = self.mint_b.key();
+            self.market_page.page = page;
+            self.market_page.page_bump = bump;
+        }
+        self.market_page.insert(self.escrow.key())?;
+        self.escrow.market_page = Some(page); //Take and refund use it to find the page to unlist from.
+        Ok(())
+    }
+
     //Handles the transfer of tokens from the maker's account to the vault account.
-    pub fn deposit(&mut self, deposit: u64) -> Result<()> {
-        let cpi_accounts = Transfer {
-            from: self.maker_ata_a.to_account_info(), //The source account from which tokens will be debited.
+    //Returns the amount that actually arrived in the vault, which is less than deposit for Token-2022 mints with a transfer fee.
+    pub fn deposit(&mut self, deposit: u64) -> Result<u64> {
+        let maker_ata_a = match &self.maker_ata_a {
+            Some(maker_ata_a) => maker_ata_a,
+            None => return self.deposit_native(deposit), //No token account means the maker pays in native SOL.
+        };
+
+        let cpi_accounts = TransferChecked {
+            from: maker_ata_a.to_account_info(), //The source account from which tokens will be debited.
+            mint: self.mint_a.to_account_info(), //transfer_checked needs the mint to verify decimals and apply any transfer fee.
             to:self.vault.to_account_info(), //The destination account to which tokens will be credited.
             authority: self.maker.to_account_info() //The account that has the authority to approve the transfer. In this case, it's the maker's account.
         };
 
         //This function creates a new Cross-Program Invocation (CPI) context. CPI is used when a program (like our escrow program) wants to call another program (like the Token program).
-        //The CPI context specifies the program being called (the Token program in this case) and the accounts involved in the call (encapsulated in the Transfer struct).
+        //The CPI context specifies the program being called (the Token program in this case) and the accounts involved in the call (encapsulated in the TransferChecked struct).
         let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts );
 
-        transfer(cpi_ctx,deposit)
-    
+        transfer_checked(cpi_ctx,deposit, self.mint_a.decimals)?;
+
+        self.vault.reload()?; //Re-reads the vault so the amount reflects the transfer above.
+        Ok(self.vault.amount)
+    }
+
+    //Wraps native SOL straight into the vault: the lamports are sent to the wrapped SOL vault and sync_native updates its token balance.
+    fn deposit_native(&mut self, deposit: u64) -> Result<u64> {
+        require!(is_native_mint(&self.mint_a.key()), EscrowError::NativeMintRequired);
+        require!(self.maker.lamports() >= deposit, EscrowError::InsufficientBalance);
+
+        let cpi_accounts = Transfer {
+            from: self.maker.to_account_info(),
+            to: self.vault.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(self.system_program.to_account_info(), cpi_accounts);
+        transfer(cpi_ctx, deposit)?;
+
+        let cpi_accounts = SyncNative {
+            account: self.vault.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+        sync_native(cpi_ctx)?;
+
+        self.vault.reload()?;
+        Ok(self.vault.amount)
     }
 }
@@ -0,0 +1,131 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    error::EscrowError,
+    events::ArbitratedEscrowCreated,
+    state::{ArbiterTerms, Escrow, EscrowStatus, MakerState},
+};
+
+#[derive(Accounts)] // Anchor macro that prepares the struct to hold account information for the make_arbitrated instruction.
+#[instruction(seed: u64, deposit: u64)]
+pub struct MakeArbitrated<'info> {
+    #[account(
+        mut, //Pays for the escrow, its vault and the maker state.
+        constraint = deposit > 0 @ EscrowError::ZeroAmount,
+    )]
+    pub maker: Signer<'info>,
+
+    #[account(mint::token_program = token_program)] //mint_a must belong to the token program passed in.
+    pub mint_a: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program,
+        constraint = maker_ata_a.amount >= deposit @ EscrowError::InsufficientBalance, //The maker must hold the deposit before the escrow and vault are created.
+    )]
+    pub maker_ata_a: InterfaceAccount<'info, TokenAccount>, //Funds the vault. Wrapped SOL has to be wrapped first.
+
+    #[account(
+        init_if_needed, //Created by the maker's first make or make_arbitrated.
+        payer = maker,
+        space = MakerState::LEN,
+        seeds = [b"maker", maker.key().as_ref()],
+        bump,
+        constraint = seed >= maker_state.next_seed @ EscrowError::SeedAlreadyUsed, //Arbitrated escrows share the maker's seeds with offers.
+    )]
+    pub maker_state: Account<'info, MakerState>,
+
+    #[account(
+        init,
+        payer = maker,
+        space = Escrow::LEN,
+        seeds = [b"escrow", maker.key().as_ref(), seed.to_le_bytes().as_ref()], //Same address as an offer, so clients list both by the maker's seeds.
+        bump
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(
+        init,
+        payer = maker,
+        associated_token::mint = mint_a, //Links the vault to the mint_a token mint.
+        associated_token::authority = escrow, //Only the escrow can move the deposit, and only when the arbiter settles it.
+        associated_token::token_program = token_program,
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>, //Holds the deposit until the arbiter settles the escrow.
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> MakeArbitrated<'info> {
+    //Moves the deposit to the vault. Returns what arrived, which is less than deposit for Token-2022 mints with a transfer fee.
+    pub fn deposit(&mut self, deposit: u64) -> Result<u64> {
+        let cpi_accounts = TransferChecked {
+            from: self.maker_ata_a.to_account_info(),
+            mint: self.mint_a.to_account_info(),
+            to: self.vault.to_account_info(),
+            authority: self.maker.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+        transfer_checked(cpi_ctx, deposit, self.mint_a.decimals)?;
+
+        self.vault.reload()?;
+        Ok(self.vault.amount)
+    }
+
+    //Saves an escrow the arbiter releases to the recipient or refunds to the maker. It is not an offer: it asks for nothing and is not listed.
+    //If the arbiter has not settled it by expires_at and nobody disputed it, the maker can reclaim it.
+    pub fn save_escrow(&mut self, seed: u64, deposited: u64, terms: ArbiterTerms, expires_at: i64, bump: u8) -> Result<()> {
+        let maker = self.maker.key();
+        require!(
+            terms.arbiter != maker && terms.recipient != maker && terms.arbiter != terms.recipient,
+            EscrowError::InvalidArbiterTerms
+        ); //An arbiter that is also a party could settle in its own favor.
+        require!(expires_at > Clock::get()?.unix_timestamp, EscrowError::InvalidExpiry); //The arbiter needs some time to settle it.
+
+        self.escrow.set_inner(Escrow {
+            maker,
+            mint_a: self.mint_a.key(),
+            mint_b: Pubkey::default(), //Nothing is asked in return.
+            offer_amount: 0,
+            seed,
+            escrow_bump: bump,
+            remaining_a: deposited, //What the arbiter settles, anything sent to the vault since goes back to the maker.
+            remaining_b: 0,
+            expires_at, //Deadline of the arbiter. Only reclaim looks at it, refund_expired rejects arbitrated escrows.
+            allowed_taker: None,
+            market_page: None, //Nobody can take it, so it is not listed.
+            nft: false,
+            oracle: None,
+            auction: None,
+            vesting: None,
+            arbitration: Some(terms),
+            status: EscrowStatus::Open,
+        });
+
+        emit!(ArbitratedEscrowCreated {
+            escrow: self.escrow.key(),
+            maker,
+            arbiter: terms.arbiter,
+            recipient: terms.recipient,
+            mint_a: self.mint_a.key(),
+            deposit: deposited,
+            expires_at,
+            seed,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+
+    //Moves the maker's next seed past the one just used, like make does.
+    pub fn allocate_seed(&mut self, seed: u64, bump: u8) -> Result<()> {
+        self.maker_state.allocate(self.maker.key(), seed, bump)
+    }
+}
@@ -0,0 +1,104 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{transfer_checked, TransferChecked},
+};
+
+use crate::{
+    constants::BUNDLE_OFFERED_LEG_ACCOUNTS,
+    error::EscrowError,
+    events::BundleCreated,
+    state::{Bundle, BundleLeg},
+    utils::{init_leg_ata, leg_mint_decimals, leg_token_amount},
+};
+
+#[derive(Accounts)] // Anchor macro that prepares the struct to hold account information for the make_bundle instruction.
+#[instruction(seed: u64)] //The seed is used for generating a unique address for the bundle account.
+pub struct MakeBundle<'info> { //The offered legs are passed as remaining accounts, BUNDLE_OFFERED_LEG_ACCOUNTS per leg: mint, token program, vault and the maker's token account.
+    #[account(mut)] //Pays for the bundle account and the vaults.
+    pub maker: Signer<'info>,
+
+    #[account(
+        init,
+        payer = maker,
+        space = Bundle::LEN,
+        seeds = [b"bundle", maker.key().as_ref(), seed.to_le_bytes().as_ref()], //Bundles use their own seed prefix so they never collide with an escrow of the same seed.
+        bump
+    )]
+    pub bundle: Account<'info, Bundle>, //Holds the legs of the bundle and owns every vault.
+
+    pub associated_token_program: Program<'info, AssociatedToken>, //Creates the vaults, one associated token account of the bundle per offered mint.
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> MakeBundle<'info> {
+    //Moves every deposit into its vault. Returns the offered legs with the amounts that actually arrived, which are less than the deposits for Token-2022 mints with a transfer fee.
+    //Tokens already in a vault are not offered, take_bundle and refund_bundle return them to the maker.
+    pub fn deposit(&mut self, deposits: &[u64], legs: &[AccountInfo<'info>]) -> Result<Vec<BundleLeg>> {
+        require!(
+            legs.len() == deposits.len() * BUNDLE_OFFERED_LEG_ACCOUNTS,
+            EscrowError::BundleAccountsMismatch
+        );
+
+        let mut offered = Vec::with_capacity(deposits.len());
+        for (deposit, accounts) in deposits.iter().zip(legs.chunks_exact(BUNDLE_OFFERED_LEG_ACCOUNTS)) {
+            let [mint, token_program, vault, maker_ata] = accounts else {
+                return err!(EscrowError::BundleAccountsMismatch);
+            };
+            let decimals = leg_mint_decimals(mint, token_program)?;
+            init_leg_ata(
+                &self.maker.to_account_info(),
+                vault,
+                &self.bundle.to_account_info(),
+                mint,
+                &self.system_program.to_account_info(),
+                token_program,
+                &self.associated_token_program.to_account_info(),
+            )?; //The vault is the bundle's associated token account for this mint.
+            let balance = leg_token_amount(vault)?; //Anyone can create the vault before the make and send tokens to it.
+
+            let cpi_accounts = TransferChecked {
+                from: maker_ata.clone(), //Only the maker can sign for it, so it does not have to be the maker's associated token account.
+                mint: mint.clone(),
+                to: vault.clone(),
+                authority: self.maker.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(token_program.clone(), cpi_accounts);
+            transfer_checked(cpi_ctx, *deposit, decimals)
+                .map_err(|_| error!(EscrowError::DepositFailed))?;
+
+            offered.push(BundleLeg {
+                mint: mint.key(),
+                amount: leg_token_amount(vault)?.checked_sub(balance).ok_or(EscrowError::ArithmeticOverflow)?, //Only what this deposit brought to the vault is offered to takers.
+            });
+        }
+        Ok(offered)
+    }
+
+    pub fn save_bundle(&mut self, seed: u64, offered: Vec<BundleLeg>, requested: Vec<BundleLeg>, expires_at: i64, allowed_taker: Option<Pubkey>, bump: u8) -> Result<()> {
+        require!(expires_at > Clock::get()?.unix_timestamp, EscrowError::InvalidExpiry); //An offer that is already expired could only ever be refunded.
+        Bundle::check_legs(&offered, &requested)?; //Also rejects a mint deposited twice, which would have shared one vault.
+
+        self.bundle.set_inner(Bundle {
+            maker: self.maker.key(),
+            seed,
+            bundle_bump: bump,
+            expires_at,
+            allowed_taker,
+            offered: offered.clone(),
+            requested: requested.clone(),
+        });
+
+        emit!(BundleCreated {
+            bundle: self.bundle.key(),
+            maker: self.maker.key(),
+            offered,
+            requested,
+            seed,
+            expires_at,
+            allowed_taker,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+use anchor_lang::{
+    prelude::*,
+    system_program::{transfer, Transfer},
+    Discriminator,
+};
+use anchor_spl::{associated_token::get_associated_token_address, token::TokenAccount};
+
+use crate::{
+    error::EscrowError,
+    state::{Escrow, EscrowStatus, LegacyEscrow, LEGACY_ESCROW_LEN},
+};
+
+#[derive(Accounts)] // Anchor macro that prepares the struct to hold account information for the migrate instruction.
+#[instruction(seed: u64)] //The seed is passed in because a legacy escrow cannot be deserialized as an Escrow before it has been migrated.
+pub struct Migrate<'info> {
+    #[account(mut)] //Pays for the extra space the current layout needs.
+    pub payer: Signer<'info>,
+
+    pub maker: Signer<'info>, //The maker the escrow was created by, proven by the PDA derivation below. Signs for the deposit it states.
+
+    #[account(
+        mut, //The account data is rewritten in place.
+        owner = crate::ID, //Only accounts owned by this program can be migrated.
+        seeds = [b"escrow", maker.key().as_ref(), seed.to_le_bytes().as_ref()], //Same derivation used by Make, ties the escrow to the maker.
+        bump
+    )]
+    /// CHECK: The data is still in the legacy layout, so it is validated by hand in Migrate::migrate.
+    pub escrow: UncheckedAccount<'info>,
+
+    #[account(
+        constraint = vault.owner == escrow.key() //The vault must be controlled by the escrow. Its mint is checked once the legacy data is read.
+    )]
+    pub vault: Account<'info, TokenAccount>, //Must still hold the deposit stated by the maker.
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> Migrate<'info> {
+    //Rewrites an escrow created before the maker was stored on-chain into the current Escrow layout.
+    pub fn migrate(&mut self, seed: u64, deposit: u64, bump: u8) -> Result<()> {
+        let legacy = {
+            let data = self.escrow.try_borrow_data()?;
+
+            require!(data.len() == LEGACY_ESCROW_LEN, EscrowError::NotLegacyEscrow); //Legacy escrows were allocated with exactly this size.
+            require!(data[..8] == Escrow::DISCRIMINATOR, EscrowError::NotLegacyEscrow); //The legacy layout shares the Escrow discriminator.
+
+            LegacyEscrow::deserialize(&mut &data[8..])?
+        };
+
+        //In a migrated account these bytes belong to other fields, so the stored seed and bump only line up for the legacy layout.
+        require!(
+            legacy.seed == seed && legacy.escrow_bump == bump,
+            EscrowError::NotLegacyEscrow
+        );
+        require_keys_eq!(
+            self.vault.key(),
+            get_associated_token_address(&self.escrow.key(), &legacy.mint_a),
+            EscrowError::NotLegacyEscrow
+        );
+        require!(deposit > 0, EscrowError::ZeroAmount);
+        require!(deposit <= self.vault.amount, EscrowError::DepositNotInVault); //Anything on top was sent to the vault after make and is returned to the maker as surplus.
+
+        //Tops up the rent for the larger account before growing it.
+        let info = self.escrow.to_account_info();
+        let rent = Rent::get()?.minimum_balance(Escrow::LEN);
+        let top_up = rent.saturating_sub(info.lamports()); //Nothing to pay if the account already holds enough lamports.
+        if top_up > 0 {
+            let cpi_accounts = Transfer {
+                from: self.payer.to_account_info(),
+                to: info.clone(),
+            };
+            let cpi_ctx = CpiContext::new(self.system_program.to_account_info(), cpi_accounts);
+            transfer(cpi_ctx, top_up)?;
+        }
+        info.realloc(Escrow::LEN, false)?;
+
+        let escrow = Escrow {
+            maker: self.maker.key(), //The maker recovered from the PDA derivation.
+            mint_a: legacy.mint_a,
+            mint_b: legacy.mint_b,
+            offer_amount: legacy.offer_amount,
+            seed: legacy.seed,
+            escrow_bump: legacy.escrow_bump,
+            remaining_a: deposit, //Legacy escrows were all-or-nothing, so nothing of the deposit has been taken.
+            remaining_b: legacy.offer_amount,
+            expires_at: i64::MAX, //Legacy offers had no deadline.
+            allowed_taker: None, //And could be taken by anyone.
+            market_page: None, //And were never listed on a market index.
+            nft: false, //And had no NFT mode.
+            oracle: None, //Or oracle pricing.
+            auction: None, //Or auctions.
+            vesting: None, //Or vesting.
+            arbitration: None, //Or an arbiter.
+            status: EscrowStatus::Open, //Swaps never leave Open.
+        };
+
+        let mut data = info.try_borrow_mut_data()?;
+        let mut writer: &mut [u8] = &mut data[..];
+        escrow.try_serialize(&mut writer) //Writes the discriminator followed by the new layout over the old data.
+    }
+}
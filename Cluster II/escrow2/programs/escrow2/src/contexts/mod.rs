@@ -1,13 +1,60 @@
 pub mod make;
 pub use make::*;
 
-//pub mod update;
-//pub use update::*;
+pub mod make_arbitrated;
+pub use make_arbitrated::*;
+
+pub mod update;
+pub use update::*;
 
 pub mod refund;
 pub use refund::*;
 
+pub mod refund_expired;
+pub use refund_expired::*;
+
+pub mod refund_many;
+pub use refund_many::*;
+
 pub mod take;
 pub use take::*;
 
+pub mod migrate;
+pub use migrate::*;
+
+pub mod config;
+pub use config::*;
+
+pub mod make_bundle;
+pub use make_bundle::*;
+
+pub mod take_bundle;
+pub use take_bundle::*;
+
+pub mod refund_bundle;
+pub use refund_bundle::*;
+
+pub mod refund_bundle_expired;
+pub use refund_bundle_expired::*;
+
+pub mod counter_offer;
+pub use counter_offer::*;
+
+pub mod accept_counter;
+pub use accept_counter::*;
+
+pub mod cancel_counter;
+pub use cancel_counter::*;
+
+pub mod claim_vested;
+pub use claim_vested::*;
+
+pub mod raise_dispute;
+pub use raise_dispute::*;
+
+pub mod arbitrate;
+pub use arbitrate::*;
+
+pub mod reclaim;
+pub use reclaim::*;
 
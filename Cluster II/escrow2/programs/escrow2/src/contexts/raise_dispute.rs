@@ -0,0 +1,38 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    error::EscrowError,
+    events::DisputeRaised,
+    state::{Escrow, EscrowStatus},
+};
+
+#[derive(Accounts)] // Anchor macro that prepares the struct to hold account information for the raise_dispute instruction.
+pub struct RaiseDispute<'info> {
+    pub party: Signer<'info>, //The maker or the recipient of the escrow. Nothing is paid, the escrow already exists.
+
+    #[account(
+        mut, //The new status is written to the escrow.
+        seeds = [b"escrow", escrow.maker.as_ref(), escrow.seed.to_le_bytes().as_ref()], //Used for deriving the account's address using the PDA mechanism.
+        bump = escrow.escrow_bump,
+    )]
+    pub escrow: Account<'info, Escrow>,
+}
+
+impl<'info> RaiseDispute<'info> {
+    //Flags the escrow as disputed. The arbiter can still release or refund it, but the maker can no longer reclaim it after its deadline.
+    pub fn raise_dispute(&mut self) -> Result<()> {
+        let terms = self.escrow.arbitration.ok_or(EscrowError::NotEscrowParty)?; //Swaps have no recipient and nobody to arbitrate them.
+        let party = self.party.key();
+        require!(party == self.escrow.maker || party == terms.recipient, EscrowError::NotEscrowParty);
+        require!(self.escrow.status == EscrowStatus::Open, EscrowError::AlreadyDisputed); //Settled escrows are closed and fail to load.
+
+        self.escrow.status = EscrowStatus::Disputed;
+        emit!(DisputeRaised {
+            escrow: self.escrow.key(),
+            raised_by: party,
+            arbiter: terms.arbiter,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,84 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+use crate::{
+    error::EscrowError,
+    events::EscrowRefunded,
+    state::{Escrow, EscrowStatus},
+    utils::refund_vault,
+};
+
+#[derive(Accounts)] // Anchor macro that prepares the struct to hold account information for the reclaim instruction.
+pub struct Reclaim<'info> {
+    #[account(mut)] //Receives the deposit and the rent of the escrow and its vault.
+    pub maker: Signer<'info>,
+
+    #[account(
+        mut, //See harvest_withheld_fees.
+        mint::token_program = token_program, //mint_a must belong to the token program passed in.
+    )]
+    pub mint_a: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed, //The maker may have closed it since the make.
+        payer = maker,
+        associated_token::mint = mint_a,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program,
+    )]
+    pub maker_ata_a: Option<InterfaceAccount<'info, TokenAccount>>, //Receives the deposit. Left out to receive wrapped SOL as native SOL.
+
+    #[account(
+        mut,
+        close = maker,
+        has_one = maker, //Only the maker can reclaim its escrow.
+        has_one = mint_a,
+        seeds = [b"escrow", maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.escrow_bump,
+        constraint = escrow.arbitration.is_some() @ EscrowError::NotArbitrated,
+        constraint = Clock::get()?.unix_timestamp > escrow.expires_at @ EscrowError::OfferNotExpired, //The arbiter has until expires_at to settle it.
+        constraint = escrow.status == EscrowStatus::Open @ EscrowError::AlreadyDisputed, //A party objected, so only the arbiter can decide.
+    )]
+    pub escrow: Account<'info, Escrow>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program,
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+impl<'info> Reclaim<'info> {
+    //Returns everything in the vault to the maker and closes it, like refund does for offers.
+    pub fn reclaim(&mut self) -> Result<()> {
+        let amount = refund_vault(
+            &self.escrow,
+            &self.maker.to_account_info(),
+            &self.mint_a.to_account_info(),
+            self.mint_a.decimals,
+            self.maker_ata_a.as_ref().map(|maker_ata_a| maker_ata_a.to_account_info()),
+            &self.vault.to_account_info(),
+            &self.token_program.to_account_info(),
+        )?;
+
+        emit!(EscrowRefunded {
+            escrow: self.escrow.key(),
+            maker: self.maker.key(),
+            refunded_by: self.maker.key(),
+            mint_a: self.mint_a.key(),
+            amount,
+            seed: self.escrow.seed,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+}
@@ -1,32 +1,44 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token::{close_account, transfer, CloseAccount, Mint, Token, TokenAccount, Transfer},
+    token_interface::{Mint, TokenAccount, TokenInterface},
 };
 
-use crate::{error::EscrowError, state::Escrow};
+use crate::{
+    error::EscrowError,
+    events::EscrowRefunded,
+    state::{Escrow, MarketPage, TraderStats},
+    utils::refund_vault,
+};
 
 #[derive(Accounts)] // This is an Anchor macro that prepares the struct to hold account information for a Solana program instruction.
 pub struct Refund<'info> { //Defines a Rust struct named Refund, which is generic over a lifetime 'info. This lifetime is used to tie the accounts to the duration of the instruction call.
     #[account(mut)] //Specifies that the maker account is mutable (can be changed during the instruction execution).
     pub maker: Signer<'info>, //is the account of the user who initiated the escrow and is a signer of the transaction.
    
-    pub mint_a: Account<'info, Mint>, //Declares an account mint_a, representing a token mint (type of token) involved in the escrow.
+    #[account(
+        mut, //See harvest_withheld_fees.
+        mint::token_program = token_program, //mint_a must belong to the token program passed in.
+    )]
+    pub mint_a: InterfaceAccount<'info, Mint>, //Declares an account mint_a, representing a token mint (type of token) involved in the escrow.
    
     #[account(
         init_if_needed, //Initializes the account if it doesn't exist.
         payer = maker, //The maker pays for the account creation or rent.
         associated_token::mint = mint_a,//Links to the mint_a token mint.
         associated_token::authority = maker,//The maker is the authority of this account.
+        associated_token::token_program = token_program,//Created by the token program that owns mint_a.
     )]
-    pub maker_ata_a: Account<'info, TokenAccount>, //this account is for the maker to receive tokens of type mint_a as part of the refund process.
+    pub maker_ata_a: Option<InterfaceAccount<'info, TokenAccount>>, //this account is for the maker to receive tokens of type mint_a as part of the refund process. Left out to receive wrapped SOL as native SOL.
    
     #[account(
         mut,//The account is mutable.
         close = maker,//Allows the maker to close this account.
+        has_one = maker,//Ensures the signer is the maker stored in the escrow account.
         has_one = mint_a,//Ensures the escrow account is associated with the mint_a token mint.
         seeds = [b"escrow", maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],//Used for deriving the account's address using the PDA mechanism.
-        bump = escrow.escrow_bump //Used for deriving the account's address using the PDA mechanism.
+        bump = escrow.escrow_bump, //Used for deriving the account's address using the PDA mechanism.
+        constraint = escrow.arbitration.is_none() @ EscrowError::ArbitratedEscrow //Only the arbiter can refund an arbitrated escrow.
     )]
     pub escrow: Account<'info, Escrow>, //Holds the state of the escrow transaction.
     
@@ -34,61 +46,59 @@ pub struct Refund<'info> { //Defines a Rust struct named Refund, which is generi
         mut,//Indicates mutability.
         associated_token::mint = mint_a,//Links the vault to the mint_a token mint.
         associated_token::authority = escrow,//Sets the escrow account as the authority of the vault.
+        associated_token::token_program = token_program,//The vault belongs to the token program that owns mint_a.
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>, //The vault account is where the maker's tokens are held during the escrow.
+
+    #[account(
+        mut, //The escrow is unlisted once it is refunded.
+        seeds = [b"market", mint_a.key().as_ref(), escrow.mint_b.as_ref(), market_page.page.to_le_bytes().as_ref()], //The index page of this mint pair.
+        bump = market_page.page_bump,
     )]
-    pub vault: Account<'info, TokenAccount>, //The vault account is where the maker's tokens are held during the escrow.
+    pub market_page: Option<Account<'info, MarketPage>>, //Index page listing the escrow. Left out for migrated escrows, which were never listed.
+
+    #[account(
+        init_if_needed, //Created by the wallet's first make, take or refund.
+        payer = maker,
+        space = TraderStats::LEN,
+        seeds = [b"stats", maker.key().as_ref()],
+        bump,
+    )]
+    pub maker_stats: Account<'info, TraderStats>, //Counts the refund on the maker's trading history.
     //These are references to the Solana System Program, SPL Token Program, and Associated Token Program, used for various operations like creating accounts and handling tokens.
     pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
 }
 
 impl<'info> Refund<'info> {
+    //Returns everything in the vault to the maker and closes it. A wrapped SOL vault passed without a maker token account is unwrapped to the maker.
     pub fn refund(&mut self) -> Result<()> { //Declares a public function named refund that can modify the state of the Refund struct and returns a Result type for error handling.
-        let transfer_accounts = Transfer {//Initializes a Transfer struct to specify the accounts involved in the token transfer.
-            from: self.vault.to_account_info(),//Specifies the vault account as the source of the transfer.
-            to: self.maker_ata_a.to_account_info(),//Specifies the maker's ATA for mint_a as the destination.
-            authority: self.escrow.to_account_info(),//Sets the escrow account as the authority for the transfer.
-        };
-
-        let signer_seeds: [&[&[u8]]; 1] = [&[ // Sets up the seeds for the PDA (escrow account) that will authorize the transfer.
-            b"escrow",
-            self.maker.to_account_info().key.as_ref(),
-            &self.escrow.seed.to_le_bytes()[..],
-            &[self.escrow.escrow_bump],
-        ]];
+        let amount = refund_vault(
+            &self.escrow,
+            &self.maker.to_account_info(),
+            &self.mint_a.to_account_info(),
+            self.mint_a.decimals,
+            self.maker_ata_a.as_ref().map(|maker_ata_a| maker_ata_a.to_account_info()),
+            &self.vault.to_account_info(),
+            &self.token_program.to_account_info(),
+        )?;
 
-        let cpi_ctx = CpiContext::new_with_signer(//Creates a CPI context with the necessary accounts and signer seeds for the token transfer.
-            self.token_program.to_account_info(),
-            transfer_accounts,
-            &signer_seeds,
-        );
-
-        transfer(cpi_ctx, self.vault.amount) //Calls the SPL transfer function to transfer the specified amount from the vault to the maker's ATA.
-            .map_err(|_| return error!(EscrowError::RefundFailed))?;
+        emit!(EscrowRefunded {
+            escrow: self.escrow.key(),
+            maker: self.maker.key(),
+            refunded_by: self.maker.key(),
+            mint_a: self.mint_a.key(),
+            amount, //Wrapped SOL left without a token account is counted too, it was unwrapped to the maker.
+            seed: self.escrow.seed,
+            slot: Clock::get()?.slot,
+        });
         Ok(())
     }
 
-    pub fn close_vault(&mut self) -> Result<()> {
-        let close_accounts = CloseAccount {
-            account: self.vault.to_account_info(),
-            destination: self.maker.to_account_info(),
-            authority: self.escrow.to_account_info(),
-        };
-
-        let signer_seeds: [&[&[u8]]; 1] = [&[
-            b"escrow",
-            self.maker.to_account_info().key.as_ref(),
-            &self.escrow.seed.to_le_bytes()[..],
-            &[self.escrow.escrow_bump],
-        ]]; 
-
-        let cpi_ctx_close = CpiContext::new_with_signer(
-            self.token_program.to_account_info(),
-            close_accounts,
-            &signer_seeds,
-        );
-
-        close_account(cpi_ctx_close).map_err(|_| return EscrowError::CloseVaultFailed)?;
-        Ok(())
+    //Counts the refunded offer on the maker's trading history.
+    pub fn record_refund(&mut self, bump: u8) -> Result<()> {
+        self.maker_stats.open(self.maker.key(), bump);
+        self.maker_stats.add_refunded(1)
     }
 }
\ No newline at end of file
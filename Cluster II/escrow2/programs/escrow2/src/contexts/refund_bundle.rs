@@ -0,0 +1,50 @@
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::AssociatedToken;
+
+use crate::{
+    events::BundleRefunded,
+    state::Bundle,
+    utils::refund_bundle_vaults,
+};
+
+#[derive(Accounts)] // Anchor macro that prepares the struct to hold account information for the refund_bundle instruction.
+pub struct RefundBundle<'info> { //The offered legs are passed as remaining accounts in the order stored on the bundle: mint, token program, vault and the maker's associated token account.
+    #[account(mut)] //Receives the tokens and the rent of the bundle and its vaults.
+    pub maker: Signer<'info>,
+
+    #[account(
+        mut,
+        close = maker,
+        has_one = maker, //Only the maker can take the bundle back.
+        seeds = [b"bundle", maker.key().as_ref(), bundle.seed.to_le_bytes().as_ref()],
+        bump = bundle.bundle_bump
+    )]
+    pub bundle: Account<'info, Bundle>,
+
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+impl<'info> RefundBundle<'info> {
+    //Returns every vault to the maker and closes it.
+    pub fn refund(&self, legs: &[AccountInfo<'info>]) -> Result<()> {
+        refund_bundle_vaults(
+            &self.bundle,
+            &self.maker.to_account_info(),
+            &self.maker.to_account_info(),
+            legs,
+            &self.system_program.to_account_info(),
+            &self.associated_token_program.to_account_info(),
+        )?;
+
+        emit!(BundleRefunded {
+            bundle: self.bundle.key(),
+            maker: self.maker.key(),
+            refunded_by: self.maker.key(),
+            offered: self.bundle.offered.clone(),
+            seed: self.bundle.seed,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,55 @@
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::AssociatedToken;
+
+use crate::{
+    error::EscrowError,
+    events::BundleRefunded,
+    state::Bundle,
+    utils::refund_bundle_vaults,
+};
+
+#[derive(Accounts)] // Anchor macro that prepares the struct to hold account information for the refund_bundle_expired instruction.
+pub struct RefundBundleExpired<'info> { //Same accounts and legs as RefundBundle, except the maker does not sign. Anyone can send this once the bundle has expired.
+    #[account(mut)] //The caller pays for the maker's token accounts that have to be created.
+    pub payer: Signer<'info>,
+
+    #[account(mut)] //Receives the tokens and the rent of the bundle and its vaults.
+    pub maker: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        close = maker, //Rent goes back to the maker, not the caller.
+        has_one = maker,
+        seeds = [b"bundle", maker.key().as_ref(), bundle.seed.to_le_bytes().as_ref()],
+        bump = bundle.bundle_bump,
+        constraint = Clock::get()?.unix_timestamp > bundle.expires_at @ EscrowError::OfferNotExpired, //Only expired bundles can be refunded without the maker's signature.
+    )]
+    pub bundle: Account<'info, Bundle>,
+
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+impl<'info> RefundBundleExpired<'info> {
+    //Returns every vault to the maker and closes it, like refund_bundle does.
+    pub fn refund(&self, legs: &[AccountInfo<'info>]) -> Result<()> {
+        refund_bundle_vaults(
+            &self.bundle,
+            &self.maker.to_account_info(),
+            &self.payer.to_account_info(),
+            legs,
+            &self.system_program.to_account_info(),
+            &self.associated_token_program.to_account_info(),
+        )?;
+
+        emit!(BundleRefunded {
+            bundle: self.bundle.key(),
+            maker: self.maker.key(),
+            refunded_by: self.payer.key(),
+            offered: self.bundle.offered.clone(),
+            seed: self.bundle.seed,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+}
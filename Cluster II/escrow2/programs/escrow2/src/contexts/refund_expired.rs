@@ -0,0 +1,108 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+use crate::{
+    error::EscrowError,
+    events::EscrowRefunded,
+    state::{Escrow, MarketPage, TraderStats},
+    utils::refund_vault,
+};
+
+#[derive(Accounts)] // Anchor macro that prepares the struct to hold account information for the refund_expired instruction.
+pub struct RefundExpired<'info> { //Same accounts as Refund, except the maker does not sign. Anyone can send this once the offer has expired.
+    #[account(mut)] //The caller pays for the maker's token account if it has to be created.
+    pub payer: Signer<'info>,
+
+    #[account(mut)] //Receives the rent of the escrow and vault accounts.
+    pub maker: SystemAccount<'info>, //The maker who created the escrow.
+
+    #[account(
+        mut, //See harvest_withheld_fees.
+        mint::token_program = token_program, //mint_a must belong to the token program passed in.
+    )]
+    pub mint_a: InterfaceAccount<'info, Mint>, //The token mint held in the vault.
+
+    #[account(
+        init_if_needed, //Initializes the account if it doesn't exist.
+        payer = payer, //The caller pays for the account creation, the refund itself is still owned by the maker.
+        associated_token::mint = mint_a, //Links to the mint_a token mint.
+        associated_token::authority = maker, //The maker is the authority of this account.
+        associated_token::token_program = token_program, //Created by the token program that owns mint_a.
+    )]
+    pub maker_ata_a: Option<InterfaceAccount<'info, TokenAccount>>, //Where the maker's tokens are returned. Left out to receive wrapped SOL as native SOL.
+
+    #[account(
+        mut, //The account is mutable.
+        close = maker, //Rent goes back to the maker, not the caller.
+        has_one = maker, //Ensures the maker passed in is the one stored in the escrow account.
+        has_one = mint_a, //Ensures the escrow account is associated with the mint_a token mint.
+        seeds = [b"escrow", maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()], //Used for deriving the account's address using the PDA mechanism.
+        bump = escrow.escrow_bump, //Used for deriving the account's address using the PDA mechanism.
+        constraint = Clock::get()?.unix_timestamp > escrow.expires_at @ EscrowError::OfferNotExpired, //Only expired offers can be refunded without the maker's signature.
+        constraint = escrow.arbitration.is_none() @ EscrowError::ArbitratedEscrow //Only the arbiter can refund an arbitrated escrow.
+    )]
+    pub escrow: Account<'info, Escrow>, //Holds the state of the escrow transaction.
+
+    #[account(
+        mut, //Indicates mutability.
+        associated_token::mint = mint_a, //Links the vault to the mint_a token mint.
+        associated_token::authority = escrow, //Sets the escrow account as the authority of the vault.
+        associated_token::token_program = token_program, //The vault belongs to the token program that owns mint_a.
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>, //The vault account where the maker's tokens are held.
+
+    #[account(
+        mut, //The escrow is unlisted once it is refunded.
+        seeds = [b"market", mint_a.key().as_ref(), escrow.mint_b.as_ref(), market_page.page.to_le_bytes().as_ref()], //The index page of this mint pair.
+        bump = market_page.page_bump,
+    )]
+    pub market_page: Option<Account<'info, MarketPage>>, //Index page listing the escrow. Left out for migrated escrows, which were never listed.
+
+    #[account(
+        init_if_needed, //Created by the wallet's first make, take or refund.
+        payer = payer,
+        space = TraderStats::LEN,
+        seeds = [b"stats", maker.key().as_ref()],
+        bump,
+    )]
+    pub maker_stats: Account<'info, TraderStats>, //Counts the refund on the maker's trading history.
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+impl<'info> RefundExpired<'info> {
+    //Returns everything in the vault to the maker and closes it, like refund does.
+    pub fn refund(&mut self) -> Result<()> {
+        let amount = refund_vault(
+            &self.escrow,
+            &self.maker.to_account_info(),
+            &self.mint_a.to_account_info(),
+            self.mint_a.decimals,
+            self.maker_ata_a.as_ref().map(|maker_ata_a| maker_ata_a.to_account_info()),
+            &self.vault.to_account_info(),
+            &self.token_program.to_account_info(),
+        )?;
+
+        emit!(EscrowRefunded {
+            escrow: self.escrow.key(),
+            maker: self.maker.key(),
+            refunded_by: self.payer.key(),
+            mint_a: self.mint_a.key(),
+            amount, //Wrapped SOL left without a token account is counted too, it was unwrapped to the maker.
+            seed: self.escrow.seed,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+
+    //Counts the refunded offer on the maker's trading history.
+    pub fn record_refund(&mut self, bump: u8) -> Result<()> {
+        self.maker_stats.open(self.maker.key(), bump);
+        self.maker_stats.add_refunded(1)
+    }
+}
@@ -0,0 +1,123 @@
+use anchor_lang::{prelude::*, AccountsClose};
+use anchor_spl::associated_token::AssociatedToken;
+
+use crate::{
+    constants::REFUND_MANY_ESCROW_ACCOUNTS,
+    error::EscrowError,
+    events::{EscrowRefunded, EscrowsRefunded},
+    state::{Escrow, MarketPage, TraderStats},
+    utils::{init_leg_ata, leg_mint_decimals, refund_vault, require_leg_ata},
+};
+
+#[derive(Accounts)] // Anchor macro that prepares the struct to hold account information for the refund_many instruction.
+pub struct RefundMany<'info> { //Every escrow is passed as remaining accounts: escrow, vault, mint_a, its token program, the maker's token account and the market page listing it.
+    #[account(mut)] //Receives the tokens and the rent of every escrow and vault.
+    pub maker: Signer<'info>,
+
+    #[account(
+        init_if_needed, //Created by the wallet's first make, take or refund.
+        payer = maker,
+        space = TraderStats::LEN,
+        seeds = [b"stats", maker.key().as_ref()],
+        bump,
+    )]
+    pub maker_stats: Account<'info, TraderStats>, //Counts every escrow refunded here on the maker's trading history.
+
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+impl<'info> RefundMany<'info> {
+    //Refunds and closes every escrow passed in. Escrows closed since the batch was built are skipped instead of failing the whole transaction.
+    pub fn refund_many(&mut self, escrows: &'info [AccountInfo<'info>], stats_bump: u8) -> Result<()> {
+        require!(
+            !escrows.is_empty() && escrows.len() % REFUND_MANY_ESCROW_ACCOUNTS == 0,
+            EscrowError::RefundAccountsMismatch
+        );
+
+        let mut refunded = Vec::new();
+        let mut skipped = Vec::new();
+        for accounts in escrows.chunks_exact(REFUND_MANY_ESCROW_ACCOUNTS) {
+            if accounts[0].data_is_empty() {
+                skipped.push(accounts[0].key());
+                continue;
+            }
+            refunded.push(self.refund_escrow(accounts)?);
+        }
+
+        for escrow in &refunded {
+            escrow.close(self.maker.to_account_info())?; //Closed once every CPI is done, like Anchor does for close constraints.
+        }
+        self.maker_stats.open(self.maker.key(), stats_bump);
+        self.maker_stats.add_refunded(refunded.len() as u64)?;
+
+        emit!(EscrowsRefunded {
+            maker: self.maker.key(),
+            refunded: refunded.iter().map(|escrow| escrow.key()).collect(),
+            skipped,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+
+    //Checks one escrow against its seed and bump, returns the vault to the maker and closes it. Returns the escrow for the caller to close. Wrapped SOL stays wrapped, use refund to unwrap it.
+    fn refund_escrow(&self, accounts: &'info [AccountInfo<'info>]) -> Result<Account<'info, Escrow>> {
+        let [escrow_info, vault, mint_a, token_program, maker_ata_a, market_page] = accounts else {
+            return err!(EscrowError::RefundAccountsMismatch);
+        };
+
+        let escrow = Account::<Escrow>::try_from(escrow_info)?; //Checks the owner and the discriminator.
+        require_keys_eq!(escrow.maker, self.maker.key(), EscrowError::RefundAccountsMismatch); //Only the maker can take an escrow back.
+        require!(escrow.arbitration.is_none(), EscrowError::ArbitratedEscrow); //Only the arbiter can refund an arbitrated escrow.
+        let address = Pubkey::create_program_address(
+            &[b"escrow", self.maker.key.as_ref(), &escrow.seed.to_le_bytes(), &[escrow.escrow_bump]],
+            &crate::ID,
+        )
+        .map_err(|_| error!(EscrowError::RefundAccountsMismatch))?;
+        require_keys_eq!(escrow_info.key(), address, EscrowError::RefundAccountsMismatch);
+        require_keys_eq!(mint_a.key(), escrow.mint_a, EscrowError::RefundAccountsMismatch);
+
+        let decimals = leg_mint_decimals(mint_a, token_program).map_err(|_| error!(EscrowError::RefundAccountsMismatch))?;
+        require_leg_ata(vault, &escrow_info.key(), mint_a, token_program).map_err(|_| error!(EscrowError::RefundAccountsMismatch))?;
+        init_leg_ata(
+            &self.maker.to_account_info(),
+            maker_ata_a,
+            &self.maker.to_account_info(),
+            mint_a,
+            &self.system_program.to_account_info(),
+            token_program,
+            &self.associated_token_program.to_account_info(),
+        )?;
+
+        let amount = refund_vault(&escrow, &self.maker.to_account_info(), mint_a, decimals, Some(maker_ata_a.clone()), vault, token_program)?;
+        emit!(EscrowRefunded {
+            escrow: escrow_info.key(),
+            maker: self.maker.key(),
+            refunded_by: self.maker.key(),
+            mint_a: mint_a.key(),
+            amount,
+            seed: escrow.seed,
+            slot: Clock::get()?.slot,
+        });
+
+        self.remove_from_market(&escrow, market_page)?;
+        Ok(escrow)
+    }
+
+    //Loads and checks the market page passed as a remaining account, then unlists the escrow like MarketPage::unlist does for the other refunds. Migrated escrows were never listed, whatever is passed in their market page slot is ignored.
+    fn remove_from_market(&self, escrow: &Account<'info, Escrow>, market_page: &'info AccountInfo<'info>) -> Result<()> {
+        let Some(page) = escrow.market_page else {
+            return Ok(());
+        };
+        let mut listing = Account::<MarketPage>::try_from(market_page).map_err(|_| error!(EscrowError::MarketPageMismatch))?;
+        let address = Pubkey::create_program_address(
+            &[b"market", escrow.mint_a.as_ref(), escrow.mint_b.as_ref(), &page.to_le_bytes(), &[listing.page_bump]],
+            &crate::ID,
+        )
+        .map_err(|_| error!(EscrowError::MarketPageMismatch))?;
+        require_keys_eq!(market_page.key(), address, EscrowError::MarketPageMismatch);
+
+        MarketPage::unlist(Some(&mut listing), escrow)?;
+        listing.exit(&crate::ID) //Written back right away, escrows of the same mint pair share the page.
+    }
+}
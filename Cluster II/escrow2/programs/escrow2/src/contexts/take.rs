@@ -1,13 +1,27 @@
-use anchor_lang::prelude::*;
+use anchor_lang::{
+    prelude::*,
+    system_program::{transfer, Transfer},
+};
 use anchor_spl::{
-    associated_token::AssociatedToken,
-    token::{close_account, transfer, CloseAccount, Mint, Token, TokenAccount, Transfer},
+    associated_token::{get_associated_token_address_with_program_id, AssociatedToken},
+    token_interface::{
+        close_account, transfer_checked, CloseAccount, Mint, TokenAccount, TokenInterface,
+        TransferChecked,
+    },
 };
 
-use crate::{error::EscrowError, state::Escrow};
+use crate::{
+    constants::{BPS_DENOMINATOR, CREATOR_SHARES_TOTAL, PYTH_ORACLE_PROGRAM_ID, TOKEN_METADATA_PROGRAM_ID},
+    error::EscrowError,
+    events::{AuctionFilled, EscrowTaken, VestingStarted},
+    state::{Config, Escrow, MarketPage, TraderStats, Vesting},
+    utils::{
+        amount_after_transfer_fee, amount_with_transfer_fee, close_vault, init_leg_ata, is_native_mint,
+        oracle_quote, protocol_fee, read_oracle_price, read_token_metadata,
+    },
+};
 
 #[derive(Accounts)] //his is an Anchor macro that prepares the struct to hold account information for a Solana program instruction.
-#[instruction(seed: u64)] //Indicates that the Take instruction takes an additional parameter seed of type u64. This seed is used in the account initialization process.
 pub struct Take<'info> { //Defines a Rust struct named Take, which is generic over a lifetime 'info. This lifetime is used to tie the accounts to the duration of the instruction call.
     #[account(mut)] //Specifies that these accounts are mutable (can be changed during the instruction execution)
     pub taker: Signer<'info>, //taker is the account of the user taking the escrow
@@ -15,8 +29,13 @@ pub struct Take<'info> { //Defines a Rust struct named Take, which is generic ov
     #[account(mut)] //Specifies that these accounts are mutable (can be changed during the instruction execution)
     pub maker: SystemAccount<'info>, //maker is the account of the user who created the escrow
     
-    pub mint_a : Account<'info, Mint>, //Declares an account mint_a, representing a token mint (type of token) involved in the escrow.
-    pub mint_b: Account<'info, Mint>, //Declares an account mint_b, representing a token mint (type of token) involved in the escrow.
+    #[account(
+        mut, //See harvest_withheld_fees.
+        mint::token_program = token_program_a, //mint_a must belong to the token program passed in for it.
+    )]
+    pub mint_a : InterfaceAccount<'info, Mint>, //Declares an account mint_a, representing a token mint (type of token) involved in the escrow.
+    #[account(mint::token_program = token_program_b)] //mint_b can belong to a different token program than mint_a.
+    pub mint_b: InterfaceAccount<'info, Mint>, //Declares an account mint_b, representing a token mint (type of token) involved in the escrow.
    
     #[account( // These annotations set up constraints and requirements for the token accounts of the taker. They specify the associated token mint and authority.
         // mut,
@@ -24,27 +43,76 @@ pub struct Take<'info> { //Defines a Rust struct named Take, which is generic ov
         payer = taker, ////Specifies that the taker account will pay for any account creation or rent fees.
         associated_token::mint = mint_a , //Links this account to the mint_a token mint.
         associated_token::authority = taker, //Sets the taker as the authority of this token account.
+        associated_token::token_program = token_program_a, //Created by the token program that owns mint_a.
     )]
-    pub taker_ata_a: Account<'info, TokenAccount>, //his account is for the taker to receive tokens of type mint_a. It's where the taker will receive tokens from the escrow (the maker's offering).
+    pub taker_ata_a: InterfaceAccount<'info, TokenAccount>, //his account is for the taker to receive tokens of type mint_a. It's where the taker will receive tokens from the escrow (the maker's offering).
    
     #[account( //These annotations set up constraints and requirements for the token accounts of the taker. They specify the associated token mint and authority.
         mut, //Indicates that this account is mutable and may be modified.
         associated_token::mint = mint_b, //Links this account to the mint_b token mint.
         associated_token::authority = taker, //Sets the taker as the authority of this token account.
+        associated_token::token_program = token_program_b, //Owned by the token program of mint_b.
     )]
-    pub taker_ata_b: Account<'info, TokenAccount>, //This account is for the taker to deposit their own tokens of type mint_b into the escrow. It's part of the taker's contribution to the escrow transaction.
+    pub taker_ata_b: Option<InterfaceAccount<'info, TokenAccount>>, //This account is for the taker to deposit their own tokens of type mint_b into the escrow. It's part of the taker's contribution to the escrow transaction. Left out with maker_ata_b to pay a wrapped SOL leg in native SOL.
     
     #[account( //Similar to taker_ata_b, but for the maker's token account for mint_b.
         init_if_needed, //This indicates that the account should be initialized if it does not already exist.
         payer = taker, //Specifies that the taker account will pay for any account creation or rent fees.
         associated_token::mint = mint_b, //Links this account to the mint_b token mint.
         associated_token::authority = maker, //Sets the maker  as the authority of this token account.
+        associated_token::token_program = token_program_b, //Created by the token program that owns mint_b.
+    )]
+    pub maker_ata_b: Option<InterfaceAccount<'info, TokenAccount>>, //This account is for the maker to receive tokens of type mint_b. It's where the maker will receive tokens from the escrow (the taker's offering). Left out when the maker is paid in native SOL.
+
+    #[account(
+        init_if_needed, //Created like maker_ata_b if the maker closed it since make.
+        payer = taker,
+        associated_token::mint = mint_a,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program_a,
+    )]
+    pub maker_ata_a: Option<InterfaceAccount<'info, TokenAccount>>, //Receives tokens sent to the vault on top of the deposit when the last fill closes it. Left out when mint_a is wrapped SOL, the surplus then goes back as native SOL.
+
+    #[account(
+        init_if_needed, //Created by the wallet's first make, take or refund.
+        payer = taker,
+        space = TraderStats::LEN,
+        seeds = [b"stats", maker.key().as_ref()],
+        bump,
+    )]
+    pub maker_stats: Account<'info, TraderStats>, //Records the fill on the maker's trading history.
+
+    #[account(
+        init_if_needed,
+        payer = taker,
+        space = TraderStats::LEN,
+        seeds = [b"stats", taker.key().as_ref()],
+        bump,
     )]
-    pub maker_ata_b: Account<'info, TokenAccount>, //This account is for the maker to receive tokens of type mint_b. It's where the maker will receive tokens from the escrow (the taker's offering).  
+    pub taker_stats: Account<'info, TraderStats>, //Records the fill on the taker's trading history. The same account as maker_stats when makers take their own offer.
+
+    #[account(
+        seeds = [b"config"], //The program wide config holding the protocol fee.
+        bump,
+    )]
+    /// CHECK: The address is checked above, Config::fee_bps checks the owner and the discriminator before reading it.
+    pub config: UncheckedAccount<'info>, //Not created until the admin runs initialize_config, fills are free until then.
+
+    #[account(mut)] //Receives the fee in lamports when mint_b is paid in native SOL.
+    /// CHECK: Compared against config.treasury by Config::fee_bps and used as a transfer destination or token account authority.
+    pub treasury: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed, //Created on the first fill that pays a fee in this mint.
+        payer = taker, //The taker pays for the account creation, like for maker_ata_b.
+        associated_token::mint = mint_b, //Links this account to the mint_b token mint.
+        associated_token::authority = treasury, //The treasury owns the collected fees.
+        associated_token::token_program = token_program_b, //Created by the token program that owns mint_b.
+    )]
+    pub treasury_ata_b: Option<InterfaceAccount<'info, TokenAccount>>, //Receives the protocol fee. Can be left out when the fee is zero or mint_b is paid in native SOL.
     
     #[account( //This annotation initializes the escrow account with specific parameters like seeds for generating its address and constraints like has_one to ensure it's associated with the correct mints.
-        mut, //The account is mutable.
-        close = maker, //Allows the maker to close this account.
+        mut, //The account is mutable. It is closed to the maker by close_escrow once the offer is fully filled, so partial fills keep it open.
 
         //maker.key().as_ref()
         //maker.key() This gets the public key of the maker account.
@@ -60,6 +128,9 @@ pub struct Take<'info> { //Defines a Rust struct named Take, which is generic ov
         //bump: This is a value used in conjunction with the seeds to derive the PDA. It's a single byte that Solana runtime adjusts to ensure the generated address is off-curve (i.e., has no corresponding private key).
         //escrow.escrow_bump, This refers to the bump value stored in the escrow account's state. When the PDA was initially created, the program would have found a bump value that, combined with the specified seeds, results in a valid PDA. This value is then stored in the escrow account for future reference.
         bump = escrow.escrow_bump,
+        has_one = maker, //Ensures that the maker passed in is the one stored in the escrow account.
+        constraint = escrow.allowed_taker.unwrap_or(taker.key()) == taker.key() @ EscrowError::UnauthorizedTaker, //Private offers can only be taken by the designated taker.
+        constraint = escrow.arbitration.is_none() @ EscrowError::ArbitratedEscrow, //Arbitrated escrows are paid out by their arbiter, not sold.
         has_one = mint_a, //Ensures that the escrow account is associated with the specified token mints.
         has_one = mint_b, //Ensures that the escrow account is associated with the specified token mints.
     )]
@@ -69,33 +140,293 @@ pub struct Take<'info> { //Defines a Rust struct named Take, which is generic ov
         mut, //Indicates mutability.
         associated_token::mint = mint_a, //Links the vault to the mint_a token mint.
         associated_token::authority = escrow, //Sets the escrow account as the authority of the vault.
+        associated_token::token_program = token_program_a, //The vault belongs to the token program that owns mint_a.
     )] // Like a PDA The vault account is an ATA with the escrow program account set as its authority, allowing the program to control it. This setup is often used in scenarios where a program needs to manage tokens on behalf of users, as is the case in escrow transactions.
-    pub vault: Account<'info, TokenAccount>, //Declares the vault account, which holds the tokens deposited by the maker during the escrow.
+    pub vault: InterfaceAccount<'info, TokenAccount>, //Declares the vault account, which holds the tokens deposited by the maker during the escrow.
+
+    #[account(
+        init, //Vested offers are only filled whole, so a taker gets one vesting per escrow.
+        payer = taker,
+        space = Vesting::LEN,
+        seeds = [b"vesting", escrow.key().as_ref(), taker.key().as_ref()],
+        bump,
+    )]
+    pub vesting: Option<Account<'info, Vesting>>, //Schedule the taker's mint_a unlocks on. Passed for vested offers only.
+
+    #[account(
+        init,
+        payer = taker,
+        associated_token::mint = mint_a,
+        associated_token::authority = vesting, //Only claim_vested can release the tokens.
+        associated_token::token_program = token_program_a,
+    )]
+    pub vesting_vault: Option<InterfaceAccount<'info, TokenAccount>>, //Receives mint_a instead of taker_ata_a for vested offers.
+
+    #[account(
+        mut, //The escrow is unlisted once it is fully filled.
+        seeds = [b"market", mint_a.key().as_ref(), mint_b.key().as_ref(), market_page.page.to_le_bytes().as_ref()], //The index page of this mint pair.
+        bump = market_page.page_bump,
+    )]
+    pub market_page: Option<Account<'info, MarketPage>>, //Index page listing the escrow. Left out for migrated escrows, which were never listed.
+
+    #[account(
+        seeds = [b"metadata", TOKEN_METADATA_PROGRAM_ID.as_ref(), mint_a.key().as_ref()], //The Metaplex metadata PDA of mint_a.
+        seeds::program = TOKEN_METADATA_PROGRAM_ID,
+        bump,
+        owner = TOKEN_METADATA_PROGRAM_ID, //Only the Metaplex program can write metadata accounts.
+    )]
+    /// CHECK: Address and owner are checked above, the data is parsed by read_token_metadata.
+    pub metadata: Option<UncheckedAccount<'info>>, //Token Metadata account of mint_a, holds the royalty and creators of NFT offers. Left out for fungible offers.
+
+    #[account(owner = PYTH_ORACLE_PROGRAM_ID)] //Only the Pyth program can publish prices.
+    /// CHECK: Owner is checked above, reprice compares the address with the escrow and read_oracle_price parses the data.
+    pub price_feed: Option<UncheckedAccount<'info>>, //Price account the escrow is pegged to. Left out for fixed price offers.
     
     pub system_program: Program<'info, System>, //Reference to the Solana System Program, used for system-level operations.
-    pub token_program: Program<'info, Token>, //Reference to the SPL Token Program, used for token-related operations.
+    pub token_program_a: Interface<'info, TokenInterface>, //The token program that owns mint_a, either SPL Token or Token-2022.
+    pub token_program_b: Interface<'info, TokenInterface>, //The token program that owns mint_b, either SPL Token or Token-2022.
     pub associated_token_program: Program<'info, AssociatedToken>, //Reference to the Associated Token Program, used for operations related to associated token accounts.
 }
 
 impl<'info> Take<'info> {
-    pub fn deposit(&mut self) -> Result<()> {//Defines a public function deposit that mutates the state of Take and returns a Result type for error handling.
-        let transfer_accounts = Transfer {//Creates a Transfer struct to specify the accounts involved in the token transfer.
-            from: self.taker_ata_b.to_account_info(),//The source account for the transfer is the taker's token account for mint_b.
-            to: self.maker_ata_b.to_account_info(), //The destination account is the maker's token account for mint_b.
+    //Sets what the maker is owed for the rest of a pegged offer or an auction from its current price, so fill prices every fill at it.
+    //Fixed price offers keep the remaining_b set by make and update.
+    pub fn reprice(&mut self) -> Result<()> {
+        if let Some(auction) = self.escrow.auction {
+            self.escrow.remaining_b = auction
+                .ask(self.escrow.remaining_a, Clock::get()?.unix_timestamp)
+                .ok_or(EscrowError::ArithmeticOverflow)?;
+            return Ok(());
+        }
+        let Some(oracle) = self.escrow.oracle else {
+            return Ok(());
+        };
+        let price_feed = self.price_feed.as_ref().ok_or(EscrowError::PriceFeedMismatch)?;
+        require_keys_eq!(price_feed.key(), oracle.price_feed, EscrowError::PriceFeedMismatch);
+        let price = read_oracle_price(price_feed)?;
+
+        let age = Clock::get()?.unix_timestamp.saturating_sub(price.publish_time);
+        require!(age <= oracle.max_staleness as i64, EscrowError::StalePrice);
+        require!(
+            (price.confidence as u128) * (BPS_DENOMINATOR as u128)
+                <= (price.price as u128) * (oracle.max_confidence_bps as u128),
+            EscrowError::PriceConfidenceTooWide
+        ); //A wide interval means the publishers disagree on the price.
+
+        self.escrow.remaining_b = oracle_quote(
+            self.escrow.remaining_a,
+            &price,
+            oracle.spread_bps,
+            self.mint_a.decimals,
+            self.mint_b.decimals,
+        )
+        .ok_or(EscrowError::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    //Works out how much of mint_a a fill of amount_b releases and records what is left on the escrow.
+    //Partial fills round the mint_a amount down, so a taker never receives more than the maker's price. Fills too small to release anything are rejected.
+    pub fn fill(&mut self, amount_b: u64) -> Result<u64> {
+        require!(
+            Clock::get()?.unix_timestamp <= self.escrow.expires_at,
+            EscrowError::OfferExpired
+        ); //Stale offers can only be refunded.
+        require!(
+            amount_b > 0 && amount_b <= self.escrow.remaining_b,
+            EscrowError::InvalidFillAmount
+        );
+        require!(
+            self.escrow.vesting.is_none() || amount_b == self.escrow.remaining_b,
+            EscrowError::VestedPartialFill
+        ); //A schedule started by a small fill would leave later fills of the same taker nothing to wait for.
+
+        let amount_a = if amount_b == self.escrow.remaining_b {
+            std::mem::take(&mut self.escrow.remaining_a) //The last fill releases what is left of the deposit. Tokens sent to the vault after make go back to the maker in close_vault.
+        } else {
+            let amount_a = (amount_b as u128)
+                .checked_mul(self.escrow.remaining_a as u128)
+                .and_then(|product| product.checked_div(self.escrow.remaining_b as u128))
+                .and_then(|amount_a| u64::try_from(amount_a).ok())
+                .ok_or(EscrowError::ArithmeticOverflow)?; //Pro-rata share of what is left, rounded down in the maker's favor.
+            require!(amount_a > 0, EscrowError::FillTooSmall);
+
+            self.escrow.remaining_a = self
+                .escrow
+                .remaining_a
+                .checked_sub(amount_a)
+                .ok_or(EscrowError::ArithmeticOverflow)?;
+            amount_a
+        };
+
+        self.escrow.remaining_b = self
+            .escrow
+            .remaining_b
+            .checked_sub(amount_b)
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+        Ok(amount_a)
+    }
+
+    //Checks that the taker gets at least expected_receive_a out of a fill releasing amount_a, after any Token-2022 transfer fee on mint_a.
+    pub fn check_receive(&self, amount_a: u64, expected_receive_a: u64) -> Result<()> {
+        let received = amount_after_transfer_fee(&self.mint_a.to_account_info(), amount_a)?;
+        require!(received >= expected_receive_a, EscrowError::SlippageExceeded);
+        Ok(())
+    }
+
+    //What the taker pays mint_b from: the token account, or the wallet when paying in native SOL.
+    pub fn taker_balance_b(&self) -> u64 {
+        match &self.taker_ata_b {
+            Some(taker_ata_b) => taker_ata_b.amount,
+            None => self.taker.lamports(),
+        }
+    }
+
+    //Checks what deposit took from the taker against max_pay_b. Measured on the balance so the fee, royalties and transfer fees all count.
+    pub fn check_paid(&mut self, balance_b: u64, max_pay_b: u64) -> Result<()> {
+        if let Some(taker_ata_b) = self.taker_ata_b.as_mut() {
+            taker_ata_b.reload()?;
+        }
+        let paid = balance_b.checked_sub(self.taker_balance_b()).ok_or(EscrowError::ArithmeticOverflow)?;
+        require!(paid <= max_pay_b, EscrowError::SlippageExceeded);
+        Ok(())
+    }
+
+    //Pays the maker for a fill of amount_b, routes the protocol fee to the treasury and, for NFT offers, the royalty to the creators.
+    //Returns the fee and the royalty that were charged.
+    pub fn deposit(&mut self, amount_b: u64, creators: &[AccountInfo<'info>]) -> Result<(u64, u64)> {//Defines a public function deposit that mutates the state of Take and returns a Result type for error handling.
+        let fee = protocol_fee(amount_b, Config::fee_bps(&self.config, &self.treasury.key())?).ok_or(EscrowError::InvalidFee)?; //The fee comes out of the maker's side of the fill.
+        let royalty = self.pay_royalties(amount_b, creators)?; //So does the royalty.
+        let to_maker = amount_b
+            .checked_sub(fee)
+            .and_then(|rest| rest.checked_sub(royalty))
+            .ok_or(EscrowError::ArithmeticOverflow)?;
+
+        match (&self.taker_ata_b, &self.maker_ata_b) {
+            (Some(taker_ata_b), Some(maker_ata_b)) => {
+                self.transfer_b(taker_ata_b.to_account_info(), maker_ata_b.to_account_info(), to_maker)?;
+                if fee > 0 {
+                    let treasury_ata_b = self
+                        .treasury_ata_b
+                        .as_ref()
+                        .ok_or(EscrowError::TreasuryAccountRequired)?;
+                    self.transfer_b(taker_ata_b.to_account_info(), treasury_ata_b.to_account_info(), fee)?;
+                }
+            }
+            (None, None) => { //Without token accounts the taker pays in native SOL.
+                require!(is_native_mint(&self.mint_b.key()), EscrowError::NativeMintRequired);
+                self.transfer_native(self.maker.to_account_info(), to_maker)?;
+                if fee > 0 {
+                    self.transfer_native(self.treasury.to_account_info(), fee)?;
+                }
+            }
+            _ => return err!(EscrowError::NativeMintRequired),
+        }
+        Ok((fee, royalty))
+    }
+
+    //Pays the creators of an NFT their share of the royalty set in its metadata and returns how much was paid. Rounding dust stays with the maker.
+    //Remaining accounts, for every creator with a share in metadata order: the creator's wallet, followed by its mint_b token account unless mint_b is paid in native SOL.
+    fn pay_royalties(&self, amount_b: u64, creators: &[AccountInfo<'info>]) -> Result<u64> {
+        if !self.escrow.nft {
+            return Ok(0);
+        }
+        let metadata = self.metadata.as_ref().ok_or(EscrowError::NftMetadataRequired)?;
+        let metadata = read_token_metadata(metadata, &self.mint_a.key())?;
+        let royalty = protocol_fee(amount_b, metadata.seller_fee_basis_points).ok_or(EscrowError::InvalidMetadata)?;
+
+        let paid_creators: Vec<_> = metadata
+            .creators
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|creator| creator.share > 0)
+            .collect();
+        let native = self.taker_ata_b.is_none();
+        require!(!native || is_native_mint(&self.mint_b.key()), EscrowError::NativeMintRequired);
+        let accounts_per_creator = if native { 1 } else { 2 };
+        require!(
+            creators.len() == paid_creators.len() * accounts_per_creator,
+            EscrowError::RoyaltyAccountsMismatch
+        );
+
+        let mut paid = 0u64;
+        for (creator, accounts) in paid_creators.iter().zip(creators.chunks_exact(accounts_per_creator)) {
+            let wallet = &accounts[0];
+            require_keys_eq!(wallet.key(), creator.address, EscrowError::RoyaltyAccountsMismatch);
+            let share = (royalty as u128)
+                .checked_mul(creator.share as u128)
+                .map(|amount| amount / CREATOR_SHARES_TOTAL as u128)
+                .and_then(|amount| u64::try_from(amount).ok())
+                .ok_or(EscrowError::ArithmeticOverflow)?;
+            if share == 0 {
+                continue;
+            }
+
+            match &self.taker_ata_b {
+                Some(taker_ata_b) => {
+                    let creator_ata = &accounts[1];
+                    require_keys_eq!(
+                        creator_ata.key(),
+                        get_associated_token_address_with_program_id(wallet.key, &self.mint_b.key(), &self.token_program_b.key()),
+                        EscrowError::RoyaltyAccountsMismatch
+                    );
+                    init_leg_ata(
+                        &self.taker.to_account_info(),
+                        creator_ata,
+                        wallet,
+                        &self.mint_b.to_account_info(),
+                        &self.system_program.to_account_info(),
+                        &self.token_program_b.to_account_info(),
+                        &self.associated_token_program.to_account_info(),
+                    )?; //The taker pays for a missing creator account, like for maker_ata_b.
+                    self.transfer_b(taker_ata_b.to_account_info(), creator_ata.clone(), share)?;
+                }
+                None => self.transfer_native(wallet.clone(), share)?,
+            }
+            paid = paid.checked_add(share).ok_or(EscrowError::ArithmeticOverflow)?;
+        }
+        Ok(paid)
+    }
+
+    //Moves mint_b from the taker's token account to `to`. The recipient is owed `amount` after any Token-2022 transfer fee, so the taker sends enough to cover the fee on top.
+    fn transfer_b(&self, from: AccountInfo<'info>, to: AccountInfo<'info>, amount: u64) -> Result<()> {
+        let amount_with_fee = amount_with_transfer_fee(&self.mint_b.to_account_info(), amount)?;
+
+        let transfer_accounts = TransferChecked {//Creates a TransferChecked struct to specify the accounts involved in the token transfer.
+            from,//The source account for the transfer is the taker's token account for mint_b.
+            mint: self.mint_b.to_account_info(),//The mint is needed to check decimals and apply any transfer fee.
+            to, //The destination account is the maker's or the treasury's token account for mint_b.
             authority: self.taker.to_account_info(), //The authority to execute this transfer is the taker's account.
         };
         // Creates a Cross-Program Invocation (CPI) context for the token transfer.
-        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), transfer_accounts);
+        let cpi_ctx = CpiContext::new(self.token_program_b.to_account_info(), transfer_accounts);
 
-        transfer(cpi_ctx, self.escrow.offer_amount) //Calls the SPL transfer function to transfer tokens from the taker to the maker.
-            .map_err(|_| return error!(EscrowError::DepositFailed))?; //Handles any errors that might occur during the transfer.
-        Ok(())
+        transfer_checked(cpi_ctx, amount_with_fee, self.mint_b.decimals) //Calls the transfer_checked function to transfer the tokens.
+            .map_err(|_| error!(EscrowError::DepositFailed)) //Handles any errors that might occur during the transfer.
     }
-    //Similar to deposit, but this time transferring from the vault to the taker_ata_a.
-    pub fn withdraw(&mut self) -> Result<()> {
+
+    //Pays a wrapped SOL leg directly in lamports from the taker, so nobody needs a wrapped SOL account.
+    fn transfer_native(&self, to: AccountInfo<'info>, amount: u64) -> Result<()> {
         let transfer_accounts = Transfer {
+            from: self.taker.to_account_info(),
+            to,
+        };
+        let cpi_ctx = CpiContext::new(self.system_program.to_account_info(), transfer_accounts);
+
+        transfer(cpi_ctx, amount).map_err(|_| error!(EscrowError::DepositFailed))
+    }
+    //Similar to deposit, but this time transferring from the vault to the taker_ata_a, or to the vesting vault for vested offers.
+    pub fn withdraw(&mut self, amount_a: u64) -> Result<()> {
+        let to = match self.escrow.vesting {
+            Some(_) => self.vesting_vault.as_ref().ok_or(EscrowError::VestingAccountsMismatch)?.to_account_info(),
+            None => {
+                require!(self.vesting.is_none(), EscrowError::VestingAccountsMismatch); //Nothing would ever close it.
+                self.taker_ata_a.to_account_info()
+            }
+        };
+        let transfer_accounts = TransferChecked {
             from: self.vault.to_account_info(), //Sets the from field to the vault account's information. This is the source account from which tokens will be withdrawn. to_account_info() converts the vault account into a format that can be used in a Cross-Program Invocation (CPI).
-            to: self.taker_ata_a.to_account_info(), //Sets the to field to the taker_ata_a account's information. This is the destination account where the tokens will be deposited. Again, to_account_info() is used for CPI compatibility.
+            mint: self.mint_a.to_account_info(), //The mint of the vault, needed by transfer_checked.
+            to, //Sets the to field to the taker_ata_a account's information. This is the destination account where the tokens will be deposited. Again, to_account_info() is used for CPI compatibility.
             authority: self.escrow.to_account_info(), //Sets the authority field to the escrow account's information. This account is the authority over the vault account and is required to authorize the transfer. The escrow account is likely a Program Derived Account (PDA), and its authority is used to validate the transaction.
         };
         //Purpose: signer_seeds are used to generate a Program Derived Address (PDA) for signing transactions that require access to an account controlled by the program (like the vault).
@@ -108,23 +439,76 @@ impl<'info> Take<'info> {
         //Creates a CPI context with the signer seeds, used for executing the transfer from a PDA.
         //Purpose: Creates a Cross-Program Invocation (CPI) context for calling another program (like the Token program) with the necessary accounts and signer seeds.
         let cpi_ctx = CpiContext::new_with_signer(// is used to create a context for a CPI when the involved account is a PDA. This is necessary because PDAs don't have private keys to sign transactions.
-            self.token_program.to_account_info(), //The account info of the Token program, which will process the token transfer.
+            self.token_program_a.to_account_info(), //The account info of the Token program, which will process the token transfer.
             transfer_accounts, //A struct specifying the accounts involved in the transfer.
             &signer_seeds, //The seeds array used to sign the transaction on behalf of the PDA.
         );
-        //Transfers the entire amount from the vault to the taker's account.
-        transfer(cpi_ctx, self.vault.amount)
-            .map_err(|_| return error!(EscrowError::WithdrawFailed))?;
+        //Transfers the amount released by this fill from the vault to the taker's account.
+        transfer_checked(cpi_ctx, amount_a, self.mint_a.decimals)
+            .map_err(|_| error!(EscrowError::WithdrawFailed))?;
+
+        if is_native_mint(&self.mint_a.key()) && self.escrow.vesting.is_none() {
+            self.unwrap_native()?; //Wrapped SOL is handed to the taker as native SOL.
+        }
         Ok(())
     }
+
+    //Starts the schedule of a vested offer on what withdraw locked in the vesting vault. Other offers have nothing to start.
+    pub fn start_vesting(&mut self, bump: u8) -> Result<()> {
+        let Some(terms) = self.escrow.vesting else {
+            return Ok(());
+        };
+        let (Some(vesting), Some(vesting_vault)) = (self.vesting.as_mut(), self.vesting_vault.as_mut()) else {
+            return err!(EscrowError::VestingAccountsMismatch);
+        };
+        vesting_vault.reload()?; //Less than the fill released for Token-2022 mints with a transfer fee.
+
+        let now = Clock::get()?.unix_timestamp;
+        vesting.set_inner(Vesting {
+            beneficiary: self.taker.key(),
+            mint: self.mint_a.key(),
+            escrow: self.escrow.key(),
+            total: vesting_vault.amount,
+            claimed: 0,
+            start_at: now,
+            cliff_at: now.checked_add(terms.cliff).ok_or(EscrowError::ArithmeticOverflow)?,
+            end_at: now.checked_add(terms.duration).ok_or(EscrowError::ArithmeticOverflow)?,
+            vesting_bump: bump,
+        });
+
+        emit!(VestingStarted {
+            vesting: vesting.key(),
+            escrow: self.escrow.key(),
+            beneficiary: self.taker.key(),
+            mint: self.mint_a.key(),
+            total: vesting.total,
+            cliff_at: vesting.cliff_at,
+            end_at: vesting.end_at,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+
+    //Closes the taker's wrapped SOL account, which turns its whole balance back into lamports on the taker's wallet.
+    fn unwrap_native(&mut self) -> Result<()> {
+        let close_accounts = CloseAccount {
+            account: self.taker_ata_a.to_account_info(),
+            destination: self.taker.to_account_info(),
+            authority: self.taker.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(self.token_program_a.to_account_info(), close_accounts);
+
+        close_account(cpi_ctx).map_err(|_| error!(EscrowError::WithdrawFailed))
+    }
 //Prepares to close the vault account.
     pub fn close_vault(&mut self) -> Result<()> { //Closes the vault account, transferring any remaining lamports (Solana's smallest unit of currency) to the taker.
-        let close_accounts = CloseAccount { //Initializes a variable close_accounts with a CloseAccount struct. This struct is used to specify the accounts involved in closing a token account.
-            account: self.vault.to_account_info(), //Sets the account field to the vault account's information. This is the account that will be closed. to_account_info() is a method that converts the vault account into a format that can be used in a Cross-Program Invocation (CPI).
-            destination: self.taker.to_account_info(), //Sets the destination field to the taker account's information. When the vault account is closed, any remaining lamports (the smallest unit of currency in Solana) will be transferred to this destination account.
-            authority: self.escrow.to_account_info(), //Sets the authority field to the escrow account's information. The escrow account, likely a Program Derived Account (PDA), is the authority over the vault account and is required to authorize its closure.
-        };
-//Similar to withdraw, sets up the seeds and CPI context for closing the account.
+        self.vault.reload()?; //What is left after the last withdraw was sent to the vault on top of the deposit.
+        let surplus = self.vault.amount;
+        if surplus > 0 {
+            self.sweep_surplus(surplus)?;
+        }
+
+//Similar to withdraw, sets up the seeds for closing the account.
            //Purpose: signer_seeds are used to generate a Program Derived Address (PDA) for signing transactions that require access to an account controlled by the program (like the vault).
            let signer_seeds: [&[&[u8]]; 1] = [&[  //Sets up the seeds for signing the transaction, necessary for operations involving a PDA (like the vault).
            b"escrow", // A static seed, usually a string literal that identifies the purpose of the PDA.
@@ -133,15 +517,108 @@ impl<'info> Take<'info> {
            &[self.escrow.escrow_bump], // The bump seed used in conjunction with the other seeds to generate the PDA.
        ]];
 //Closing the Vault:
-        let cpi_ctx_close = CpiContext::new_with_signer( //Similar to cpi_ctx in withdraw, but specifically set up for closing the vault account.
-            self.token_program.to_account_info(),
-            close_accounts,
+        close_vault( //The escrow PDA, which owns the vault, closes it and its rent goes to the taker.
+            &self.escrow.to_account_info(),
             &signer_seeds,
-        );
+            &self.mint_a.to_account_info(),
+            &self.vault.to_account_info(),
+            &self.taker.to_account_info(),
+            &self.token_program_a.to_account_info(),
+        )?;
 
-        close_account(cpi_ctx_close).map_err(|_| return error!(EscrowError::CloseVaultFailed))?;
+        if surplus > 0 && self.maker_ata_a.is_none() {
+            let cpi_ctx = CpiContext::new(
+                self.system_program.to_account_info(),
+                Transfer {
+                    from: self.taker.to_account_info(),
+                    to: self.maker.to_account_info(),
+                },
+            );
+            transfer(cpi_ctx, surplus)?; //Closing the wrapped SOL vault unwrapped the surplus to the taker, it is passed on to the maker.
+        }
         Ok(())
     }
+
+    //Returns tokens sent to the vault on top of the deposit to the maker, so a taker only ever receives what the maker offered.
+    fn sweep_surplus(&mut self, surplus: u64) -> Result<()> {
+        let Some(maker_ata_a) = &self.maker_ata_a else {
+            require!(is_native_mint(&self.mint_a.key()), EscrowError::NativeMintRequired);
+            return Ok(()); //Wrapped SOL is unwrapped with the vault and forwarded in close_vault.
+        };
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"escrow",
+            self.maker.to_account_info().key.as_ref(),
+            &self.escrow.seed.to_le_bytes()[..],
+            &[self.escrow.escrow_bump],
+        ]];
+        let transfer_accounts = TransferChecked {
+            from: self.vault.to_account_info(),
+            mint: self.mint_a.to_account_info(),
+            to: maker_ata_a.to_account_info(),
+            authority: self.escrow.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(self.token_program_a.to_account_info(), transfer_accounts, &signer_seeds);
+        transfer_checked(cpi_ctx, surplus, self.mint_a.decimals)
+            .map_err(|_| error!(EscrowError::RefundFailed))
+    }
+
+    //Adds a fill to the trading history of both parties: the volume of both mints, the taker's fill and, once nothing is left, the maker's filled offer.
+    pub fn record_fill(&mut self, amount_a: u64, amount_b: u64, maker_bump: u8, taker_bump: u8) -> Result<()> {
+        self.maker_stats.open(self.maker.key(), maker_bump);
+        if self.escrow.remaining_b == 0 {
+            self.maker_stats.offers_filled = self.maker_stats.offers_filled.checked_add(1).ok_or(EscrowError::ArithmeticOverflow)?;
+        }
+        self.maker_stats.add_volume(self.mint_a.key(), amount_a);
+        self.maker_stats.add_volume(self.mint_b.key(), amount_b);
+
+        if self.taker_stats.key() == self.maker_stats.key() {
+            *self.taker_stats = (*self.maker_stats).clone(); //Both are written back on exit, taker_stats last, so it has to carry the maker's side too.
+        }
+        self.taker_stats.open(self.taker.key(), taker_bump);
+        self.taker_stats.fills_taken = self.taker_stats.fills_taken.checked_add(1).ok_or(EscrowError::ArithmeticOverflow)?;
+        self.taker_stats.add_volume(self.mint_a.key(), amount_a);
+        self.taker_stats.add_volume(self.mint_b.key(), amount_b);
+        Ok(())
+    }
+
+    //Records a fill. Called before the escrow is closed so the final fill is reported too.
+    pub fn emit_taken(&self, amount_a: u64, amount_b: u64, fee: u64, royalty: u64) -> Result<()> {
+        emit!(EscrowTaken {
+            escrow: self.escrow.key(),
+            maker: self.maker.key(),
+            taker: self.taker.key(),
+            mint_a: self.mint_a.key(),
+            mint_b: self.mint_b.key(),
+            amount_a,
+            amount_b,
+            fee,
+            royalty,
+            remaining_a: self.escrow.remaining_a,
+            remaining_b: self.escrow.remaining_b,
+            seed: self.escrow.seed,
+            slot: Clock::get()?.slot,
+        });
+
+        if let Some(auction) = self.escrow.auction {
+            let clock = Clock::get()?;
+            emit!(AuctionFilled { //Lets buyers check the price they paid against the auction terms.
+                escrow: self.escrow.key(),
+                taker: self.taker.key(),
+                price: auction.price_at(clock.unix_timestamp).ok_or(EscrowError::ArithmeticOverflow)?,
+                amount_a,
+                amount_b,
+                unix_timestamp: clock.unix_timestamp,
+                slot: clock.slot,
+            });
+        }
+        Ok(())
+    }
+
+    //Closes the escrow account once the offer is fully filled, returning its rent to the maker.
+    pub fn close_escrow(&mut self) -> Result<()> {
+        self.escrow.close(self.maker.to_account_info())
+    }
 }
 
 //signer_seeds are crucial for operations involving PDAs, allowing the program to sign transactions on behalf of these accounts.
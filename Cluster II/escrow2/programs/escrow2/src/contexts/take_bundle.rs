@@ -0,0 +1,214 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{transfer_checked, TransferChecked},
+};
+
+use crate::{
+    constants::{BUNDLE_REQUESTED_LEG_ACCOUNTS, BUNDLE_TAKEN_LEG_ACCOUNTS},
+    error::EscrowError,
+    events::BundleTaken,
+    state::{Bundle, Config},
+    utils::{
+        amount_with_transfer_fee, close_vault, init_leg_ata, leg_mint_decimals,
+        leg_token_amount, protocol_fee, require_leg_ata,
+    },
+};
+
+#[derive(Accounts)] // Anchor macro that prepares the struct to hold account information for the take_bundle instruction.
+pub struct TakeBundle<'info> { //The legs are passed as remaining accounts, the offered legs first and then the requested legs, both in the order stored on the bundle.
+    #[account(mut)] //Pays for any token account that has to be created, like in take.
+    pub taker: Signer<'info>,
+
+    #[account(mut)] //Receives the rent of the bundle account.
+    pub maker: SystemAccount<'info>,
+
+    #[account(
+        seeds = [b"config"], //The program wide config holding the protocol fee.
+        bump,
+    )]
+    /// CHECK: The address is checked above, Config::fee_bps checks the owner and the discriminator before reading it.
+    pub config: UncheckedAccount<'info>, //Not created until the admin runs initialize_config, fills are free until then.
+
+    /// CHECK: Compared against config.treasury by Config::fee_bps and used as token account authority.
+    pub treasury: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        close = maker, //Bundles are filled whole, so the account is always closed.
+        seeds = [b"bundle", maker.key().as_ref(), bundle.seed.to_le_bytes().as_ref()],
+        bump = bundle.bundle_bump,
+        has_one = maker, //Ensures that the maker passed in is the one stored in the bundle.
+        constraint = bundle.allowed_taker.unwrap_or(taker.key()) == taker.key() @ EscrowError::UnauthorizedTaker, //Private bundles can only be taken by the designated taker.
+    )]
+    pub bundle: Account<'info, Bundle>,
+
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+impl<'info> TakeBundle<'info> {
+    //Splits the remaining accounts into the offered and the requested legs. Any leg missing or left over fails the whole fill.
+    pub fn split_legs<'a>(&self, legs: &'a [AccountInfo<'info>]) -> Result<(&'a [AccountInfo<'info>], &'a [AccountInfo<'info>])> {
+        require!(
+            Clock::get()?.unix_timestamp <= self.bundle.expires_at,
+            EscrowError::OfferExpired
+        ); //Stale bundles can only be refunded.
+
+        let offered_accounts = self.bundle.offered.len() * BUNDLE_TAKEN_LEG_ACCOUNTS;
+        let requested_accounts = self.bundle.requested.len() * BUNDLE_REQUESTED_LEG_ACCOUNTS;
+        require!(
+            legs.len() == offered_accounts + requested_accounts,
+            EscrowError::BundleAccountsMismatch
+        );
+        Ok(legs.split_at(offered_accounts))
+    }
+
+    //Pays the maker every requested leg, routing the protocol fee of each one to the treasury. Returns the fee charged on each leg.
+    //Remaining accounts per leg: mint, token program, taker's token account, maker's and treasury's associated token accounts.
+    pub fn deposit(&self, legs: &[AccountInfo<'info>]) -> Result<Vec<u64>> {
+        let fee_bps = Config::fee_bps(&self.config, &self.treasury.key())?;
+        let mut fees = Vec::with_capacity(self.bundle.requested.len());
+        for (leg, accounts) in self.bundle.requested.iter().zip(legs.chunks_exact(BUNDLE_REQUESTED_LEG_ACCOUNTS)) {
+            let [mint, token_program, taker_ata, maker_ata, treasury_ata] = accounts else {
+                return err!(EscrowError::BundleAccountsMismatch);
+            };
+            require_keys_eq!(mint.key(), leg.mint, EscrowError::BundleAccountsMismatch);
+            let decimals = leg_mint_decimals(mint, token_program)?;
+
+            let fee = protocol_fee(leg.amount, fee_bps).ok_or(EscrowError::InvalidFee)?; //The fee comes out of the maker's side of the leg.
+            let to_maker = leg
+                .amount
+                .checked_sub(fee)
+                .ok_or(EscrowError::ArithmeticOverflow)?;
+
+            init_leg_ata(
+                &self.taker.to_account_info(),
+                maker_ata,
+                &self.maker.to_account_info(),
+                mint,
+                &self.system_program.to_account_info(),
+                token_program,
+                &self.associated_token_program.to_account_info(),
+            )?;
+            self.transfer_leg(mint, token_program, taker_ata, maker_ata, to_maker, decimals)?;
+
+            if fee > 0 {
+                init_leg_ata(
+                    &self.taker.to_account_info(),
+                    treasury_ata,
+                    &self.treasury.to_account_info(),
+                    mint,
+                    &self.system_program.to_account_info(),
+                    token_program,
+                    &self.associated_token_program.to_account_info(),
+                )?; //Only created when there is a fee to collect.
+                self.transfer_leg(mint, token_program, taker_ata, treasury_ata, fee, decimals)?;
+            }
+            fees.push(fee);
+        }
+        Ok(fees)
+    }
+
+    //Moves one requested leg from the taker. The recipient is owed `amount` after any Token-2022 transfer fee, so the taker sends enough to cover the fee on top.
+    fn transfer_leg(&self, mint: &AccountInfo<'info>, token_program: &AccountInfo<'info>, from: &AccountInfo<'info>, to: &AccountInfo<'info>, amount: u64, decimals: u8) -> Result<()> {
+        let amount_with_fee = amount_with_transfer_fee(mint, amount)?;
+
+        let transfer_accounts = TransferChecked {
+            from: from.clone(),
+            mint: mint.clone(),
+            to: to.clone(),
+            authority: self.taker.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(token_program.clone(), transfer_accounts);
+
+        transfer_checked(cpi_ctx, amount_with_fee, decimals)
+            .map_err(|_| error!(EscrowError::DepositFailed))
+    }
+
+    //Releases the deposit of every leg to the taker's associated token accounts and closes the vaults, sending their rent to the taker like take does.
+    //Tokens sent to a vault after make_bundle were never offered, they go back to the maker like in take.
+    //Remaining accounts per leg: mint, token program, vault, the taker's and the maker's associated token accounts.
+    pub fn withdraw(&self, legs: &[AccountInfo<'info>]) -> Result<()> {
+        for (leg, accounts) in self.bundle.offered.iter().zip(legs.chunks_exact(BUNDLE_TAKEN_LEG_ACCOUNTS)) {
+            let [mint, token_program, vault, taker_ata, maker_ata] = accounts else {
+                return err!(EscrowError::BundleAccountsMismatch);
+            };
+            require_keys_eq!(mint.key(), leg.mint, EscrowError::BundleAccountsMismatch);
+            let decimals = leg_mint_decimals(mint, token_program)?;
+            require_leg_ata(vault, &self.bundle.key(), mint, token_program)?;
+            init_leg_ata(
+                &self.taker.to_account_info(),
+                taker_ata,
+                &self.taker.to_account_info(),
+                mint,
+                &self.system_program.to_account_info(),
+                token_program,
+                &self.associated_token_program.to_account_info(),
+            )?;
+
+            let signer_seeds: [&[&[u8]]; 1] = [&[
+                b"bundle",
+                self.maker.to_account_info().key.as_ref(),
+                &self.bundle.seed.to_le_bytes()[..],
+                &[self.bundle.bundle_bump],
+            ]];
+
+            let transfer_accounts = TransferChecked {
+                from: vault.clone(),
+                mint: mint.clone(),
+                to: taker_ata.clone(),
+                authority: self.bundle.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new_with_signer(token_program.clone(), transfer_accounts, &signer_seeds);
+            transfer_checked(cpi_ctx, leg.amount, decimals) //What reached the vault at make_bundle, the amount the taker was offered.
+                .map_err(|_| error!(EscrowError::WithdrawFailed))?;
+
+            let surplus = leg_token_amount(vault)?;
+            if surplus > 0 {
+                init_leg_ata(
+                    &self.taker.to_account_info(),
+                    maker_ata,
+                    &self.maker.to_account_info(),
+                    mint,
+                    &self.system_program.to_account_info(),
+                    token_program,
+                    &self.associated_token_program.to_account_info(),
+                )?; //Only created when there is a surplus to return.
+                let transfer_accounts = TransferChecked {
+                    from: vault.clone(),
+                    mint: mint.clone(),
+                    to: maker_ata.clone(),
+                    authority: self.bundle.to_account_info(),
+                };
+                let cpi_ctx = CpiContext::new_with_signer(token_program.clone(), transfer_accounts, &signer_seeds);
+                transfer_checked(cpi_ctx, surplus, decimals)
+                    .map_err(|_| error!(EscrowError::RefundFailed))?;
+            }
+
+            close_vault(
+                &self.bundle.to_account_info(),
+                &signer_seeds,
+                mint,
+                vault,
+                &self.taker.to_account_info(),
+                token_program,
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn emit_taken(&self, fees: Vec<u64>) -> Result<()> {
+        emit!(BundleTaken {
+            bundle: self.bundle.key(),
+            maker: self.maker.key(),
+            taker: self.taker.key(),
+            offered: self.bundle.offered.clone(),
+            requested: self.bundle.requested.clone(),
+            fees,
+            seed: self.bundle.seed,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+}
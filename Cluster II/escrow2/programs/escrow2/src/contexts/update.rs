@@ -0,0 +1,164 @@
+use anchor_lang::{
+    prelude::*,
+    system_program::{transfer, Transfer},
+};
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{
+        close_account, sync_native, transfer_checked, CloseAccount, Mint, SyncNative,
+        TokenAccount, TokenInterface, TransferChecked,
+    },
+};
+
+use crate::{error::EscrowError, events::EscrowUpdated, state::Escrow, utils::is_native_mint};
+
+#[derive(Accounts)] // Anchor macro that prepares the struct to hold account information for the update instruction.
+pub struct Update<'info> {
+    #[account(mut)] //The maker pays for top ups and for its token account if it has to be created.
+    pub maker: Signer<'info>, //Only the maker can change the terms of its offer.
+
+    #[account(mint::token_program = token_program)] //mint_a must belong to the token program passed in.
+    pub mint_a: InterfaceAccount<'info, Mint>, //The token mint held in the vault.
+
+    #[account(
+        init_if_needed, //Initializes the account if it doesn't exist, e.g. when part of a native SOL deposit is withdrawn.
+        payer = maker, //The maker pays for the account creation.
+        associated_token::mint = mint_a, //Links to the mint_a token mint.
+        associated_token::authority = maker, //The maker is the authority of this account.
+        associated_token::token_program = token_program, //Created by the token program that owns mint_a.
+    )]
+    pub maker_ata_a: InterfaceAccount<'info, TokenAccount>, //Source of top ups and destination of withdrawals.
+
+    #[account(
+        mut, //The new terms are written to the escrow.
+        has_one = maker, //Ensures the signer is the maker stored in the escrow account.
+        has_one = mint_a, //Ensures the escrow account is associated with the mint_a token mint.
+        seeds = [b"escrow", maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()], //Used for deriving the account's address using the PDA mechanism.
+        bump = escrow.escrow_bump, //Used for deriving the account's address using the PDA mechanism.
+        constraint = escrow.arbitration.is_none() @ EscrowError::ArbitratedEscrow, //The recipient was promised what the maker funded.
+        constraint = escrow.oracle.is_none() && escrow.auction.is_none() @ EscrowError::PricedOfferUpdate, //Takes reprice these offers, which would overwrite the new receive.
+    )]
+    pub escrow: Account<'info, Escrow>, //Holds the state of the escrow transaction.
+
+    #[account(
+        mut, //Indicates mutability.
+        associated_token::mint = mint_a, //Links the vault to the mint_a token mint.
+        associated_token::authority = escrow, //Sets the escrow account as the authority of the vault.
+        associated_token::token_program = token_program, //The vault belongs to the token program that owns mint_a.
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>, //The vault account where the maker's tokens are held.
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+impl<'info> Update<'info> {
+    //Changes the amount of mint_b the maker asks for and tops up or withdraws from the vault so deposit is what remains on offer.
+    pub fn update(&mut self, receive: u64, deposit: u64) -> Result<()> {
+        require!(receive > 0 && deposit > 0, EscrowError::ZeroAmount); //An offer must both give and ask for something, use refund to cancel it.
+
+        let old_deposit = self.escrow.remaining_a;
+        let old_receive = self.escrow.remaining_b;
+
+        if deposit > old_deposit {
+            let top_up = deposit - old_deposit;
+            let received = self.top_up(top_up)?; //Transfer fees can make the vault receive less than the top up.
+            self.escrow.remaining_a = old_deposit
+                .checked_add(received)
+                .ok_or(EscrowError::ArithmeticOverflow)?;
+        } else if deposit < old_deposit {
+            self.withdraw(old_deposit - deposit)?;
+            self.escrow.remaining_a = deposit;
+        }
+        self.escrow.remaining_b = receive;
+
+        emit!(EscrowUpdated {
+            escrow: self.escrow.key(),
+            maker: self.maker.key(),
+            old_deposit,
+            new_deposit: self.escrow.remaining_a,
+            old_receive,
+            new_receive: receive,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+
+    //Moves more of mint_a into the vault and returns how much arrived. Wrapped SOL is topped up from the maker's lamports.
+    fn top_up(&mut self, amount: u64) -> Result<u64> {
+        let before = self.vault.amount;
+
+        if is_native_mint(&self.mint_a.key()) {
+            require!(self.maker.lamports() >= amount, EscrowError::InsufficientBalance);
+
+            let cpi_accounts = Transfer {
+                from: self.maker.to_account_info(),
+                to: self.vault.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(self.system_program.to_account_info(), cpi_accounts);
+            transfer(cpi_ctx, amount)?;
+
+            let cpi_accounts = SyncNative {
+                account: self.vault.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+            sync_native(cpi_ctx)?;
+        } else {
+            require!(self.maker_ata_a.amount >= amount, EscrowError::InsufficientBalance);
+
+            let cpi_accounts = TransferChecked {
+                from: self.maker_ata_a.to_account_info(),
+                mint: self.mint_a.to_account_info(),
+                to: self.vault.to_account_info(),
+                authority: self.maker.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+            transfer_checked(cpi_ctx, amount, self.mint_a.decimals)
+                .map_err(|_| error!(EscrowError::DepositFailed))?;
+        }
+
+        self.vault.reload()?; //Re-reads the vault so the amount reflects the transfer above.
+        self.vault
+            .amount
+            .checked_sub(before)
+            .ok_or(error!(EscrowError::ArithmeticOverflow))
+    }
+
+    //Returns part of the deposit to the maker. Wrapped SOL is unwrapped to the maker's wallet.
+    fn withdraw(&mut self, amount: u64) -> Result<()> {
+        let transfer_accounts = TransferChecked {
+            from: self.vault.to_account_info(),
+            mint: self.mint_a.to_account_info(),
+            to: self.maker_ata_a.to_account_info(),
+            authority: self.escrow.to_account_info(),
+        };
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"escrow",
+            self.maker.to_account_info().key.as_ref(),
+            &self.escrow.seed.to_le_bytes()[..],
+            &[self.escrow.escrow_bump],
+        ]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            transfer_accounts,
+            &signer_seeds,
+        );
+
+        transfer_checked(cpi_ctx, amount, self.mint_a.decimals)
+            .map_err(|_| error!(EscrowError::WithdrawFailed))?;
+
+        if is_native_mint(&self.mint_a.key()) {
+            let close_accounts = CloseAccount {
+                account: self.maker_ata_a.to_account_info(),
+                destination: self.maker.to_account_info(),
+                authority: self.maker.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), close_accounts);
+            close_account(cpi_ctx).map_err(|_| error!(EscrowError::WithdrawFailed))?;
+        }
+        Ok(())
+    }
+}
@@ -8,8 +8,110 @@ pub enum EscrowError {
     RefundFailed,
     #[msg("Withdraw of tokens failed")]
     WithdrawFailed,
-    #[msg("Unable to get escrow bump")]
-    EscrowBumpError,
     #[msg("Failed to close the vault account")]
     CloseVaultFailed,
-}
\ No newline at end of file
+    #[msg("Escrow account is not in the legacy layout")]
+    NotLegacyEscrow,
+    #[msg("Fill amount must be greater than zero and no more than the remaining amount")]
+    InvalidFillAmount,
+    #[msg("Fill is too small to release any tokens from the vault")]
+    FillTooSmall,
+    #[msg("Expiry must be in the future")]
+    InvalidExpiry,
+    #[msg("Offer has expired")]
+    OfferExpired,
+    #[msg("Offer has not expired yet")]
+    OfferNotExpired,
+    #[msg("Signer is not the designated taker for this offer")]
+    UnauthorizedTaker,
+    #[msg("Unable to calculate the transfer fee for this mint")]
+    TransferFeeCalculationFailed,
+    #[msg("Amount must be greater than zero")]
+    ZeroAmount,
+    #[msg("Offered and requested mints must be different")]
+    SameMint,
+    #[msg("Maker does not hold enough tokens for the deposit")]
+    InsufficientBalance,
+    #[msg("Arithmetic overflow")]
+    ArithmeticOverflow,
+    #[msg("Token accounts can only be left out for native SOL legs")]
+    NativeMintRequired,
+    #[msg("Fee must be between 0 and 1000 basis points")]
+    InvalidFee,
+    #[msg("Treasury token account is required to collect the protocol fee")]
+    TreasuryAccountRequired,
+    #[msg("Market page is full, list the escrow on another page")]
+    MarketPageFull,
+    #[msg("Market page does not list this escrow")]
+    MarketPageMismatch,
+    #[msg("Bundles need 1 to 4 distinct mints on each side, each with a non-zero amount")]
+    InvalidBundleLegs,
+    #[msg("Remaining accounts do not match the bundle legs")]
+    BundleAccountsMismatch,
+    #[msg("Mint is not an NFT, it needs a supply of 1 and 0 decimals")]
+    NotAnNft,
+    #[msg("Token Metadata account is required for NFT offers")]
+    NftMetadataRequired,
+    #[msg("Token Metadata account could not be read for this mint")]
+    InvalidMetadata,
+    #[msg("NFT is not a verified member of the collection")]
+    CollectionMismatch,
+    #[msg("Remaining accounts do not match the NFT creators")]
+    RoyaltyAccountsMismatch,
+    #[msg("Counter-offers are not available for NFT offers")]
+    NftCounterOffer,
+    #[msg("Escrow changed since the counter-offer was made")]
+    StaleCounterOffer,
+    #[msg("Oracle terms need a spread above -100%, a staleness bound and a confidence bound of at most 100%")]
+    InvalidOracleTerms,
+    #[msg("Price feed account is missing or not the one set on the escrow")]
+    PriceFeedMismatch,
+    #[msg("Price feed could not be read or has no trading price")]
+    InvalidPriceFeed,
+    #[msg("Price feed is older than the escrow allows")]
+    StalePrice,
+    #[msg("Price feed confidence interval is wider than the escrow allows")]
+    PriceConfidenceTooWide,
+    #[msg("Auctions need an end price above zero, a start price at or above it and an end after the start")]
+    InvalidAuctionTerms,
+    #[msg("Remaining accounts do not match the escrows to refund")]
+    RefundAccountsMismatch,
+    #[msg("Fill would give the taker less mint_a or cost more mint_b than the taker allowed")]
+    SlippageExceeded,
+    #[msg("Seed is below the maker's next seed, it may already be in use")]
+    SeedAlreadyUsed,
+    #[msg("Vesting needs a duration above zero and a cliff between zero and the duration")]
+    InvalidVestingTerms,
+    #[msg("Vesting accounts must be passed for vested offers and left out for the others")]
+    VestingAccountsMismatch,
+    #[msg("Vested offers can only be filled whole")]
+    VestedPartialFill,
+    #[msg("Counter-offers are not available for vested offers")]
+    VestedCounterOffer,
+    #[msg("Nothing has unlocked since the last claim")]
+    NothingToClaim,
+    #[msg("Arbiter and recipient must differ from each other and the maker")]
+    InvalidArbiterTerms,
+    #[msg("Arbitrated escrows can only be settled by their arbiter")]
+    ArbitratedEscrow,
+    #[msg("Signer is not the arbiter of this escrow")]
+    NotArbiter,
+    #[msg("Only the maker or the recipient of an arbitrated escrow can raise a dispute")]
+    NotEscrowParty,
+    #[msg("Recipient does not match the escrow")]
+    RecipientMismatch,
+    #[msg("Escrow is already disputed")]
+    AlreadyDisputed,
+    #[msg("Token account of the party the escrow is settled to is required")]
+    SettlementAccountRequired,
+    #[msg("Only the upgrade authority of the program can initialize the config")]
+    NotUpgradeAuthority,
+    #[msg("Treasury does not match the config")]
+    TreasuryMismatch,
+    #[msg("Offers priced by an oracle or an auction cannot be updated, refund and make them again instead")]
+    PricedOfferUpdate,
+    #[msg("Only arbitrated escrows can be reclaimed, use refund for offers")]
+    NotArbitrated,
+    #[msg("Vault holds less than the stated deposit")]
+    DepositNotInVault,
+}
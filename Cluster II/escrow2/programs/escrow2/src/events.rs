@@ -0,0 +1,198 @@
+use anchor_lang::prelude::*;
+
+use crate::state::{BundleLeg, Settlement};
+
+#[event] //Emitted when the maker changes the terms of an open offer with the update instruction.
+pub struct EscrowUpdated {
+    pub escrow: Pubkey, //The escrow account that was updated.
+    pub maker: Pubkey, //The maker who signed the update.
+    pub old_deposit: u64, //Amount of mint_a that was available to takers before the update.
+    pub new_deposit: u64, //Amount of mint_a available to takers after the update.
+    pub old_receive: u64, //Amount of mint_b the maker was asking for before the update.
+    pub new_receive: u64, //Amount of mint_b the maker is asking for after the update.
+    pub slot: u64, //Slot the update happened in.
+}
+
+#[event] //Emitted by make once the deposit is in the vault.
+pub struct EscrowCreated {
+    pub escrow: Pubkey, //The new escrow account.
+    pub maker: Pubkey, //The maker who created the offer.
+    pub mint_a: Pubkey, //The mint the maker deposited.
+    pub mint_b: Pubkey, //The mint the maker asks for.
+    pub deposit: u64, //Amount of mint_a that reached the vault.
+    pub receive: u64, //Amount of mint_b the maker asks for.
+    pub seed: u64, //Seed used to derive the escrow address.
+    pub expires_at: i64, //Unix timestamp after which the offer can only be refunded.
+    pub allowed_taker: Option<Pubkey>, //Designated taker for private offers.
+    pub slot: u64, //Slot the offer was created in.
+}
+
+#[event] //Emitted by take and take_partial for every fill.
+pub struct EscrowTaken {
+    pub escrow: Pubkey, //The escrow that was filled.
+    pub maker: Pubkey, //The maker who created the offer.
+    pub taker: Pubkey, //The taker who filled it.
+    pub mint_a: Pubkey, //The mint released from the vault.
+    pub mint_b: Pubkey, //The mint paid to the maker.
+    pub amount_a: u64, //Amount of mint_a sent from the vault to the taker.
+    pub amount_b: u64, //Amount of mint_b paid by the taker, fee and royalty included.
+    pub fee: u64, //Part of amount_b sent to the treasury.
+    pub royalty: u64, //Part of amount_b paid to the creators of an NFT, the maker received the rest.
+    pub remaining_a: u64, //Amount of mint_a still on offer, zero once the escrow is closed.
+    pub remaining_b: u64, //Amount of mint_b the maker is still owed, zero once the escrow is closed.
+    pub seed: u64, //Seed used to derive the escrow address.
+    pub slot: u64, //Slot the fill happened in.
+}
+
+#[event] //Emitted by take and take_partial next to EscrowTaken for every fill of a Dutch auction.
+pub struct AuctionFilled {
+    pub escrow: Pubkey, //The auction that was filled.
+    pub taker: Pubkey, //The taker who filled it.
+    pub price: u64, //Amount of mint_b the whole auction was priced at when it was filled.
+    pub amount_a: u64, //Amount of mint_a sent from the vault to the taker.
+    pub amount_b: u64, //Amount of mint_b paid by the taker, fee and royalty included.
+    pub unix_timestamp: i64, //Clock time the price was computed at.
+    pub slot: u64, //Slot the fill happened in.
+}
+
+#[event] //Emitted by refund, refund_expired, refund_many and reclaim before the escrow is closed.
+pub struct EscrowRefunded {
+    pub escrow: Pubkey, //The escrow that was refunded.
+    pub maker: Pubkey, //The maker the tokens went back to.
+    pub refunded_by: Pubkey, //The maker for refund and reclaim, or whoever cranked refund_expired.
+    pub mint_a: Pubkey, //The mint returned to the maker.
+    pub amount: u64, //Amount of mint_a returned from the vault.
+    pub seed: u64, //Seed used to derive the escrow address.
+    pub slot: u64, //Slot the refund happened in.
+}
+
+#[event] //Emitted by refund_many once every escrow in the batch was handled.
+pub struct EscrowsRefunded {
+    pub maker: Pubkey, //The maker the tokens went back to.
+    pub refunded: Vec<Pubkey>, //Escrows refunded and closed, in the order they were passed.
+    pub skipped: Vec<Pubkey>, //Escrows that were already closed, by a take or an earlier refund.
+    pub slot: u64, //Slot the refund happened in.
+}
+
+#[event] //Emitted by make_bundle once every offered mint is in its vault.
+pub struct BundleCreated {
+    pub bundle: Pubkey, //The new bundle account.
+    pub maker: Pubkey, //The maker who created the bundle.
+    pub offered: Vec<BundleLeg>, //Mints and amounts that reached the vaults.
+    pub requested: Vec<BundleLeg>, //Mints and amounts the maker asks for.
+    pub seed: u64, //Seed used to derive the bundle address.
+    pub expires_at: i64, //Unix timestamp after which the bundle can only be refunded.
+    pub allowed_taker: Option<Pubkey>, //Designated taker for private bundles.
+    pub slot: u64, //Slot the bundle was created in.
+}
+
+#[event] //Emitted by take_bundle. Bundles are always filled whole.
+pub struct BundleTaken {
+    pub bundle: Pubkey, //The bundle that was filled.
+    pub maker: Pubkey, //The maker who created the bundle.
+    pub taker: Pubkey, //The taker who filled it.
+    pub offered: Vec<BundleLeg>, //Mints and amounts sent from the vaults to the taker.
+    pub requested: Vec<BundleLeg>, //Mints and amounts paid by the taker, fees included.
+    pub fees: Vec<u64>, //Part of each requested leg sent to the treasury, in the order of requested.
+    pub seed: u64, //Seed used to derive the bundle address.
+    pub slot: u64, //Slot the fill happened in.
+}
+
+#[event] //Emitted by refund_bundle and refund_bundle_expired before the bundle is closed.
+pub struct BundleRefunded {
+    pub bundle: Pubkey, //The bundle that was refunded.
+    pub maker: Pubkey, //The maker the tokens went back to.
+    pub refunded_by: Pubkey, //The maker for refund_bundle, or whoever cranked refund_bundle_expired.
+    pub offered: Vec<BundleLeg>, //Mints and amounts returned from the vaults.
+    pub seed: u64, //Seed used to derive the bundle address.
+    pub slot: u64, //Slot the refund happened in.
+}
+
+#[event] //Emitted by counter_offer once the proposer's deposit is in the counter vault.
+pub struct CounterOffered {
+    pub counter_offer: Pubkey, //The new counter-offer account.
+    pub escrow: Pubkey, //The escrow it proposes to fill.
+    pub proposer: Pubkey, //The taker who proposed it.
+    pub receive: u64, //Amount of mint_b locked for the maker.
+    pub remaining_a: u64, //Amount of mint_a the proposer receives if the maker accepts.
+    pub slot: u64, //Slot the counter-offer was made in.
+}
+
+#[event] //Emitted by accept_counter before the escrow and the counter-offer are closed.
+pub struct CounterAccepted {
+    pub counter_offer: Pubkey, //The counter-offer that was accepted.
+    pub escrow: Pubkey, //The escrow it filled.
+    pub maker: Pubkey, //The maker who accepted.
+    pub proposer: Pubkey, //The taker who proposed it.
+    pub amount_a: u64, //Amount of mint_a sent from the vault to the proposer.
+    pub amount_b: u64, //Amount of mint_b released from the counter vault, fee included.
+    pub fee: u64, //Part of amount_b sent to the treasury, the maker received the rest.
+    pub slot: u64, //Slot the counter-offer was accepted in.
+}
+
+#[event] //Emitted by cancel_counter before the counter-offer is closed.
+pub struct CounterCancelled {
+    pub counter_offer: Pubkey, //The counter-offer that was cancelled.
+    pub escrow: Pubkey, //The escrow it was for, which may already be closed.
+    pub proposer: Pubkey, //The proposer the deposit went back to.
+    pub amount: u64, //Amount of mint_b returned from the counter vault.
+    pub slot: u64, //Slot the counter-offer was cancelled in.
+}
+
+#[event] //Emitted by take when a vested offer is filled and its mint_a is locked for the taker.
+pub struct VestingStarted {
+    pub vesting: Pubkey, //The new vesting account.
+    pub escrow: Pubkey, //The escrow that was filled.
+    pub beneficiary: Pubkey, //The taker the tokens unlock for.
+    pub mint: Pubkey, //The mint locked in the vesting vault.
+    pub total: u64, //Amount of mint that reached the vesting vault.
+    pub cliff_at: i64, //Unix timestamp before which nothing can be claimed.
+    pub end_at: i64, //Unix timestamp from which everything can be claimed.
+    pub slot: u64, //Slot the fill happened in.
+}
+
+#[event] //Emitted by claim_vested for every claim.
+pub struct VestedClaimed {
+    pub vesting: Pubkey, //The vesting account claimed from.
+    pub beneficiary: Pubkey, //The taker the tokens went to.
+    pub mint: Pubkey, //The mint released.
+    pub amount: u64, //Amount released from the vesting vault by this claim.
+    pub claimed: u64, //Amount released so far, equal to total once the vesting is closed.
+    pub total: u64, //Amount locked by the take.
+    pub slot: u64, //Slot the claim happened in.
+}
+
+#[event] //Emitted by make_arbitrated.
+pub struct ArbitratedEscrowCreated {
+    pub escrow: Pubkey, //The new escrow account.
+    pub maker: Pubkey, //The maker who funded it.
+    pub arbiter: Pubkey, //The arbiter who settles it.
+    pub recipient: Pubkey, //Receives the deposit if the arbiter releases it.
+    pub mint_a: Pubkey, //The mint the maker deposited.
+    pub deposit: u64, //Amount of mint_a that reached the vault.
+    pub expires_at: i64, //Unix timestamp after which the maker can reclaim the escrow unless it is disputed.
+    pub seed: u64, //Seed used to derive the escrow address.
+    pub slot: u64, //Slot the escrow was created in.
+}
+
+#[event] //Emitted by raise_dispute.
+pub struct DisputeRaised {
+    pub escrow: Pubkey, //The arbitrated escrow in dispute.
+    pub raised_by: Pubkey, //The maker or the recipient who raised it.
+    pub arbiter: Pubkey, //The arbiter expected to settle it.
+    pub slot: u64, //Slot the dispute was raised in.
+}
+
+#[event] //Emitted by release and arbiter_refund before the escrow is closed.
+pub struct EscrowSettled {
+    pub escrow: Pubkey, //The arbitrated escrow that was settled.
+    pub arbiter: Pubkey, //The arbiter who settled it.
+    pub maker: Pubkey, //The maker who funded it.
+    pub recipient: Pubkey, //The recipient named at make.
+    pub settlement: Settlement, //Released to the recipient or Refunded to the maker.
+    pub disputed: bool, //Whether a dispute was raised before the settlement.
+    pub mint_a: Pubkey, //The mint sent out of the vault.
+    pub amount: u64, //Amount of mint_a sent out of the vault.
+    pub seed: u64, //Seed used to derive the escrow address.
+    pub slot: u64, //Slot the settlement happened in.
+}
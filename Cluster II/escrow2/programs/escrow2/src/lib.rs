@@ -6,6 +6,10 @@ use contexts::*;
 pub mod state;
 pub mod error;
 pub mod constants;
+pub mod events;
+pub mod utils;
+
+use state::{ArbiterTerms, BundleLeg, MakeArgs, MarketPage, Settlement};
 
 
 
@@ -18,25 +22,175 @@ pub mod escrow2 {
     use super::*;
 
     //This function initializes the escrow transaction.
-    pub fn make(ctx: Context<Make>, seed: u64, deposit: u64, receive: u64) -> Result<()> {
-        ctx.accounts.deposit(deposit)?; // Ensures the maker deposits the specified amount (deposit) into the vault. If the deposit fails, the transaction will not proceed.
-        ctx.accounts.save_escrow(seed, receive, ctx.bumps.escrow)?; //Saves the escrow details, including the seed for PDA generation, the amount to be received by the taker, and the bump seed for the escrow account.
-        Ok(())
+    pub fn make(ctx: Context<Make>, args: MakeArgs) -> Result<()> {
+        let deposited = ctx.accounts.deposit(args.deposit)?; // Ensures the maker deposits the specified amount (deposit) into the vault. If the deposit fails, the transaction will not proceed.
+        ctx.accounts.save_escrow(args.seed, deposited, args.receive, args.expires_at, args.allowed_taker, ctx.bumps.escrow)?; //Saves the escrow details, including the seed for PDA generation, the amount to be received by the taker, and the bump seed for the escrow account. Only what reached the vault is offered to takers.
+        ctx.accounts.allocate_seed(args.seed, ctx.bumps.maker_state)?; //The next make has to use a higher seed.
+        ctx.accounts.record_offer(ctx.bumps.maker_stats)?;
+        ctx.accounts.check_nft(args.collection)?; //NFT offers are validated against their Token Metadata account.
+        ctx.accounts.set_kind(args.kind)?; //Pegged offers are priced from their feed and auctions from the clock on every take.
+        ctx.accounts.set_vesting(args.vesting)?; //Vested offers lock what the taker buys until it unlocks.
+        ctx.accounts.add_to_market(args.page, ctx.bumps.market_page) //Lists the offer on the mint pair's index page.
+    }
+
+    //Funds an escrow the arbiter later releases to the recipient or refunds to the maker. It cannot be taken, updated or refunded by the maker,
+    //only reclaimed once expires_at has passed without a dispute.
+    pub fn make_arbitrated(ctx: Context<MakeArbitrated>, seed: u64, deposit: u64, terms: ArbiterTerms, expires_at: i64) -> Result<()> {
+        let deposited = ctx.accounts.deposit(deposit)?;
+        ctx.accounts.save_escrow(seed, deposited, terms, expires_at, ctx.bumps.escrow)?;
+        ctx.accounts.allocate_seed(seed, ctx.bumps.maker_state)
     }
 
     //Facilitates the completion of the escrow transaction.
-    pub fn take(ctx: Context<Take>) -> Result<()> {
-        ctx.accounts.deposit()?; //Checks if the taker has deposited their required tokens (of type mint_b). If this fails, the transaction does not proceed.
-        ctx.accounts.withdraw()?; //Withdraws the tokens from the vault to the taker's account (taker_ata_a). This step is contingent on the successful deposit by the taker.
+    //NFT offers pay royalties to the creators passed in the remaining accounts.
+    //The fill fails unless the taker receives at least expected_receive_a of mint_a and pays at most max_pay_b of mint_b, transfer fees included.
+    pub fn take<'info>(ctx: Context<'_, '_, '_, 'info, Take<'info>>, expected_receive_a: u64, max_pay_b: u64) -> Result<()> {
+        ctx.accounts.reprice()?; //Pegged offers and auctions ask their current price for what is left.
+        let amount_b = ctx.accounts.escrow.remaining_b; //Takes whatever is left of the offer.
+        let amount_a = ctx.accounts.fill(amount_b)?; //Works out how much of mint_a is released and updates the escrow.
+        ctx.accounts.check_receive(amount_a, expected_receive_a)?; //An update by the maker since the taker signed fails here, before anything moves.
+        let balance_b = ctx.accounts.taker_balance_b();
+        let (fee, royalty) = ctx.accounts.deposit(amount_b, ctx.remaining_accounts)?; //Checks if the taker has deposited their required tokens (of type mint_b). If this fails, the transaction does not proceed.
+        ctx.accounts.check_paid(balance_b, max_pay_b)?;
+        ctx.accounts.withdraw(amount_a)?; //Withdraws the tokens from the vault to the taker's account (taker_ata_a). This step is contingent on the successful deposit by the taker.
+        ctx.accounts.start_vesting(ctx.bumps.vesting)?; //Vested offers lock the tokens until claim_vested releases them.
+        ctx.accounts.emit_taken(amount_a, amount_b, fee, royalty)?; //Lets indexers follow the fill without parsing token balances.
+        ctx.accounts.record_fill(amount_a, amount_b, ctx.bumps.maker_stats, ctx.bumps.taker_stats)?; //Lets UIs show both parties' history without an indexer.
+        MarketPage::unlist(ctx.accounts.market_page.as_mut(), &ctx.accounts.escrow)?; //A filled offer is no longer listed for its mint pair.
         ctx.accounts.close_vault()?;//Closes the vault account, transferring any remaining lamports to the taker. This is the final step in the escrow process.
+        ctx.accounts.close_escrow()
+    }
+
+    //Fills part of the offer, releasing a pro-rata amount of mint_a for amount_b. The escrow stays open until it is fully filled.
+    //The same limits as take apply: at least min_receive_a of mint_a for at most max_pay_b of mint_b, transfer fees included.
+    pub fn take_partial<'info>(ctx: Context<'_, '_, '_, 'info, Take<'info>>, amount_b: u64, min_receive_a: u64, max_pay_b: u64) -> Result<()> {
+        ctx.accounts.reprice()?;
+        let amount_a = ctx.accounts.fill(amount_b)?;
+        ctx.accounts.check_receive(amount_a, min_receive_a)?; //An update or a new price since the taker signed can worsen the rate of a partial fill too.
+        let balance_b = ctx.accounts.taker_balance_b();
+        let (fee, royalty) = ctx.accounts.deposit(amount_b, ctx.remaining_accounts)?;
+        ctx.accounts.check_paid(balance_b, max_pay_b)?;
+        ctx.accounts.withdraw(amount_a)?;
+        ctx.accounts.start_vesting(ctx.bumps.vesting)?;
+        ctx.accounts.emit_taken(amount_a, amount_b, fee, royalty)?;
+        ctx.accounts.record_fill(amount_a, amount_b, ctx.bumps.maker_stats, ctx.bumps.taker_stats)?;
+        if ctx.accounts.escrow.remaining_b == 0 { //The last fill closes the vault and the escrow just like take.
+            MarketPage::unlist(ctx.accounts.market_page.as_mut(), &ctx.accounts.escrow)?;
+            ctx.accounts.close_vault()?;
+            ctx.accounts.close_escrow()?;
+        }
         Ok(())
     }
 
+    //Lets the maker change the amount of mint_b it asks for and top up or withdraw part of the vault without closing the offer. Fixed price offers only.
+    pub fn update(ctx: Context<Update>, receive: u64, deposit: u64) -> Result<()> {
+        ctx.accounts.update(receive, deposit)
+    }
+
     //andles the refund process in case the escrow conditions are not met.
     pub fn refund(ctx: Context<Refund>) -> Result<()> {
-        ctx.accounts.refund()?; //Initiates the refund of tokens from the vault back to the maker. This occurs if the taker does not fulfill their part of the transaction.
-        ctx.accounts.close_vault()?;//Closes the vault account after the refund, similar to the take function.
-        Ok(())
+        ctx.accounts.record_refund(ctx.bumps.maker_stats)?;
+        ctx.accounts.refund()?; //Returns the tokens from the vault back to the maker and closes the vault. This occurs if the taker does not fulfill their part of the transaction.
+        MarketPage::unlist(ctx.accounts.market_page.as_mut(), &ctx.accounts.escrow) //Refunded offers are unlisted from their mint pair's index.
+    }
+
+    //Lets anyone (e.g. a crank) return an expired offer to the maker. The tokens and all rent go back to the maker.
+    pub fn refund_expired(ctx: Context<RefundExpired>) -> Result<()> {
+        ctx.accounts.record_refund(ctx.bumps.maker_stats)?;
+        ctx.accounts.refund()?;
+        MarketPage::unlist(ctx.accounts.market_page.as_mut(), &ctx.accounts.escrow)
+    }
+
+    //Refunds several escrows of the maker in one transaction. Each escrow comes with its accounts as remaining accounts.
+    pub fn refund_many<'info>(ctx: Context<'_, '_, 'info, 'info, RefundMany<'info>>) -> Result<()> {
+        ctx.accounts.refund_many(ctx.remaining_accounts, ctx.bumps.maker_stats)
+    }
+
+    //Creates a bundle offering up to MAX_BUNDLE_LEGS mints, one deposit per offered leg passed in the remaining accounts, for up to MAX_BUNDLE_LEGS requested mints.
+    pub fn make_bundle<'info>(ctx: Context<'_, '_, '_, 'info, MakeBundle<'info>>, seed: u64, deposits: Vec<u64>, requested: Vec<BundleLeg>, expires_at: i64, allowed_taker: Option<Pubkey>) -> Result<()> {
+        let offered = ctx.accounts.deposit(&deposits, ctx.remaining_accounts)?; //Creates the vaults and fills them, keeping what actually arrived.
+        ctx.accounts.save_bundle(seed, offered, requested, expires_at, allowed_taker, ctx.bumps.bundle)
+    }
+
+    //Fills a whole bundle. Every requested leg is paid and every vault is emptied in this one instruction, so either all legs settle or none do.
+    pub fn take_bundle<'info>(ctx: Context<'_, '_, '_, 'info, TakeBundle<'info>>) -> Result<()> {
+        let (offered, requested) = ctx.accounts.split_legs(ctx.remaining_accounts)?;
+        let fees = ctx.accounts.deposit(requested)?; //The maker is paid first, like in take.
+        ctx.accounts.withdraw(offered)?;
+        ctx.accounts.emit_taken(fees) //The bundle account itself is closed to the maker by Anchor.
+    }
+
+    //Returns every offered leg of a bundle to the maker.
+    pub fn refund_bundle<'info>(ctx: Context<'_, '_, '_, 'info, RefundBundle<'info>>) -> Result<()> {
+        ctx.accounts.refund(ctx.remaining_accounts)
+    }
+
+    //Lets anyone return an expired bundle to the maker, like refund_expired does for single offers.
+    pub fn refund_bundle_expired<'info>(ctx: Context<'_, '_, '_, 'info, RefundBundleExpired<'info>>) -> Result<()> {
+        ctx.accounts.refund(ctx.remaining_accounts)
+    }
+
+    //Proposes to fill everything left in an escrow for a different amount of mint_b, locking that amount in a vault of the counter-offer.
+    pub fn counter_offer(ctx: Context<ProposeCounter>, receive: u64) -> Result<()> {
+        let deposited = ctx.accounts.deposit(receive)?; //Only what reached the counter vault is offered to the maker.
+        ctx.accounts.save_counter(deposited, ctx.bumps.counter_offer)
+    }
+
+    //Lets the maker accept a counter-offer, swapping the whole vault for the counter vault in one instruction.
+    pub fn accept_counter(ctx: Context<AcceptCounter>) -> Result<()> {
+        let (amount_b, fee) = ctx.accounts.settle_counter()?; //The maker is paid first, like in take.
+        let amount_a = ctx.accounts.release_vault()?;
+        ctx.accounts.emit_accepted(amount_a, amount_b, fee)?;
+        ctx.accounts.record_fill(amount_a, amount_b, ctx.bumps.maker_stats, ctx.bumps.proposer_stats)?;
+        MarketPage::unlist(ctx.accounts.market_page.as_mut(), &ctx.accounts.escrow) //The escrow and the counter-offer are closed by Anchor.
+    }
+
+    //Lets the proposer withdraw a counter-offer and take the deposit back, whether or not the escrow still exists.
+    pub fn cancel_counter(ctx: Context<CancelCounter>) -> Result<()> {
+        ctx.accounts.cancel()
+    }
+
+    //Releases what has unlocked of a vested fill to the taker. The claim that releases the last of it closes the vesting and its vault.
+    pub fn claim_vested(ctx: Context<ClaimVested>) -> Result<()> {
+        ctx.accounts.claim()
+    }
+
+    //Lets the maker or the recipient of an arbitrated escrow flag it as disputed before the arbiter settles it.
+    pub fn raise_dispute(ctx: Context<RaiseDispute>) -> Result<()> {
+        ctx.accounts.raise_dispute()
+    }
+
+    //Lets the arbiter send the deposit of an arbitrated escrow to its recipient. The escrow is closed by Anchor.
+    pub fn release(ctx: Context<Arbitrate>) -> Result<()> {
+        ctx.accounts.settle(Settlement::Released)?;
+        ctx.accounts.close_vault()
+    }
+
+    //Lets the arbiter send the deposit of an arbitrated escrow back to the maker. The escrow is closed by Anchor.
+    pub fn arbiter_refund(ctx: Context<Arbitrate>) -> Result<()> {
+        ctx.accounts.settle(Settlement::Refunded)?;
+        ctx.accounts.close_vault()
+    }
+
+    //Lets the maker take back an arbitrated escrow the arbiter did not settle by its deadline. Disputed escrows are left to the arbiter.
+    pub fn reclaim(ctx: Context<Reclaim>) -> Result<()> {
+        ctx.accounts.reclaim()
+    }
+
+    //Creates the program wide config. Only the upgrade authority of the program can, and it becomes the admin.
+    pub fn initialize_config(ctx: Context<InitializeConfig>, fee_bps: u16, treasury: Pubkey) -> Result<()> {
+        ctx.accounts.initialize_config(fee_bps, treasury, ctx.bumps.config)
+    }
+
+    //Lets the admin change the fee, the treasury or hand the admin role to another key.
+    pub fn update_config(ctx: Context<UpdateConfig>, admin: Pubkey, fee_bps: u16, treasury: Pubkey) -> Result<()> {
+        ctx.accounts.update_config(admin, fee_bps, treasury)
+    }
+
+    //Rewrites an escrow created before the maker was stored into the current layout, so indexers can read the counterparty directly.
+    //The legacy layout did not store the deposit, so the maker states it.
+    pub fn migrate(ctx: Context<Migrate>, seed: u64, deposit: u64) -> Result<()> {
+        ctx.accounts.migrate(seed, deposit, ctx.bumps.escrow)
     }
 
 }
\ No newline at end of file
@@ -1,17 +1,421 @@
 use anchor_lang::prelude::*;  // Importing the necessary items from the anchor_lang crate
 
-use crate::constants::{ANCHOR_DISCRIMINATOR_BYTES, PUBKEY_BYTES, U64_BYTES, U8_BYTES};  // Importing constants from the crate module
+use crate::{constants::{ANCHOR_DISCRIMINATOR_BYTES, I16_BYTES, I64_BYTES, MARKET_PAGE_CAPACITY, MAX_BUNDLE_LEGS, MAX_STATS_MINTS, PUBKEY_BYTES, U16_BYTES, U32_BYTES, U64_BYTES, U8_BYTES}, error::EscrowError};  // Importing constants from the crate module
 
 #[account]  // Attribute to define the account structure
 pub struct Escrow {  // Defining a public structure named Escrow
-   // pub maker: Pubkey,  // Public key of the maker // 3 bytes
-    pub mint_a: Pubkey,  // Public key of the maker's token // 3 bytes
-    pub mint_b: Pubkey,  // Public key of the taker's token // 3 bytes
+    pub maker: Pubkey,  // Public key of the maker // 32 bytes
+    pub mint_a: Pubkey,  // Public key of the maker's token // 32 bytes
+    pub mint_b: Pubkey,  // Public key of the taker's token // 32 bytes
     pub offer_amount: u64,  // Amount of the offer // 8 bytes
     pub seed: u64,  // Seed value // 8 bytes
     pub escrow_bump: u8,  // Escrow bump  // 1 bytes
+    pub remaining_a: u64,  // Amount of mint_a still in the vault for takers // 8 bytes
+    pub remaining_b: u64,  // Amount of mint_b the maker is still owed // 8 bytes
+    pub expires_at: i64,  // Unix timestamp after which the offer can no longer be taken, or the maker can reclaim an undisputed arbitrated escrow // 8 bytes
+    pub allowed_taker: Option<Pubkey>,  // Only taker allowed to fill a private offer, None for public offers // 1 + 32 bytes
+    pub market_page: Option<u32>,  // Page of the mint pair's market index listing this escrow, None for migrated escrows // 1 + 4 bytes
+    pub nft: bool,  // mint_a is an NFT, fills pay its creators their royalties // 1 bytes
+    pub oracle: Option<OraclePeg>,  // Prices mint_b from a price feed on every fill instead of remaining_b, None for fixed price offers // 1 + OraclePeg::LEN bytes
+    pub auction: Option<DutchAuction>,  // Prices mint_b from a price falling over time on every fill, None for fixed price offers // 1 + DutchAuction::LEN bytes
+    pub vesting: Option<VestingTerms>,  // Locks the taker's mint_a in a vesting vault on take, None to release it straight to the taker // 1 + VestingTerms::LEN bytes
+    pub arbitration: Option<ArbiterTerms>,  // Set by make_arbitrated for a payment the arbiter releases or refunds instead of an offer takers fill, None for swaps // 1 + ArbiterTerms::LEN bytes
+    pub status: EscrowStatus,  // Where an arbitrated escrow is in its settlement, swaps stay Open // 1 bytes
 }  // End of the Escrow structure definition
 
 impl Escrow {
-    pub const LEN: usize = ANCHOR_DISCRIMINATOR_BYTES + 3 * PUBKEY_BYTES + 2 * U64_BYTES + 1 * U8_BYTES;
-}
\ No newline at end of file
+    pub const LEN: usize = ANCHOR_DISCRIMINATOR_BYTES + 3 * PUBKEY_BYTES + 4 * U64_BYTES + I64_BYTES + U8_BYTES + (U8_BYTES + PUBKEY_BYTES) + (U8_BYTES + U32_BYTES) + U8_BYTES + (U8_BYTES + OraclePeg::LEN) + (U8_BYTES + DutchAuction::LEN) + (U8_BYTES + VestingTerms::LEN) + (U8_BYTES + ArbiterTerms::LEN) + U8_BYTES;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]  // Terms of an offer pegged to a price feed quoting mint_a in mint_b
+pub struct OraclePeg {
+    pub price_feed: Pubkey,  // Pyth price account the fill price is read from // 32 bytes
+    pub spread_bps: i16,  // Premium on the feed price the maker asks for, negative for a discount // 2 bytes
+    pub max_staleness: u32,  // Oldest price accepted, in seconds before the fill // 4 bytes
+    pub max_confidence_bps: u16,  // Widest confidence interval accepted, relative to the price // 2 bytes
+}
+
+impl OraclePeg {
+    pub const LEN: usize = PUBKEY_BYTES + I16_BYTES + U32_BYTES + U16_BYTES;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]  // Terms of a new offer passed to make
+pub struct MakeArgs {
+    pub seed: u64,  // Seed of the escrow address, at least the maker's next seed
+    pub deposit: u64,  // Amount of mint_a moved to the vault
+    pub receive: u64,  // Amount of mint_b asked for, only the starting ask of pegged offers and auctions
+    pub expires_at: i64,  // Unix timestamp after which the offer can only be refunded
+    pub allowed_taker: Option<Pubkey>,  // Only taker allowed to fill the offer, None for public offers
+    pub page: u32,  // Page of the mint pair's market index the offer is listed on
+    pub collection: Option<Pubkey>,  // Verified collection the NFT must belong to, only for NFT offers
+    pub kind: OfferKind,  // How takes are priced
+    pub vesting: Option<VestingTerms>,  // Locks what takers buy until claim_vested releases it, None to release it on take
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]  // How takes of an offer are priced. An offer has a single way to set its price
+pub enum OfferKind {
+    Fixed,  // remaining_b, set by make and changed by update
+    Pegged(OraclePeg),  // The feed price with the maker's spread on every fill
+    Auction(AuctionTerms),  // A price falling over time for the whole deposit
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]  // Auction terms passed to make, stored as a DutchAuction once the deposit is known
+pub struct AuctionTerms {
+    pub start_price: u64,
+    pub end_price: u64,
+    pub start_at: i64,
+    pub end_at: i64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]  // Terms of an offer whose price falls linearly from start_price to end_price
+pub struct DutchAuction {
+    pub amount_a: u64,  // Amount of mint_a the prices are for, the deposit that reached the vault // 8 bytes
+    pub start_price: u64,  // Amount of mint_b asked for amount_a until start_at // 8 bytes
+    pub end_price: u64,  // Amount of mint_b asked for amount_a from end_at on // 8 bytes
+    pub start_at: i64,  // Unix timestamp the price starts falling at // 8 bytes
+    pub end_at: i64,  // Unix timestamp the price reaches end_price at // 8 bytes
+}
+
+impl DutchAuction {
+    pub const LEN: usize = 3 * U64_BYTES + 2 * I64_BYTES;
+
+    pub fn check(&self) -> Result<()> {
+        require!(
+            self.end_price > 0 && self.start_price >= self.end_price && self.start_at < self.end_at,
+            EscrowError::InvalidAuctionTerms
+        );
+        Ok(())
+    }
+
+    //Price of amount_a at `now`, falling linearly between start_at and end_at and rounded up in the maker's favor.
+    pub fn price_at(&self, now: i64) -> Option<u64> {
+        if now <= self.start_at {
+            return Some(self.start_price);
+        }
+        if now >= self.end_at {
+            return Some(self.end_price);
+        }
+        let elapsed = u128::try_from(now.checked_sub(self.start_at)?).ok()?; //Terms as wide as i64::MIN to i64::MAX overflow here instead of panicking.
+        let duration = u128::try_from(self.end_at.checked_sub(self.start_at)?).ok()?;
+        let drop = (self.start_price.checked_sub(self.end_price)? as u128).checked_mul(elapsed)? / duration; //Rounding the drop down rounds the price up.
+        u64::try_from((self.start_price as u128).checked_sub(drop)?).ok()
+    }
+
+    //Amount of mint_b owed for `remaining_a` at `now`, the pro-rata share of the current price rounded up.
+    pub fn ask(&self, remaining_a: u64, now: i64) -> Option<u64> {
+        let owed = (self.price_at(now)? as u128).checked_mul(remaining_a as u128)?;
+        let amount_a = self.amount_a as u128;
+        let ask = owed.checked_add(amount_a.checked_sub(1)?)?.checked_div(amount_a)?; //Rounded up by hand, u128::div_ceil is newer than the compiler of the Solana platform tools.
+        u64::try_from(ask).ok()
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]  // Vesting schedule of a vested offer, relative to the time it is taken
+pub struct VestingTerms {
+    pub cliff: i64,  // Seconds after the fill before anything unlocks // 8 bytes
+    pub duration: i64,  // Seconds after the fill until everything is unlocked, linearly from the fill on // 8 bytes
+}
+
+impl VestingTerms {
+    pub const LEN: usize = 2 * I64_BYTES;
+
+    pub fn check(&self) -> Result<()> {
+        require!(
+            self.duration > 0 && self.cliff >= 0 && self.cliff <= self.duration,
+            EscrowError::InvalidVestingTerms
+        );
+        Ok(())
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]  // Parties of an arbitrated escrow besides the maker
+pub struct ArbiterTerms {
+    pub arbiter: Pubkey,  // Only key that can release the vault to the recipient or refund it to the maker // 32 bytes
+    pub recipient: Pubkey,  // Receives the vault once the arbiter releases it // 32 bytes
+}
+
+impl ArbiterTerms {
+    pub const LEN: usize = 2 * PUBKEY_BYTES;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]  // Dispute state of an escrow. Only arbitrated escrows leave Open. Settling closes the escrow, so there is no settled state
+pub enum EscrowStatus {
+    Open,  // Funded, the arbiter can settle it either way and the maker can reclaim it after expires_at
+    Disputed,  // The maker or the recipient asked the arbiter to step in, only the arbiter can settle it, even after expires_at
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]  // Which way the arbiter settled an escrow
+pub enum Settlement {
+    Released,  // Sent to the recipient by release
+    Refunded,  // Sent back to the maker by arbiter_refund
+}
+
+//Aggregate price read from a Pyth price account. Like TokenMetadata, the layout is parsed by hand instead of pulling in the Pyth crate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OraclePrice {
+    pub price: i64,  // Price of one mint_a in mint_b, scaled by 10^exponent
+    pub confidence: u64,  // Confidence interval around the price, same scale
+    pub exponent: i32,
+    pub publish_time: i64,  // Unix timestamp the price was published at
+}
+
+#[account]  // One page of the index of open escrows for a mint pair, derived from b"market", mint_a, mint_b and the page number
+pub struct MarketPage {
+    pub mint_a: Pubkey,  // Mint offered by every escrow on this page // 32 bytes
+    pub mint_b: Pubkey,  // Mint asked for by every escrow on this page // 32 bytes
+    pub page: u32,  // Page number, clients read pages from 0 until one does not exist // 4 bytes
+    pub page_bump: u8,  // Market page bump // 1 bytes
+    pub escrows: Vec<Pubkey>,  // Open escrows, at most MARKET_PAGE_CAPACITY // 4 + 32 * MARKET_PAGE_CAPACITY bytes
+}
+
+impl MarketPage {
+    pub const LEN: usize = ANCHOR_DISCRIMINATOR_BYTES + 2 * PUBKEY_BYTES + U32_BYTES + U8_BYTES + (U32_BYTES + MARKET_PAGE_CAPACITY * PUBKEY_BYTES);
+
+    //Lists a new escrow. Makers pick another page once this one is full.
+    pub fn insert(&mut self, escrow: Pubkey) -> Result<()> {
+        require!(self.escrows.len() < MARKET_PAGE_CAPACITY, EscrowError::MarketPageFull);
+        self.escrows.push(escrow);
+        Ok(())
+    }
+
+    //Unlists an escrow that was filled or refunded. Order is not kept, the last entry takes the removed one's place.
+    pub fn remove(&mut self, escrow: &Pubkey) -> Result<()> {
+        let index = self
+            .escrows
+            .iter()
+            .position(|listed| listed == escrow)
+            .ok_or(EscrowError::MarketPageMismatch)?;
+        self.escrows.swap_remove(index);
+        Ok(())
+    }
+
+    //Unlists `escrow` from the page it was listed on. Migrated and arbitrated escrows were never listed and have nothing to remove.
+    pub fn unlist(market_page: Option<&mut Account<MarketPage>>, escrow: &Account<Escrow>) -> Result<()> {
+        let Some(page) = escrow.market_page else {
+            return Ok(());
+        };
+        let market_page = market_page.ok_or(EscrowError::MarketPageMismatch)?;
+        require!(market_page.page == page, EscrowError::MarketPageMismatch);
+        market_page.remove(&escrow.key())
+    }
+}
+
+#[account]  // A proposal to fill an escrow for a different amount of mint_b, derived from b"counter", the escrow and the proposer
+pub struct CounterOffer {
+    pub escrow: Pubkey,  // Escrow the counter-offer is for // 32 bytes
+    pub proposer: Pubkey,  // Taker who proposed it and funded the counter vault // 32 bytes
+    pub mint_b: Pubkey,  // Mint held in the counter vault, the escrow's mint_b // 32 bytes
+    pub receive: u64,  // Amount of mint_b in the counter vault, offered for everything left in the escrow // 8 bytes
+    pub remaining_a: u64,  // Amount of mint_a left in the escrow when the counter-offer was made // 8 bytes
+    pub counter_bump: u8,  // Counter-offer bump // 1 bytes
+}
+
+impl CounterOffer {
+    pub const LEN: usize = ANCHOR_DISCRIMINATOR_BYTES + 3 * PUBKEY_BYTES + 2 * U64_BYTES + U8_BYTES;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]  // One mint of a bundle and the amount of it on offer or asked for
+pub struct BundleLeg {
+    pub mint: Pubkey,  // Mint of this leg // 32 bytes
+    pub amount: u64,  // Amount held in the leg's vault for offered legs, amount owed to the maker for requested legs // 8 bytes
+}
+
+impl BundleLeg {
+    pub const LEN: usize = PUBKEY_BYTES + U64_BYTES;
+}
+
+#[account]  // Multi-asset escrow, derived from b"bundle", the maker and a seed. Every offered mint sits in its own vault owned by this account
+pub struct Bundle {
+    pub maker: Pubkey,  // Public key of the maker // 32 bytes
+    pub seed: u64,  // Seed value // 8 bytes
+    pub bundle_bump: u8,  // Bundle bump // 1 bytes
+    pub expires_at: i64,  // Unix timestamp after which the bundle can no longer be taken // 8 bytes
+    pub allowed_taker: Option<Pubkey>,  // Only taker allowed to fill a private bundle, None for public bundles // 1 + 32 bytes
+    pub offered: Vec<BundleLeg>,  // Mints deposited by the maker, in the order their remaining accounts are passed // 4 + 40 * MAX_BUNDLE_LEGS bytes
+    pub requested: Vec<BundleLeg>,  // Mints the taker pays the maker, in the order their remaining accounts are passed // 4 + 40 * MAX_BUNDLE_LEGS bytes
+}
+
+impl Bundle {
+    pub const LEN: usize = ANCHOR_DISCRIMINATOR_BYTES + PUBKEY_BYTES + U64_BYTES + U8_BYTES + I64_BYTES + (U8_BYTES + PUBKEY_BYTES) + 2 * (U32_BYTES + MAX_BUNDLE_LEGS * BundleLeg::LEN);
+
+    //Both sides need 1 to MAX_BUNDLE_LEGS legs with non-zero amounts, and a mint can only appear once across the whole bundle.
+    pub fn check_legs(offered: &[BundleLeg], requested: &[BundleLeg]) -> Result<()> {
+        for legs in [offered, requested] {
+            require!(!legs.is_empty() && legs.len() <= MAX_BUNDLE_LEGS, EscrowError::InvalidBundleLegs);
+            require!(legs.iter().all(|leg| leg.amount > 0), EscrowError::InvalidBundleLegs);
+        }
+        let mints: Vec<&Pubkey> = offered.iter().chain(requested).map(|leg| &leg.mint).collect();
+        for (index, mint) in mints.iter().enumerate() {
+            require!(!mints[index + 1..].contains(mint), EscrowError::InvalidBundleLegs);
+        }
+        Ok(())
+    }
+}
+
+#[account]  // Seed allocator of a maker, derived from b"maker" and the maker. Escrows made through it use seeds 0, 1, 2 and so on
+pub struct MakerState {
+    pub maker: Pubkey,  // Maker the seeds are allocated for // 32 bytes
+    pub next_seed: u64,  // Lowest seed make accepts next, every seed below it was used or skipped // 8 bytes
+    pub maker_state_bump: u8,  // Maker state bump // 1 bytes
+}
+
+impl MakerState {
+    pub const LEN: usize = ANCHOR_DISCRIMINATOR_BYTES + PUBKEY_BYTES + U64_BYTES + U8_BYTES;
+
+    //Moves the next seed past the one just used. Seeds skipped by passing a higher one are never handed out again.
+    pub fn allocate(&mut self, maker: Pubkey, seed: u64, bump: u8) -> Result<()> {
+        self.maker = maker;
+        self.next_seed = seed.checked_add(1).ok_or(EscrowError::ArithmeticOverflow)?;
+        self.maker_state_bump = bump;
+        Ok(())
+    }
+}
+
+#[account]  // mint_a a taker bought from a vested offer, derived from b"vesting", the escrow and the taker. Its vault is the account's associated token account
+pub struct Vesting {
+    pub beneficiary: Pubkey,  // Taker the tokens unlock for // 32 bytes
+    pub mint: Pubkey,  // mint_a of the escrow // 32 bytes
+    pub escrow: Pubkey,  // Escrow the tokens were bought from, closed by the take // 32 bytes
+    pub total: u64,  // Amount that reached the vesting vault // 8 bytes
+    pub claimed: u64,  // Amount released to the beneficiary so far // 8 bytes
+    pub start_at: i64,  // Unix timestamp of the fill, unlocking starts from it // 8 bytes
+    pub cliff_at: i64,  // Unix timestamp before which nothing can be claimed // 8 bytes
+    pub end_at: i64,  // Unix timestamp from which everything can be claimed // 8 bytes
+    pub vesting_bump: u8,  // Vesting bump // 1 bytes
+}
+
+impl Vesting {
+    pub const LEN: usize = ANCHOR_DISCRIMINATOR_BYTES + 3 * PUBKEY_BYTES + 2 * U64_BYTES + 3 * I64_BYTES + U8_BYTES;
+
+    //Amount unlocked at `now`: nothing before the cliff, then the share of total for the time since start_at, rounded down.
+    pub fn unlocked(&self, now: i64) -> Option<u64> {
+        if now < self.cliff_at {
+            return Some(0);
+        }
+        if now >= self.end_at {
+            return Some(self.total);
+        }
+        let elapsed = (now - self.start_at) as u128;
+        let duration = (self.end_at - self.start_at) as u128;
+        u64::try_from((self.total as u128).checked_mul(elapsed)? / duration).ok()
+    }
+}
+
+#[account]  // Trading history of a wallet, derived from b"stats" and the wallet. Outlives the escrows it counts, which are closed once settled. Only swap offers are counted: bundles and arbitrated escrows are not trades at a price and are left out
+pub struct TraderStats {
+    pub wallet: Pubkey,  // Wallet the history belongs to // 32 bytes
+    pub offers_created: u64,  // Offers made by the wallet with make // 8 bytes
+    pub offers_filled: u64,  // Escrows of the wallet filled completely // 8 bytes
+    pub offers_refunded: u64,  // Escrows of the wallet refunded before they were filled // 8 bytes
+    pub fills_taken: u64,  // Fills the wallet made as a taker, partial fills included // 8 bytes
+    pub volume: Vec<MintVolume>,  // Amount traded per mint as maker or taker, for the first MAX_STATS_MINTS mints traded // 4 + 40 * MAX_STATS_MINTS bytes
+    pub stats_bump: u8,  // Trader stats bump // 1 bytes
+}
+
+impl TraderStats {
+    pub const LEN: usize = ANCHOR_DISCRIMINATOR_BYTES + PUBKEY_BYTES + 4 * U64_BYTES + (U32_BYTES + MAX_STATS_MINTS * MintVolume::LEN) + U8_BYTES;
+
+    //Sets the owner of stats created by init_if_needed. Existing stats are left as they are.
+    pub fn open(&mut self, wallet: Pubkey, bump: u8) {
+        if self.wallet == Pubkey::default() {
+            self.wallet = wallet;
+            self.stats_bump = bump;
+        }
+    }
+
+    //Counts offers taken back by their maker.
+    pub fn add_refunded(&mut self, count: u64) -> Result<()> {
+        self.offers_refunded = self.offers_refunded.checked_add(count).ok_or(EscrowError::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    //Adds a traded amount to the mint's volume. Once MAX_STATS_MINTS mints are tracked, new mints are not recorded.
+    pub fn add_volume(&mut self, mint: Pubkey, amount: u64) {
+        if let Some(volume) = self.volume.iter_mut().find(|volume| volume.mint == mint) {
+            volume.amount = volume.amount.saturating_add(amount);
+        } else if self.volume.len() < MAX_STATS_MINTS {
+            self.volume.push(MintVolume { mint, amount });
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]  // Volume a trader moved in one mint
+pub struct MintVolume {
+    pub mint: Pubkey,  // Mint traded // 32 bytes
+    pub amount: u64,  // Total amount of it sent or received in fills, saturating at u64::MAX // 8 bytes
+}
+
+impl MintVolume {
+    pub const LEN: usize = PUBKEY_BYTES + U64_BYTES;
+}
+
+#[account]  // Program wide settings, stored in a single PDA derived from b"config"
+pub struct Config {
+    pub admin: Pubkey,  // Only key allowed to change the config // 32 bytes
+    pub fee_bps: u16,  // Share of the mint_b leg of every fill sent to the treasury, in basis points // 2 bytes
+    pub treasury: Pubkey,  // Wallet that receives the protocol fee // 32 bytes
+    pub config_bump: u8,  // Config bump // 1 bytes
+}
+
+impl Config {
+    pub const LEN: usize = ANCHOR_DISCRIMINATOR_BYTES + 2 * PUBKEY_BYTES + U16_BYTES + U8_BYTES;
+
+    //Fee charged on fills, read from the config PDA passed to take, take_bundle and accept_counter. The config does not exist until the admin
+    //runs initialize_config, and fills stay free until then so a program upgraded to charge fees keeps settling offers. Once it exists,
+    //`treasury` must be the one it names.
+    pub fn fee_bps(config: &AccountInfo, treasury: &Pubkey) -> Result<u16> {
+        if config.data_is_empty() {
+            return Ok(0);
+        }
+        require_keys_eq!(*config.owner, crate::ID, ErrorCode::AccountOwnedByWrongProgram);
+        let config = Config::try_deserialize(&mut &config.try_borrow_data()?[..])?; //Checks the discriminator.
+        require_keys_eq!(*treasury, config.treasury, EscrowError::TreasuryMismatch);
+        Ok(config.fee_bps)
+    }
+}
+
+//Leading fields of a Metaplex Token Metadata account, up to the collection. The program only reads these accounts, so the
+//layout is declared here instead of pulling in the Metaplex crate. Fields after the collection are never read.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct TokenMetadata {
+    pub key: u8,  // Account type, METADATA_V1_KEY for metadata accounts
+    pub update_authority: Pubkey,
+    pub mint: Pubkey,  // Mint the metadata describes
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+    pub seller_fee_basis_points: u16,  // Royalty owed to the creators on every sale, in basis points
+    pub creators: Option<Vec<MetadataCreator>>,
+    pub primary_sale_happened: bool,
+    pub is_mutable: bool,
+    pub edition_nonce: Option<u8>,
+    pub token_standard: Option<u8>,
+    pub collection: Option<MetadataCollection>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MetadataCreator {
+    pub address: Pubkey,  // Wallet that receives this creator's part of the royalty
+    pub verified: bool,
+    pub share: u8,  // Percentage of the royalty, the shares of all creators add up to 100
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MetadataCollection {
+    pub verified: bool,  // Set by the collection's update authority, unverified collections can be claimed by anyone
+    pub key: Pubkey,  // Mint of the collection NFT
+}
+
+//Layout written by versions of the program that did not store the maker. These accounts share the Escrow discriminator and were
+//allocated with room for three pubkeys, so every field sits 32 bytes closer to the start of the account than in Escrow.
+//It is only used by the migrate instruction to rewrite old escrows.
+pub const LEGACY_ESCROW_LEN: usize = ANCHOR_DISCRIMINATOR_BYTES + 3 * PUBKEY_BYTES + 2 * U64_BYTES + U8_BYTES;
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct LegacyEscrow {
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub offer_amount: u64,
+    pub seed: u64,
+    pub escrow_bump: u8,
+}
@@ -0,0 +1,404 @@
+use anchor_lang::{prelude::*, solana_program::program::invoke};
+use anchor_spl::{
+    associated_token::{create_idempotent, get_associated_token_address_with_program_id, Create},
+    token::spl_token,
+    token_interface::{close_account, transfer_checked, CloseAccount, TransferChecked},
+    token_2022::spl_token_2022::{
+        self,
+        extension::{
+            transfer_fee::{
+                instruction::harvest_withheld_tokens_to_mint, TransferFeeAmount, TransferFeeConfig,
+            },
+            BaseStateWithExtensions, StateWithExtensions,
+        },
+        state::{Account as SplAccount, Mint as SplMint},
+    },
+};
+
+use crate::{
+    constants::{
+        BPS_DENOMINATOR, BUNDLE_OFFERED_LEG_ACCOUNTS, METADATA_V1_KEY, PYTH_AGGREGATE_OFFSET, PYTH_EXPONENT_OFFSET, PYTH_MAGIC,
+        PYTH_PRICE_ACCOUNT_MIN_LEN, PYTH_PRICE_ACCOUNT_TYPE, PYTH_PUBLISH_TIME_OFFSET,
+        PYTH_STATUS_TRADING, PYTH_VERSION,
+    },
+    error::EscrowError,
+    state::{Bundle, Escrow, OraclePrice, TokenMetadata},
+};
+
+//Wrapped SOL mints of both token programs. Legs in these mints can be paid and received as native SOL.
+pub fn is_native_mint(mint: &Pubkey) -> bool {
+    *mint == spl_token::native_mint::ID || *mint == spl_token_2022::native_mint::ID
+}
+
+//Returns how much has to be sent so that `amount` arrives after the mint's transfer fee.
+//Legacy SPL mints and Token-2022 mints without the transfer fee extension return the amount unchanged.
+pub fn amount_with_transfer_fee(mint: &AccountInfo, amount: u64) -> Result<u64> {
+    if *mint.owner != spl_token_2022::ID {
+        return Ok(amount);
+    }
+
+    let data = mint.try_borrow_data()?;
+    let mint = StateWithExtensions::<SplMint>::unpack(&data)?;
+    match mint.get_extension::<TransferFeeConfig>() {
+        Ok(config) => config
+            .get_epoch_fee(Clock::get()?.epoch)
+            .calculate_pre_fee_amount(amount)
+            .ok_or(error!(EscrowError::TransferFeeCalculationFailed)),
+        Err(_) => Ok(amount),
+    }
+}
+
+//Returns what arrives when `amount` is sent, after the mint's transfer fee. The counterpart of amount_with_transfer_fee.
+pub fn amount_after_transfer_fee(mint: &AccountInfo, amount: u64) -> Result<u64> {
+    if *mint.owner != spl_token_2022::ID {
+        return Ok(amount);
+    }
+
+    let data = mint.try_borrow_data()?;
+    let mint = StateWithExtensions::<SplMint>::unpack(&data)?;
+    match mint.get_extension::<TransferFeeConfig>() {
+        Ok(config) => config
+            .get_epoch_fee(Clock::get()?.epoch)
+            .calculate_fee(amount)
+            .and_then(|fee| amount.checked_sub(fee))
+            .ok_or(error!(EscrowError::TransferFeeCalculationFailed)),
+        Err(_) => Ok(amount),
+    }
+}
+
+//Token-2022 refuses to close an account that still holds withheld transfer fees, so they are moved to the mint first.
+//Every vault is passed through here right before it is closed. Harvesting is permissionless and a no-op for accounts without withheld fees.
+pub fn harvest_withheld_fees<'info>(
+    token_program: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+) -> Result<()> {
+    if *account.owner != spl_token_2022::ID {
+        return Ok(());
+    }
+
+    let withheld = {
+        let data = account.try_borrow_data()?;
+        let account = StateWithExtensions::<SplAccount>::unpack(&data)?;
+        account
+            .get_extension::<TransferFeeAmount>()
+            .map(|fees| u64::from(fees.withheld_amount))
+            .unwrap_or(0)
+    };
+    if withheld == 0 {
+        return Ok(());
+    }
+
+    let ix = harvest_withheld_tokens_to_mint(token_program.key, mint.key, &[account.key])?;
+    invoke(&ix, &[mint.clone(), account.clone(), token_program.clone()])?;
+    Ok(())
+}
+
+//Harvests a vault's withheld fees and closes it, sending its rent to `destination`. Every vault the program owns is closed through here.
+//`authority` is the PDA owning the vault (escrow, counter-offer, vesting or bundle) and `signer_seeds` its seeds.
+pub fn close_vault<'info>(
+    authority: &AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+    mint: &AccountInfo<'info>,
+    vault: &AccountInfo<'info>,
+    destination: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+) -> Result<()> {
+    harvest_withheld_fees(token_program, mint, vault)?;
+
+    let close_accounts = CloseAccount {
+        account: vault.clone(),
+        destination: destination.clone(),
+        authority: authority.clone(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(token_program.clone(), close_accounts, signer_seeds);
+    close_account(cpi_ctx).map_err(|_| error!(EscrowError::CloseVaultFailed))
+}
+
+//Returns everything in an escrow's vault to the maker and closes the vault, sending its rent to the maker. Shared by refund, refund_expired and refund_many.
+//Without a maker token account the vault must hold wrapped SOL, which closing the vault unwraps to the maker. Returns the amount refunded.
+pub fn refund_vault<'info>(
+    escrow: &Account<'info, Escrow>,
+    maker: &AccountInfo<'info>,
+    mint_a: &AccountInfo<'info>,
+    decimals: u8,
+    maker_ata_a: Option<AccountInfo<'info>>,
+    vault: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+) -> Result<u64> {
+    let amount = leg_token_amount(vault)?;
+    let signer_seeds: [&[&[u8]]; 1] = [&[
+        b"escrow",
+        escrow.maker.as_ref(),
+        &escrow.seed.to_le_bytes()[..],
+        &[escrow.escrow_bump],
+    ]];
+
+    match maker_ata_a {
+        Some(maker_ata_a) => {
+            let transfer_accounts = TransferChecked {
+                from: vault.clone(),
+                mint: mint_a.clone(),
+                to: maker_ata_a,
+                authority: escrow.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new_with_signer(token_program.clone(), transfer_accounts, &signer_seeds);
+            transfer_checked(cpi_ctx, amount, decimals).map_err(|_| error!(EscrowError::RefundFailed))?;
+        }
+        None => require!(is_native_mint(mint_a.key), EscrowError::NativeMintRequired),
+    }
+
+    close_vault(&escrow.to_account_info(), &signer_seeds, mint_a, vault, maker, token_program)?;
+    Ok(amount)
+}
+
+//Returns every vault of a bundle to the maker and closes it, sending its rent to the maker. Shared by refund_bundle and refund_bundle_expired.
+//`legs` are the offered legs in the order stored on the bundle, `payer` creates the maker's token accounts that are missing.
+pub fn refund_bundle_vaults<'info>(
+    bundle: &Account<'info, Bundle>,
+    maker: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    legs: &[AccountInfo<'info>],
+    system_program: &AccountInfo<'info>,
+    associated_token_program: &AccountInfo<'info>,
+) -> Result<()> {
+    require!(
+        legs.len() == bundle.offered.len() * BUNDLE_OFFERED_LEG_ACCOUNTS,
+        EscrowError::BundleAccountsMismatch
+    );
+    let signer_seeds: [&[&[u8]]; 1] = [&[
+        b"bundle",
+        bundle.maker.as_ref(),
+        &bundle.seed.to_le_bytes()[..],
+        &[bundle.bundle_bump],
+    ]];
+
+    for (leg, accounts) in bundle.offered.iter().zip(legs.chunks_exact(BUNDLE_OFFERED_LEG_ACCOUNTS)) {
+        let [mint, token_program, vault, maker_ata] = accounts else {
+            return err!(EscrowError::BundleAccountsMismatch);
+        };
+        require_keys_eq!(mint.key(), leg.mint, EscrowError::BundleAccountsMismatch);
+        let decimals = leg_mint_decimals(mint, token_program)?;
+        require_leg_ata(vault, &bundle.key(), mint, token_program)?;
+        init_leg_ata(payer, maker_ata, maker, mint, system_program, token_program, associated_token_program)?;
+
+        let transfer_accounts = TransferChecked {
+            from: vault.clone(),
+            mint: mint.clone(),
+            to: maker_ata.clone(),
+            authority: bundle.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(token_program.clone(), transfer_accounts, &signer_seeds);
+        transfer_checked(cpi_ctx, leg_token_amount(vault)?, decimals)
+            .map_err(|_| error!(EscrowError::RefundFailed))?;
+
+        close_vault(&bundle.to_account_info(), &signer_seeds, mint, vault, maker, token_program)?;
+    }
+    Ok(())
+}
+
+//Bundle legs are passed as remaining accounts, so the checks Anchor runs on a context are done by hand here.
+//Returns the decimals of `mint` after checking that `token_program` is SPL Token or Token-2022 and owns the mint.
+pub fn leg_mint_decimals(mint: &AccountInfo, token_program: &AccountInfo) -> Result<u8> {
+    require!(
+        *token_program.key == spl_token::ID || *token_program.key == spl_token_2022::ID,
+        EscrowError::BundleAccountsMismatch
+    );
+    require!(mint.owner == token_program.key, EscrowError::BundleAccountsMismatch);
+
+    let data = mint.try_borrow_data()?;
+    let mint = StateWithExtensions::<SplMint>::unpack(&data)?;
+    Ok(mint.base.decimals)
+}
+
+//Token balance of a bundle leg's token account. Works for both token programs since Token-2022 accounts start with the legacy layout.
+pub fn leg_token_amount(account: &AccountInfo) -> Result<u64> {
+    let data = account.try_borrow_data()?;
+    let account = StateWithExtensions::<SplAccount>::unpack(&data)?;
+    Ok(account.base.amount)
+}
+
+//Checks that `account` is the associated token account of `owner` for the leg's mint.
+pub fn require_leg_ata(account: &AccountInfo, owner: &Pubkey, mint: &AccountInfo, token_program: &AccountInfo) -> Result<()> {
+    require_keys_eq!(
+        *account.key,
+        get_associated_token_address_with_program_id(owner, mint.key, token_program.key),
+        EscrowError::BundleAccountsMismatch
+    );
+    Ok(())
+}
+
+//Same as require_leg_ata, and creates the account if it does not exist yet, like init_if_needed does for the single mint escrow.
+pub fn init_leg_ata<'info>(
+    payer: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+    owner: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    associated_token_program: &AccountInfo<'info>,
+) -> Result<()> {
+    require_leg_ata(account, owner.key, mint, token_program)?;
+
+    let cpi_accounts = Create {
+        payer: payer.clone(),
+        associated_token: account.clone(),
+        authority: owner.clone(),
+        mint: mint.clone(),
+        system_program: system_program.clone(),
+        token_program: token_program.clone(),
+    };
+    let cpi_ctx = CpiContext::new(associated_token_program.clone(), cpi_accounts);
+    create_idempotent(cpi_ctx)
+}
+
+//Reads the Token Metadata account of `mint`. The caller checks that the account is the metadata PDA owned by the Metaplex program.
+pub fn read_token_metadata(metadata: &AccountInfo, mint: &Pubkey) -> Result<TokenMetadata> {
+    let data = metadata.try_borrow_data()?;
+    let metadata = TokenMetadata::deserialize(&mut &data[..])
+        .map_err(|_| error!(EscrowError::InvalidMetadata))?; //Only the leading fields are read, the rest of the account is left alone.
+    require!(
+        metadata.key == METADATA_V1_KEY && metadata.mint == *mint,
+        EscrowError::InvalidMetadata
+    );
+    Ok(metadata)
+}
+
+//Reads the aggregate price of a Pyth price account. The caller checks that the account is owned by the Pyth program.
+pub fn read_oracle_price(feed: &AccountInfo) -> Result<OraclePrice> {
+    let data = feed.try_borrow_data()?;
+    require!(data.len() >= PYTH_PRICE_ACCOUNT_MIN_LEN, EscrowError::InvalidPriceFeed);
+    let u32_at = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+    let u64_at = |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+    require!(
+        u32_at(0) == PYTH_MAGIC && u32_at(4) == PYTH_VERSION && u32_at(8) == PYTH_PRICE_ACCOUNT_TYPE,
+        EscrowError::InvalidPriceFeed
+    );
+
+    let price = OraclePrice {
+        price: u64_at(PYTH_AGGREGATE_OFFSET) as i64,
+        confidence: u64_at(PYTH_AGGREGATE_OFFSET + 8),
+        exponent: u32_at(PYTH_EXPONENT_OFFSET) as i32,
+        publish_time: u64_at(PYTH_PUBLISH_TIME_OFFSET) as i64,
+    };
+    require!(
+        u32_at(PYTH_AGGREGATE_OFFSET + 16) == PYTH_STATUS_TRADING && price.price > 0,
+        EscrowError::InvalidPriceFeed
+    ); //Halted or unknown prices have no meaningful value.
+    Ok(price)
+}
+
+//Amount of mint_b owed for `amount_a` at the feed price with the maker's spread applied, rounded up in the maker's favor.
+//Returns None if the price is not positive, the spread is -100% or less, or the amount does not fit.
+pub fn oracle_quote(amount_a: u64, price: &OraclePrice, spread_bps: i16, decimals_a: u8, decimals_b: u8) -> Option<u64> {
+    let feed_price = u128::try_from(price.price).ok().filter(|feed_price| *feed_price > 0)?;
+    let spread = u128::try_from(BPS_DENOMINATOR as i32 + spread_bps as i32).ok().filter(|spread| *spread > 0)?;
+    let mut numerator = (amount_a as u128).checked_mul(feed_price)?.checked_mul(spread)?;
+    let mut denominator = BPS_DENOMINATOR as u128;
+
+    let scale = price.exponent.checked_add(decimals_b as i32 - decimals_a as i32)?; //Converts whole tokens to base units on both sides.
+    if scale >= 0 {
+        numerator = numerator.checked_mul(10u128.checked_pow(scale as u32)?)?;
+    } else {
+        denominator = denominator.checked_mul(10u128.checked_pow(scale.unsigned_abs())?)?;
+    }
+    let quote = numerator.checked_add(denominator.checked_sub(1)?)?.checked_div(denominator)?; //Rounded up by hand, like DutchAuction::ask.
+    u64::try_from(quote).ok()
+}
+
+//Protocol fee owed on a fill of `amount`, rounded down. Returns None if fee_bps is above 100%. Also used for NFT royalties, which are in basis points too.
+pub fn protocol_fee(amount: u64, fee_bps: u16) -> Option<u64> {
+    if fee_bps > BPS_DENOMINATOR {
+        return None;
+    }
+    let fee = (amount as u128)
+        .checked_mul(fee_bps as u128)?
+        .checked_div(BPS_DENOMINATOR as u128)?;
+    u64::try_from(fee).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{PYTH_ORACLE_PROGRAM_ID, TOKEN_METADATA_PROGRAM_ID};
+
+    #[test]
+    fn token_metadata_program_id_is_metaplex() {
+        assert_eq!(
+            TOKEN_METADATA_PROGRAM_ID.to_string(),
+            "metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s"
+        );
+    }
+
+    fn feed_price(price: i64, exponent: i32) -> OraclePrice {
+        OraclePrice {
+            price,
+            confidence: 0,
+            exponent,
+            publish_time: 0,
+        }
+    }
+
+    #[test]
+    fn pyth_oracle_program_id_is_pyth() {
+        assert_eq!(
+            PYTH_ORACLE_PROGRAM_ID.to_string(),
+            "FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH"
+        );
+    }
+
+    #[test]
+    fn oracle_quote_scales_by_exponent_and_decimals() {
+        assert_eq!(oracle_quote(1_000_000, &feed_price(250, -2), 0, 6, 6), Some(2_500_000));
+        assert_eq!(oracle_quote(1_000_000, &feed_price(250, -2), 0, 6, 9), Some(2_500_000_000));
+        assert_eq!(oracle_quote(1_000_000_000, &feed_price(25, 1), 0, 9, 6), Some(250_000_000));
+    }
+
+    #[test]
+    fn oracle_quote_applies_spread_and_rounds_up() {
+        assert_eq!(oracle_quote(1_000_000, &feed_price(250, -2), 100, 6, 6), Some(2_525_000));
+        assert_eq!(oracle_quote(1_000_000, &feed_price(250, -2), -100, 6, 6), Some(2_475_000));
+        assert_eq!(oracle_quote(1, &feed_price(1, -3), 0, 0, 0), Some(1));
+    }
+
+    #[test]
+    fn oracle_quote_rejects_unusable_terms() {
+        assert_eq!(oracle_quote(1, &feed_price(0, 0), 0, 0, 0), None);
+        assert_eq!(oracle_quote(1, &feed_price(-5, 0), 0, 0, 0), None);
+        assert_eq!(oracle_quote(1, &feed_price(1, 0), -10_000, 0, 0), None);
+        assert_eq!(oracle_quote(u64::MAX, &feed_price(i64::MAX, 0), 0, 0, 0), None);
+    }
+
+    #[test]
+    fn protocol_fee_is_zero_without_fee_or_amount() {
+        assert_eq!(protocol_fee(1_000_000, 0), Some(0));
+        assert_eq!(protocol_fee(0, BPS_DENOMINATOR), Some(0));
+        assert_eq!(protocol_fee(0, 0), Some(0));
+    }
+
+    #[test]
+    fn protocol_fee_takes_everything_at_100_percent() {
+        assert_eq!(protocol_fee(1, BPS_DENOMINATOR), Some(1));
+        assert_eq!(protocol_fee(u64::MAX, BPS_DENOMINATOR), Some(u64::MAX));
+    }
+
+    #[test]
+    fn protocol_fee_rounds_down() {
+        assert_eq!(protocol_fee(1, BPS_DENOMINATOR - 1), Some(0));
+        assert_eq!(protocol_fee(9_999, 1), Some(0));
+        assert_eq!(protocol_fee(10_000, 1), Some(1));
+        assert_eq!(protocol_fee(19_999, 1), Some(1));
+    }
+
+    #[test]
+    fn protocol_fee_does_not_overflow_on_large_amounts() {
+        assert_eq!(protocol_fee(u64::MAX, 1), Some(u64::MAX / 10_000));
+        assert_eq!(protocol_fee(u64::MAX, 5_000), Some(u64::MAX / 2));
+    }
+
+    #[test]
+    fn protocol_fee_rejects_more_than_100_percent() {
+        assert_eq!(protocol_fee(1, BPS_DENOMINATOR + 1), None);
+        assert_eq!(protocol_fee(0, u16::MAX), None);
+    }
+}
@@ -0,0 +1,390 @@
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::{associated_token, token::spl_token};
+use escrow2::{
+    error::EscrowError,
+    state::{ArbiterTerms, EscrowStatus},
+};
+use solana_program_test::BanksClientError;
+use solana_sdk::{
+    instruction::Instruction,
+    signature::{Keypair, Signer},
+    system_program,
+};
+
+use crate::common::*;
+
+//Seconds the arbiter of `arbitrated` has to settle before the maker can reclaim the escrow.
+const DEADLINE: i64 = 600;
+
+//Funds the escrow of `offer` with 1_000 of mint_a. mint_b and the market page of the offer are not used.
+async fn make_arbitrated(
+    env: &mut Env,
+    offer: &Offer,
+    terms: ArbiterTerms,
+    expires_at: i64,
+) -> Result<(), BanksClientError> {
+    let accounts = escrow2::accounts::MakeArbitrated {
+        maker: offer.maker_key(),
+        mint_a: offer.mint_a,
+        maker_ata_a: offer.maker_ata_a(),
+        maker_state: maker_state_address(&offer.maker_key()),
+        escrow: offer.escrow,
+        vault: offer.vault,
+        associated_token_program: associated_token::ID,
+        token_program: offer.token_program_a,
+        system_program: system_program::ID,
+    };
+    let args = escrow2::instruction::MakeArbitrated {
+        seed: offer.seed,
+        deposit: 1_000,
+        terms,
+        expires_at,
+    };
+    env.send(&[ix(accounts, args)], &[&offer.maker]).await
+}
+
+//Accounts of release and arbiter_refund signed by `arbiter`, passing the token account of whoever it is settled to and the maker's.
+fn arbitrate_accounts(
+    offer: &Offer,
+    arbiter: &Pubkey,
+    recipient: &Pubkey,
+    release: bool,
+) -> escrow2::accounts::Arbitrate {
+    escrow2::accounts::Arbitrate {
+        arbiter: *arbiter,
+        maker: offer.maker_key(),
+        recipient: *recipient,
+        mint_a: offer.mint_a,
+        recipient_ata_a: release.then(|| ata(recipient, &offer.mint_a, &offer.token_program_a)),
+        maker_ata_a: Some(offer.maker_ata_a()),
+        escrow: offer.escrow,
+        vault: offer.vault,
+        token_program: offer.token_program_a,
+        associated_token_program: associated_token::ID,
+        system_program: system_program::ID,
+    }
+}
+
+fn release(offer: &Offer, arbiter: &Pubkey, recipient: &Pubkey) -> Instruction {
+    let accounts = arbitrate_accounts(offer, arbiter, recipient, true);
+    ix(accounts, escrow2::instruction::Release {})
+}
+
+fn arbiter_refund(offer: &Offer, arbiter: &Pubkey, recipient: &Pubkey) -> Instruction {
+    let accounts = arbitrate_accounts(offer, arbiter, recipient, false);
+    ix(accounts, escrow2::instruction::ArbiterRefund {})
+}
+
+fn reclaim(offer: &Offer) -> Instruction {
+    let accounts = escrow2::accounts::Reclaim {
+        maker: offer.maker_key(),
+        mint_a: offer.mint_a,
+        maker_ata_a: Some(offer.maker_ata_a()),
+        escrow: offer.escrow,
+        vault: offer.vault,
+        system_program: system_program::ID,
+        token_program: offer.token_program_a,
+        associated_token_program: associated_token::ID,
+    };
+    ix(accounts, escrow2::instruction::Reclaim {})
+}
+
+async fn raise_dispute(
+    env: &mut Env,
+    offer: &Offer,
+    party: &Keypair,
+) -> Result<(), BanksClientError> {
+    let accounts = escrow2::accounts::RaiseDispute {
+        party: party.pubkey(),
+        escrow: offer.escrow,
+    };
+    env.send(
+        &[ix(accounts, escrow2::instruction::RaiseDispute {})],
+        &[party],
+    )
+    .await
+}
+
+//An arbitrated escrow of 1_000 of mint_a. Returns the arbiter and the recipient.
+async fn arbitrated(env: &mut Env) -> (Offer, Keypair, Keypair) {
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    let arbiter = env.create_user().await;
+    let recipient = env.create_user().await;
+    let terms = ArbiterTerms {
+        arbiter: arbiter.pubkey(),
+        recipient: recipient.pubkey(),
+    };
+    let expires_at = env.now().await + DEADLINE;
+    make_arbitrated(env, &offer, terms, expires_at)
+        .await
+        .unwrap();
+    (offer, arbiter, recipient)
+}
+
+#[tokio::test]
+async fn release_sends_the_deposit_to_the_recipient() {
+    let mut env = Env::new().await;
+    let (offer, arbiter, recipient) = arbitrated(&mut env).await;
+    let escrow = env.escrow(&offer.escrow).await;
+    assert_eq!(escrow.status, EscrowStatus::Open);
+    assert_eq!(escrow.remaining_a, 1_000);
+    assert_eq!(escrow.market_page, None); //Arbitrated escrows are not listed.
+    assert!(!env.exists(&offer.market_page()).await);
+    assert!(!env.exists(&stats_address(&offer.maker_key())).await); //Arbitrated escrows are not trades, so they are not counted.
+    assert_eq!(env.token_balance(&offer.vault).await, 1_000);
+
+    let settle = release(&offer, &arbiter.pubkey(), &recipient.pubkey());
+    env.send(&[settle], &[&arbiter]).await.unwrap();
+
+    let recipient_ata_a = ata(&recipient.pubkey(), &offer.mint_a, &spl_token::ID);
+    assert_eq!(env.token_balance(&recipient_ata_a).await, 1_000);
+    assert_eq!(env.token_balance(&offer.maker_ata_a()).await, 0);
+    assert!(!env.exists(&offer.escrow).await);
+    assert!(!env.exists(&offer.vault).await);
+}
+
+#[tokio::test]
+async fn release_returns_tokens_sent_after_the_make_to_the_maker() {
+    let mut env = Env::new().await;
+    let (offer, arbiter, recipient) = arbitrated(&mut env).await;
+    env.donate(&offer, 7).await;
+
+    let mut accounts = arbitrate_accounts(&offer, &arbiter.pubkey(), &recipient.pubkey(), true);
+    accounts.maker_ata_a = None;
+    assert_escrow_error(
+        env.send(
+            &[ix(accounts, escrow2::instruction::Release {})],
+            &[&arbiter],
+        )
+        .await,
+        EscrowError::SettlementAccountRequired,
+    );
+
+    let settle = release(&offer, &arbiter.pubkey(), &recipient.pubkey());
+    env.send(&[settle], &[&arbiter]).await.unwrap();
+
+    let recipient_ata_a = ata(&recipient.pubkey(), &offer.mint_a, &spl_token::ID);
+    assert_eq!(env.token_balance(&recipient_ata_a).await, 1_000);
+    assert_eq!(env.token_balance(&offer.maker_ata_a()).await, 7);
+    assert!(!env.exists(&offer.vault).await);
+}
+
+#[tokio::test]
+async fn disputed_escrows_can_still_be_refunded_by_the_arbiter() {
+    let mut env = Env::new().await;
+    let (offer, arbiter, recipient) = arbitrated(&mut env).await;
+
+    raise_dispute(&mut env, &offer, &recipient).await.unwrap();
+    assert_eq!(
+        env.escrow(&offer.escrow).await.status,
+        EscrowStatus::Disputed
+    );
+    assert_escrow_error(
+        raise_dispute(&mut env, &offer, &offer.maker).await,
+        EscrowError::AlreadyDisputed,
+    );
+
+    let settle = arbiter_refund(&offer, &arbiter.pubkey(), &recipient.pubkey());
+    env.send(&[settle], &[&arbiter]).await.unwrap();
+
+    assert_eq!(env.token_balance(&offer.maker_ata_a()).await, 1_000);
+    assert!(!env.exists(&offer.escrow).await);
+}
+
+#[tokio::test]
+async fn only_the_maker_or_the_recipient_can_dispute() {
+    let mut env = Env::new().await;
+    let (offer, arbiter, _) = arbitrated(&mut env).await;
+    let stranger = env.create_user().await;
+
+    for party in [&stranger, &arbiter] {
+        assert_escrow_error(
+            raise_dispute(&mut env, &offer, party).await,
+            EscrowError::NotEscrowParty,
+        );
+    }
+
+    let swap = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&swap, 1_000, 500).await.unwrap();
+    assert_escrow_error(
+        raise_dispute(&mut env, &swap, &swap.maker).await,
+        EscrowError::NotEscrowParty,
+    ); //Swaps have nobody to arbitrate them.
+}
+
+#[tokio::test]
+async fn only_the_arbiter_settles_to_the_named_recipient() {
+    let mut env = Env::new().await;
+    let (offer, arbiter, recipient) = arbitrated(&mut env).await;
+    let stranger = env.create_user().await;
+
+    let settle = release(&offer, &stranger.pubkey(), &recipient.pubkey());
+    assert_escrow_error(
+        env.send(&[settle], &[&stranger]).await,
+        EscrowError::NotArbiter,
+    );
+    let settle = release(&offer, &arbiter.pubkey(), &stranger.pubkey());
+    assert_escrow_error(
+        env.send(&[settle], &[&arbiter]).await,
+        EscrowError::RecipientMismatch,
+    );
+
+    let mut accounts = arbitrate_accounts(&offer, &arbiter.pubkey(), &recipient.pubkey(), true);
+    accounts.recipient_ata_a = None;
+    assert_escrow_error(
+        env.send(
+            &[ix(accounts, escrow2::instruction::Release {})],
+            &[&arbiter],
+        )
+        .await,
+        EscrowError::SettlementAccountRequired,
+    );
+}
+
+#[tokio::test]
+async fn settled_escrows_cannot_be_settled_again() {
+    let mut env = Env::new().await;
+    let (offer, arbiter, recipient) = arbitrated(&mut env).await;
+
+    let ixs = [
+        release(&offer, &arbiter.pubkey(), &recipient.pubkey()),
+        arbiter_refund(&offer, &arbiter.pubkey(), &recipient.pubkey()),
+    ];
+    assert!(env.send(&ixs, &[&arbiter]).await.is_err());
+    assert_eq!(env.token_balance(&offer.vault).await, 1_000);
+
+    let settle = release(&offer, &arbiter.pubkey(), &recipient.pubkey());
+    env.send(&[settle], &[&arbiter]).await.unwrap();
+    let settle = arbiter_refund(&offer, &arbiter.pubkey(), &recipient.pubkey());
+    assert!(env.send(&[settle], &[&arbiter]).await.is_err());
+    assert_eq!(env.token_balance(&offer.maker_ata_a()).await, 0);
+    assert!(raise_dispute(&mut env, &offer, &recipient).await.is_err());
+}
+
+#[tokio::test]
+async fn arbitrated_escrows_cannot_be_taken_updated_or_refunded() {
+    let mut env = Env::new().await;
+    let (offer, _, _) = arbitrated(&mut env).await;
+    let taker = env.taker(&offer, 1).await;
+
+    assert!(env.take(&offer, &taker).await.is_err()); //Nothing is asked in return, so there is no mint_b to pay with.
+    let mut accounts = offer.refund_accounts();
+    accounts.market_page = None; //Arbitrated escrows are not listed.
+    assert_escrow_error(
+        env.send(
+            &[ix(accounts, escrow2::instruction::Refund {})],
+            &[&offer.maker],
+        )
+        .await,
+        EscrowError::ArbitratedEscrow,
+    );
+    let update = escrow2::instruction::Update {
+        receive: 1,
+        deposit: 0,
+    };
+    assert_escrow_error(
+        env.send(&[ix(offer.update_accounts(), update)], &[&offer.maker])
+            .await,
+        EscrowError::ArbitratedEscrow,
+    );
+    assert_eq!(env.token_balance(&offer.vault).await, 1_000);
+}
+
+#[tokio::test]
+async fn make_rejects_invalid_arbiter_terms() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    let maker = offer.maker_key();
+    let other = Pubkey::new_unique();
+
+    for terms in [
+        ArbiterTerms {
+            arbiter: maker,
+            recipient: other,
+        },
+        ArbiterTerms {
+            arbiter: other,
+            recipient: maker,
+        },
+        ArbiterTerms {
+            arbiter: other,
+            recipient: other,
+        },
+    ] {
+        assert_escrow_error(
+            make_arbitrated(&mut env, &offer, terms, NEVER).await,
+            EscrowError::InvalidArbiterTerms,
+        );
+    }
+
+    let terms = ArbiterTerms {
+        arbiter: other,
+        recipient: Pubkey::new_unique(),
+    };
+    let now = env.now().await;
+    assert_escrow_error(
+        make_arbitrated(&mut env, &offer, terms, now).await,
+        EscrowError::InvalidExpiry,
+    ); //The arbiter would have no time to settle.
+}
+
+#[tokio::test]
+async fn makers_reclaim_undisputed_escrows_after_the_deadline() {
+    let mut env = Env::new().await;
+    let (offer, _, _) = arbitrated(&mut env).await;
+    let expires_at = env.escrow(&offer.escrow).await.expires_at;
+
+    assert_escrow_error(
+        env.send(&[reclaim(&offer)], &[&offer.maker]).await,
+        EscrowError::OfferNotExpired,
+    );
+
+    env.set_time(expires_at + 1).await;
+    env.send(&[reclaim(&offer)], &[&offer.maker]).await.unwrap();
+
+    assert_eq!(env.token_balance(&offer.maker_ata_a()).await, 1_000);
+    assert!(!env.exists(&offer.escrow).await);
+    assert!(!env.exists(&offer.vault).await);
+}
+
+#[tokio::test]
+async fn disputed_escrows_are_left_to_the_arbiter_after_the_deadline() {
+    let mut env = Env::new().await;
+    let (offer, arbiter, recipient) = arbitrated(&mut env).await;
+    raise_dispute(&mut env, &offer, &recipient).await.unwrap();
+    let expires_at = env.escrow(&offer.escrow).await.expires_at;
+    env.set_time(expires_at + 1).await;
+
+    assert_escrow_error(
+        env.send(&[reclaim(&offer)], &[&offer.maker]).await,
+        EscrowError::AlreadyDisputed,
+    );
+
+    let settle = release(&offer, &arbiter.pubkey(), &recipient.pubkey());
+    env.send(&[settle], &[&arbiter]).await.unwrap();
+    let recipient_ata_a = ata(&recipient.pubkey(), &offer.mint_a, &spl_token::ID);
+    assert_eq!(env.token_balance(&recipient_ata_a).await, 1_000);
+}
+
+#[tokio::test]
+async fn reclaim_rejects_swaps() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    let expires_at = env.now().await + 60;
+    let args = escrow2::state::MakeArgs {
+        expires_at,
+        ..offer.make_args(1_000, 500)
+    };
+    env.send(
+        &[offer.make_ix(offer.make_accounts(), args)],
+        &[&offer.maker],
+    )
+    .await
+    .unwrap();
+    env.set_time(expires_at + 1).await;
+
+    assert_escrow_error(
+        env.send(&[reclaim(&offer)], &[&offer.maker]).await,
+        EscrowError::NotArbitrated,
+    ); //Expired offers go through refund or refund_expired.
+}
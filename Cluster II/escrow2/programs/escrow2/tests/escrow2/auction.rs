@@ -0,0 +1,157 @@
+use anchor_spl::token::spl_token;
+use escrow2::{
+    error::EscrowError,
+    state::{AuctionTerms, DutchAuction, MakeArgs, OfferKind},
+};
+use solana_program_test::BanksClientError;
+use solana_sdk::signature::Signer;
+
+use crate::common::*;
+
+//Falls from 10_000 to 2_000 of mint_b for the whole deposit of 1_000 over 1_000 seconds, starting 100 seconds from now.
+async fn auction(env: &mut Env) -> (Offer, AuctionTerms) {
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    let now = env.now().await;
+    let terms = AuctionTerms {
+        start_price: 10_000,
+        end_price: 2_000,
+        start_at: now + 100,
+        end_at: now + 1_100,
+    };
+    (offer, terms)
+}
+
+async fn make_auction(
+    env: &mut Env,
+    offer: &Offer,
+    terms: AuctionTerms,
+) -> Result<(), BanksClientError> {
+    let args = MakeArgs {
+        kind: OfferKind::Auction(terms),
+        ..offer.make_args(1_000, terms.start_price)
+    };
+    env.send(
+        &[offer.make_ix(offer.make_accounts(), args)],
+        &[&offer.maker],
+    )
+    .await
+}
+
+#[tokio::test]
+async fn make_auction_stores_the_deposit_it_prices() {
+    let mut env = Env::new().await;
+    let (offer, terms) = auction(&mut env).await;
+
+    make_auction(&mut env, &offer, terms).await.unwrap();
+
+    assert_eq!(
+        env.escrow(&offer.escrow).await.auction,
+        Some(DutchAuction {
+            amount_a: 1_000,
+            start_price: 10_000,
+            end_price: 2_000,
+            start_at: terms.start_at,
+            end_at: terms.end_at,
+        })
+    );
+}
+
+#[tokio::test]
+async fn update_rejects_auctions() {
+    let mut env = Env::new().await;
+    let (offer, terms) = auction(&mut env).await;
+    make_auction(&mut env, &offer, terms).await.unwrap();
+
+    let update = escrow2::instruction::Update {
+        receive: 1,
+        deposit: 1_000,
+    };
+    assert_escrow_error(
+        env.send(&[ix(offer.update_accounts(), update)], &[&offer.maker])
+            .await,
+        EscrowError::PricedOfferUpdate,
+    );
+    assert_eq!(
+        env.escrow(&offer.escrow).await.remaining_b,
+        terms.start_price
+    );
+}
+
+#[tokio::test]
+async fn take_pays_the_decayed_price() {
+    for (elapsed, paid) in [(0, 10_000), (600, 6_000), (2_000, 2_000)] {
+        let mut env = Env::new().await;
+        let (offer, terms) = auction(&mut env).await;
+        make_auction(&mut env, &offer, terms).await.unwrap();
+        let taker = env.taker(&offer, 10_000).await;
+        env.set_time(terms.start_at - 100 + elapsed).await;
+
+        env.take(&offer, &taker).await.unwrap();
+
+        assert_eq!(env.token_balance(&offer.maker_ata_b()).await, paid);
+        let taker_ata_a = ata(&taker.pubkey(), &offer.mint_a, &spl_token::ID);
+        assert_eq!(env.token_balance(&taker_ata_a).await, 1_000);
+    }
+}
+
+#[tokio::test]
+async fn take_partial_prices_each_fill_when_it_happens() {
+    let mut env = Env::new().await;
+    let (offer, terms) = auction(&mut env).await;
+    make_auction(&mut env, &offer, terms).await.unwrap();
+    let taker = env.taker(&offer, 10_000).await;
+
+    env.set_time(terms.start_at + 500).await; //6_000 for the whole deposit.
+    env.take_partial(&offer, &taker, 3_000).await.unwrap();
+    let taker_ata_a = ata(&taker.pubkey(), &offer.mint_a, &spl_token::ID);
+    assert_eq!(env.token_balance(&taker_ata_a).await, 500);
+    assert_eq!(env.escrow(&offer.escrow).await.remaining_b, 3_000); //Half of the price at the time of the fill.
+
+    env.set_time(terms.end_at).await; //2_000 for the whole deposit.
+    env.take(&offer, &taker).await.unwrap();
+    assert_eq!(env.token_balance(&offer.maker_ata_b()).await, 3_000 + 1_000);
+}
+
+#[tokio::test]
+async fn make_auction_rejects_invalid_terms() {
+    let mut env = Env::new().await;
+    let (offer, terms) = auction(&mut env).await;
+
+    for invalid in [
+        AuctionTerms {
+            start_price: 1_000,
+            ..terms
+        },
+        AuctionTerms {
+            end_price: 0,
+            ..terms
+        },
+        AuctionTerms {
+            end_at: terms.start_at,
+            ..terms
+        },
+    ] {
+        assert_escrow_error(
+            make_auction(&mut env, &offer, invalid).await,
+            EscrowError::InvalidAuctionTerms,
+        );
+    }
+}
+
+#[tokio::test]
+async fn take_rejects_auctions_too_long_to_price() {
+    let mut env = Env::new().await;
+    let (offer, terms) = auction(&mut env).await;
+    let terms = AuctionTerms {
+        start_at: i64::MIN,
+        end_at: i64::MAX,
+        ..terms
+    };
+    make_auction(&mut env, &offer, terms).await.unwrap();
+    let taker = env.taker(&offer, 10_000).await;
+
+    assert_escrow_error(
+        env.take(&offer, &taker).await,
+        EscrowError::ArithmeticOverflow,
+    );
+}
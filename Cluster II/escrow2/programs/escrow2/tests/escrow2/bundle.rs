@@ -0,0 +1,303 @@
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::{token::spl_token, token_2022::spl_token_2022};
+use escrow2::{error::EscrowError, state::BundleLeg};
+use solana_sdk::signature::{Keypair, Signer};
+
+use crate::common::*;
+
+const MIXED: [Pubkey; 2] = [spl_token::ID, spl_token_2022::ID];
+
+#[tokio::test]
+async fn make_bundle_fills_a_vault_per_offered_mint() {
+    let mut env = Env::new().await;
+    let offer = env.bundle_offer(&MIXED, &MIXED, 1_000).await;
+
+    env.make_bundle(&offer, 400, 50).await.unwrap();
+
+    for (mint, token_program) in &offer.offered {
+        let vault = offer.vault(mint, token_program);
+        assert_eq!(env.token_balance(&vault).await, 400);
+        let maker_ata = ata(&offer.maker_key(), mint, token_program);
+        assert_eq!(env.token_balance(&maker_ata).await, 600);
+    }
+    let bundle = env.bundle(&offer.bundle).await;
+    assert_eq!(bundle.maker, offer.maker_key());
+    assert_eq!(
+        bundle.offered,
+        vec![
+            BundleLeg {
+                mint: offer.offered[0].0,
+                amount: 400
+            },
+            BundleLeg {
+                mint: offer.offered[1].0,
+                amount: 400
+            },
+        ]
+    );
+    assert_eq!(bundle.requested, offer.requested_legs(50));
+}
+
+#[tokio::test]
+async fn make_bundle_does_not_offer_tokens_already_in_a_vault() {
+    let mut env = Env::new().await;
+    let offer = env.bundle_offer(&MIXED, &MIXED, 1_000).await;
+    let (funded, funded_program) = offer.offered[0];
+    env.mint_to(&funded, &offer.bundle, &funded_program, 7)
+        .await; //Creates the vault before the make.
+
+    env.make_bundle(&offer, 400, 500).await.unwrap();
+
+    let bundle = env.bundle(&offer.bundle).await;
+    assert!(bundle.offered.iter().all(|leg| leg.amount == 400));
+    let taker = env.bundle_taker(&offer, 500).await;
+    env.take_bundle(&offer, &taker).await.unwrap();
+    for (mint, token_program) in &offer.offered {
+        let taker_ata = ata(&taker.pubkey(), mint, token_program);
+        let maker_ata = ata(&offer.maker_key(), mint, token_program);
+        let surplus = if *mint == funded { 7 } else { 0 };
+        assert_eq!(env.token_balance(&taker_ata).await, 400);
+        assert_eq!(env.token_balance(&maker_ata).await, 600 + surplus);
+    }
+}
+
+#[tokio::test]
+async fn take_bundle_settles_every_leg() {
+    let mut env = Env::with_fee(100).await; //1%
+    let offer = env.bundle_offer(&MIXED, &MIXED, 1_000).await;
+    env.make_bundle(&offer, 400, 500).await.unwrap();
+    let taker = env.bundle_taker(&offer, 500).await;
+
+    env.take_bundle(&offer, &taker).await.unwrap();
+
+    for (mint, token_program) in &offer.offered {
+        let taker_ata = ata(&taker.pubkey(), mint, token_program);
+        assert_eq!(env.token_balance(&taker_ata).await, 400);
+        assert!(!env.exists(&offer.vault(mint, token_program)).await);
+    }
+    for (mint, token_program) in &offer.requested {
+        let maker_ata = ata(&offer.maker_key(), mint, token_program);
+        let treasury_ata = ata(&env.treasury.pubkey(), mint, token_program);
+        assert_eq!(env.token_balance(&maker_ata).await, 495);
+        assert_eq!(env.token_balance(&treasury_ata).await, 5);
+    }
+    assert!(!env.exists(&offer.bundle).await);
+}
+
+#[tokio::test]
+async fn take_bundle_returns_tokens_sent_after_make_bundle_to_the_maker() {
+    let mut env = Env::new().await;
+    let offer = env.bundle_offer(&MIXED, &MIXED, 1_000).await;
+    env.make_bundle(&offer, 400, 500).await.unwrap();
+    let (donated, donated_program) = offer.offered[1];
+    let donation = spl_token_2022::instruction::mint_to(
+        &donated_program,
+        &donated,
+        &offer.vault(&donated, &donated_program),
+        &env.payer(),
+        &[],
+        7,
+    )
+    .unwrap(); //Straight into the Token-2022 vault.
+    env.send(&[donation], &[]).await.unwrap();
+    let taker = env.bundle_taker(&offer, 500).await;
+
+    env.take_bundle(&offer, &taker).await.unwrap();
+
+    for (mint, token_program) in &offer.offered {
+        let taker_ata = ata(&taker.pubkey(), mint, token_program);
+        let maker_ata = ata(&offer.maker_key(), mint, token_program);
+        let surplus = if *mint == donated { 7 } else { 0 };
+        assert_eq!(env.token_balance(&taker_ata).await, 400);
+        assert_eq!(env.token_balance(&maker_ata).await, 600 + surplus);
+        assert!(!env.exists(&offer.vault(mint, token_program)).await);
+    }
+}
+
+#[tokio::test]
+async fn take_bundle_fails_entirely_when_one_leg_fails() {
+    let mut env = Env::new().await;
+    let offer = env.bundle_offer(&MIXED, &MIXED, 1_000).await;
+    env.make_bundle(&offer, 400, 500).await.unwrap();
+    let taker = env.create_user().await;
+    let (first, first_program) = offer.requested[0];
+    let (second, second_program) = offer.requested[1];
+    env.mint_to(&first, &taker.pubkey(), &first_program, 500)
+        .await;
+    env.mint_to(&second, &taker.pubkey(), &second_program, 499)
+        .await; //One short on the last leg.
+
+    assert_escrow_error(
+        env.take_bundle(&offer, &taker).await,
+        EscrowError::DepositFailed,
+    );
+
+    let taker_ata = ata(&taker.pubkey(), &first, &first_program);
+    assert_eq!(env.token_balance(&taker_ata).await, 500); //The first leg was rolled back.
+    for (mint, token_program) in &offer.offered {
+        assert_eq!(
+            env.token_balance(&offer.vault(mint, token_program)).await,
+            400
+        );
+    }
+    assert!(env.exists(&offer.bundle).await);
+}
+
+#[tokio::test]
+async fn take_bundle_rejects_legs_that_do_not_match() {
+    let mut env = Env::new().await;
+    let offer = env.bundle_offer(&MIXED, &MIXED, 1_000).await;
+    env.make_bundle(&offer, 400, 500).await.unwrap();
+    let taker = env.bundle_taker(&offer, 500).await;
+
+    let mut ix = offer.take_ix(&taker.pubkey(), &env.treasury.pubkey());
+    ix.accounts.pop(); //Last requested leg is missing its treasury account.
+    assert_escrow_error(
+        env.send(&[ix], &[&taker]).await,
+        EscrowError::BundleAccountsMismatch,
+    );
+
+    let swapped = BundleOffer::new(
+        Keypair::from_bytes(&offer.maker.to_bytes()).unwrap(),
+        offer.seed,
+        offer.offered.clone(),
+        offer.requested.iter().rev().copied().collect(),
+    );
+    let ix = swapped.take_ix(&taker.pubkey(), &env.treasury.pubkey());
+    assert_escrow_error(
+        env.send(&[ix], &[&taker]).await,
+        EscrowError::BundleAccountsMismatch,
+    );
+}
+
+#[tokio::test]
+async fn take_bundle_checks_expiry_and_allowed_taker() {
+    let mut env = Env::new().await;
+    let offer = env.bundle_offer(&MIXED, &MIXED, 1_000).await;
+    let allowed = env.bundle_taker(&offer, 500).await;
+    let other = env.bundle_taker(&offer, 500).await;
+    let expires_at = env.now().await + 60;
+    let ix = offer.make_ix(
+        vec![400, 400],
+        offer.requested_legs(500),
+        expires_at,
+        Some(allowed.pubkey()),
+    );
+    env.send(&[ix], &[&offer.maker]).await.unwrap();
+
+    assert_escrow_error(
+        env.take_bundle(&offer, &other).await,
+        EscrowError::UnauthorizedTaker,
+    );
+
+    env.set_time(expires_at + 1).await;
+    assert_escrow_error(
+        env.take_bundle(&offer, &allowed).await,
+        EscrowError::OfferExpired,
+    );
+}
+
+#[tokio::test]
+async fn refund_bundle_returns_every_leg() {
+    let mut env = Env::new().await;
+    let offer = env.bundle_offer(&MIXED, &[spl_token::ID], 1_000).await;
+    env.make_bundle(&offer, 400, 500).await.unwrap();
+
+    env.refund_bundle(&offer).await.unwrap();
+
+    for (mint, token_program) in &offer.offered {
+        let maker_ata = ata(&offer.maker_key(), mint, token_program);
+        assert_eq!(env.token_balance(&maker_ata).await, 1_000);
+        assert!(!env.exists(&offer.vault(mint, token_program)).await);
+    }
+    assert!(!env.exists(&offer.bundle).await);
+}
+
+#[tokio::test]
+async fn refund_bundle_expired_lets_anyone_return_an_expired_bundle() {
+    let mut env = Env::new().await;
+    let offer = env.bundle_offer(&MIXED, &[spl_token::ID], 1_000).await;
+    let expires_at = env.now().await + 60;
+    let ix = offer.make_ix(vec![400, 400], offer.requested_legs(500), expires_at, None);
+    env.send(&[ix], &[&offer.maker]).await.unwrap();
+    let crank = env.create_user().await;
+
+    assert_escrow_error(
+        env.send(&[offer.refund_expired_ix(&crank.pubkey())], &[&crank])
+            .await,
+        EscrowError::OfferNotExpired,
+    );
+
+    env.set_time(expires_at + 1).await;
+    env.send(&[offer.refund_expired_ix(&crank.pubkey())], &[&crank])
+        .await
+        .unwrap();
+
+    for (mint, token_program) in &offer.offered {
+        let maker_ata = ata(&offer.maker_key(), mint, token_program);
+        assert_eq!(env.token_balance(&maker_ata).await, 1_000);
+        assert!(!env.exists(&offer.vault(mint, token_program)).await);
+    }
+    assert!(!env.exists(&offer.bundle).await);
+}
+
+#[tokio::test]
+async fn make_bundle_rejects_invalid_legs() {
+    let mut env = Env::new().await;
+    let offer = env
+        .bundle_offer(&[spl_token::ID], &[spl_token::ID; 5], 1_000)
+        .await;
+    let offered_mint = offer.offered[0].0;
+
+    let too_many = offer.make_ix(vec![100], offer.requested_legs(10), NEVER, None);
+    let zero_amount = offer.make_ix(
+        vec![100],
+        offer.requested_legs(0)[..1].to_vec(),
+        NEVER,
+        None,
+    );
+    let same_mint = offer.make_ix(
+        vec![100],
+        vec![BundleLeg {
+            mint: offered_mint,
+            amount: 10,
+        }],
+        NEVER,
+        None,
+    );
+    let nothing_asked = offer.make_ix(vec![100], vec![], NEVER, None);
+    for ix in [too_many, zero_amount, same_mint, nothing_asked] {
+        assert_escrow_error(
+            env.send(&[ix], &[&offer.maker]).await,
+            EscrowError::InvalidBundleLegs,
+        );
+    }
+
+    let twice = BundleOffer::new(
+        Keypair::from_bytes(&offer.maker.to_bytes()).unwrap(),
+        offer.seed,
+        vec![offer.offered[0]; 2],
+        offer.requested.clone(),
+    );
+    let ix = twice.make_ix(
+        vec![100, 100],
+        twice.requested_legs(10)[..1].to_vec(),
+        NEVER,
+        None,
+    );
+    assert_escrow_error(
+        env.send(&[ix], &[&offer.maker]).await,
+        EscrowError::InvalidBundleLegs,
+    );
+
+    let ix = offer.make_ix(
+        vec![100, 100],
+        offer.requested_legs(10)[..1].to_vec(),
+        NEVER,
+        None,
+    ); //Two deposits, but the accounts of a single leg.
+    assert_escrow_error(
+        env.send(&[ix], &[&offer.maker]).await,
+        EscrowError::BundleAccountsMismatch,
+    );
+}
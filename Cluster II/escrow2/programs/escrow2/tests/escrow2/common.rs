@@ -0,0 +1,1052 @@
+use anchor_lang::{
+    prelude::{AccountInfo, Clock, Pubkey},
+    solana_program::{
+        entrypoint::ProgramResult, instruction::Instruction, program_option::COption,
+        program_pack::Pack, system_instruction, system_program,
+    },
+    AccountDeserialize, AnchorSerialize, InstructionData, ToAccountMetas,
+};
+use anchor_spl::{
+    associated_token::{self, get_associated_token_address_with_program_id},
+    token::spl_token,
+    token_2022::spl_token_2022::{
+        self,
+        extension::{
+            transfer_fee::instruction::initialize_transfer_fee_config, ExtensionType,
+            StateWithExtensions,
+        },
+    },
+};
+use escrow2::{
+    constants::{
+        METADATA_V1_KEY, PYTH_AGGREGATE_OFFSET, PYTH_EXPONENT_OFFSET, PYTH_MAGIC,
+        PYTH_ORACLE_PROGRAM_ID, PYTH_PRICE_ACCOUNT_MIN_LEN, PYTH_PRICE_ACCOUNT_TYPE,
+        PYTH_PUBLISH_TIME_OFFSET, PYTH_STATUS_TRADING, PYTH_VERSION, TOKEN_METADATA_PROGRAM_ID,
+    },
+    error::EscrowError,
+    state::{
+        Bundle, BundleLeg, Escrow, MakeArgs, MetadataCollection, MetadataCreator, OfferKind,
+        TokenMetadata,
+    },
+};
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+    instruction::{AccountMeta, InstructionError},
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+
+pub const DECIMALS: u8 = 6;
+pub const NEVER: i64 = i64::MAX; //Expiry used by offers that should stay open for the whole test.
+pub const TAKE_ANY: escrow2::instruction::Take = escrow2::instruction::Take {
+    expected_receive_a: 0,
+    max_pay_b: u64::MAX,
+}; //Take arguments that accept any fill, for tests that are not about slippage.
+
+//TakePartial arguments for amount_b that accept any fill.
+pub fn take_partial_any(amount_b: u64) -> escrow2::instruction::TakePartial {
+    escrow2::instruction::TakePartial {
+        amount_b,
+        min_receive_a: 0,
+        max_pay_b: u64::MAX,
+    }
+}
+const USER_LAMPORTS: u64 = 10_000_000_000;
+
+//Anchor ties the lifetime of the accounts slice to the account infos, which the processor! signature can't express.
+//Leaking the slice is fine in tests, every instruction gets its own copy.
+fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    escrow2::entry(program_id, accounts, data)
+}
+
+//Runs escrow2 natively, or the compiled program when run through cargo test-sbf.
+pub fn program_test() -> ProgramTest {
+    let mut program_test =
+        ProgramTest::new("escrow2", escrow2::ID, processor!(process_instruction));
+    for (mint, owner) in [
+        (spl_token::native_mint::ID, spl_token::ID),
+        (spl_token_2022::native_mint::ID, spl_token_2022::ID),
+    ] {
+        program_test.add_account(mint, native_mint_account(owner));
+    }
+    program_test
+}
+
+fn native_mint_account(owner: Pubkey) -> Account {
+    let mut data = vec![0; spl_token::state::Mint::LEN];
+    spl_token::state::Mint {
+        mint_authority: COption::None,
+        supply: 0,
+        decimals: spl_token::native_mint::DECIMALS,
+        is_initialized: true,
+        freeze_authority: COption::None,
+    }
+    .pack_into_slice(&mut data);
+    Account {
+        lamports: 1_000_000_000,
+        data,
+        owner,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+//Builds an escrow2 instruction from the Anchor generated account and argument structs.
+pub fn ix(accounts: impl ToAccountMetas, args: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: escrow2::ID,
+        accounts: accounts.to_account_metas(None),
+        data: args.data(),
+    }
+}
+
+pub fn program_data_address() -> Pubkey {
+    Pubkey::find_program_address(&[escrow2::ID.as_ref()], &bpf_loader_upgradeable::ID).0
+}
+
+pub fn config_address() -> Pubkey {
+    Pubkey::find_program_address(&[b"config"], &escrow2::ID).0
+}
+
+pub fn escrow_address(maker: &Pubkey, seed: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"escrow", maker.as_ref(), &seed.to_le_bytes()],
+        &escrow2::ID,
+    )
+    .0
+}
+
+pub fn bundle_address(maker: &Pubkey, seed: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"bundle", maker.as_ref(), &seed.to_le_bytes()],
+        &escrow2::ID,
+    )
+    .0
+}
+
+pub fn metadata_address(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"metadata",
+            TOKEN_METADATA_PROGRAM_ID.as_ref(),
+            mint.as_ref(),
+        ],
+        &TOKEN_METADATA_PROGRAM_ID,
+    )
+    .0
+}
+
+//Metadata of an NFT with the given royalty, creators and collection. The mint is filled in by Env::set_metadata.
+pub fn nft_metadata(
+    seller_fee_basis_points: u16,
+    creators: Vec<MetadataCreator>,
+    collection: Option<MetadataCollection>,
+) -> TokenMetadata {
+    TokenMetadata {
+        key: METADATA_V1_KEY,
+        update_authority: Pubkey::new_unique(),
+        mint: Pubkey::default(),
+        name: "Cluster NFT".to_string(),
+        symbol: "CNFT".to_string(),
+        uri: "https://example.com/nft.json".to_string(),
+        seller_fee_basis_points,
+        creators: Some(creators),
+        primary_sale_happened: true,
+        is_mutable: true,
+        edition_nonce: None,
+        token_standard: Some(0), //NonFungible
+        collection,
+    }
+}
+
+pub fn maker_state_address(maker: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"maker", maker.as_ref()], &escrow2::ID).0
+}
+
+pub fn stats_address(wallet: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"stats", wallet.as_ref()], &escrow2::ID).0
+}
+
+pub fn market_page_address(mint_a: &Pubkey, mint_b: &Pubkey, page: u32) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"market",
+            mint_a.as_ref(),
+            mint_b.as_ref(),
+            &page.to_le_bytes(),
+        ],
+        &escrow2::ID,
+    )
+    .0
+}
+
+pub fn is_native(mint: &Pubkey) -> bool {
+    *mint == spl_token::native_mint::ID || *mint == spl_token_2022::native_mint::ID
+}
+
+pub fn ata(owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(owner, mint, token_program)
+}
+
+//Asserts the transaction failed with the given escrow2 error.
+pub fn assert_escrow_error(result: Result<(), BanksClientError>, error: EscrowError) {
+    assert_custom_error(result, u32::from(error));
+}
+
+pub fn assert_custom_error(result: Result<(), BanksClientError>, code: u32) {
+    match result.expect_err("transaction should have failed").unwrap() {
+        TransactionError::InstructionError(_, InstructionError::Custom(actual)) => {
+            assert_eq!(actual, code)
+        }
+        other => panic!("expected custom error {code}, got {other:?}"),
+    }
+}
+
+pub struct Env {
+    pub ctx: ProgramTestContext,
+    pub treasury: Keypair,
+}
+
+impl Env {
+    //Starts a fresh validator with the config initialized and no protocol fee.
+    pub async fn new() -> Self {
+        Self::with_fee(0).await
+    }
+
+    pub async fn with_fee(fee_bps: u16) -> Self {
+        let mut env = Self::without_config().await;
+        env.initialize_config(fee_bps).await.unwrap();
+        env
+    }
+
+    pub async fn without_config() -> Self {
+        Self::start(program_test()).await
+    }
+
+    //Starts from a ProgramTest the caller may have preloaded with accounts. The config is not initialized.
+    //The payer is made the upgrade authority of the program, which processor! loads without a ProgramData account.
+    pub async fn start(program_test: ProgramTest) -> Self {
+        let mut env = Self {
+            ctx: program_test.start_with_context().await,
+            treasury: Keypair::new(),
+        };
+        let payer = env.payer();
+        env.set_upgrade_authority(Some(payer));
+        env
+    }
+
+    pub fn set_upgrade_authority(&mut self, upgrade_authority_address: Option<Pubkey>) {
+        let state = UpgradeableLoaderState::ProgramData {
+            slot: 0,
+            upgrade_authority_address,
+        };
+        let account =
+            Account::new_data(1_000_000_000, &state, &bpf_loader_upgradeable::ID).unwrap();
+        self.ctx
+            .set_account(&program_data_address(), &account.into());
+    }
+
+    pub fn payer(&self) -> Pubkey {
+        self.ctx.payer.pubkey()
+    }
+
+    pub async fn initialize_config(&mut self, fee_bps: u16) -> Result<(), BanksClientError> {
+        let accounts = escrow2::accounts::InitializeConfig {
+            admin: self.payer(),
+            program_data: program_data_address(),
+            config: config_address(),
+            system_program: system_program::ID,
+        };
+        let args = escrow2::instruction::InitializeConfig {
+            fee_bps,
+            treasury: self.treasury.pubkey(),
+        };
+        self.send(&[ix(accounts, args)], &[]).await
+    }
+
+    //Signs with the payer and the extra signers and processes the transaction.
+    pub async fn send(
+        &mut self,
+        ixs: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<(), BanksClientError> {
+        let blockhash = self.ctx.banks_client.get_latest_blockhash().await.unwrap();
+        let mut all_signers = vec![&self.ctx.payer];
+        all_signers.extend_from_slice(signers);
+        let tx = Transaction::new_signed_with_payer(
+            ixs,
+            Some(&self.ctx.payer.pubkey()),
+            &all_signers,
+            blockhash,
+        );
+        self.ctx.banks_client.process_transaction(tx).await
+    }
+
+    pub async fn create_user(&mut self) -> Keypair {
+        let user = Keypair::new();
+        let ix = system_instruction::transfer(&self.payer(), &user.pubkey(), USER_LAMPORTS);
+        self.send(&[ix], &[]).await.unwrap();
+        user
+    }
+
+    //Creates a mint owned by token_program, with the payer as mint and freeze authority.
+    pub async fn create_mint(&mut self, token_program: &Pubkey) -> Pubkey {
+        self.create_mint_account(Keypair::new(), token_program, DECIMALS, &[], vec![])
+            .await
+    }
+
+    //Creates a Token-2022 mint charging fee_bps on every transfer, capped at max_fee.
+    pub async fn create_fee_mint(&mut self, fee_bps: u16, max_fee: u64) -> Pubkey {
+        let payer = self.payer();
+        let mint = Keypair::new();
+        let init_fee = initialize_transfer_fee_config(
+            &spl_token_2022::ID,
+            &mint.pubkey(),
+            Some(&payer),
+            Some(&payer),
+            fee_bps,
+            max_fee,
+        )
+        .unwrap();
+        self.create_mint_account(
+            mint,
+            &spl_token_2022::ID,
+            DECIMALS,
+            &[ExtensionType::TransferFeeConfig],
+            vec![init_fee],
+        )
+        .await
+    }
+
+    async fn create_mint_account(
+        &mut self,
+        mint: Keypair,
+        token_program: &Pubkey,
+        decimals: u8,
+        extensions: &[ExtensionType],
+        init_extensions: Vec<Instruction>,
+    ) -> Pubkey {
+        let payer = self.payer();
+        let space =
+            ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(extensions)
+                .unwrap();
+        let rent = self.ctx.banks_client.get_rent().await.unwrap();
+
+        let mut ixs = vec![system_instruction::create_account(
+            &payer,
+            &mint.pubkey(),
+            rent.minimum_balance(space),
+            space as u64,
+            token_program,
+        )];
+        ixs.extend(init_extensions);
+        ixs.push(
+            spl_token_2022::instruction::initialize_mint2(
+                token_program,
+                &mint.pubkey(),
+                &payer,
+                Some(&payer),
+                decimals,
+            )
+            .unwrap(),
+        );
+        self.send(&ixs, &[&mint]).await.unwrap();
+        mint.pubkey()
+    }
+
+    //Creates an NFT owned by `owner`: a mint with no decimals and a supply of 1, described by `metadata`.
+    pub async fn create_nft(&mut self, owner: &Pubkey, metadata: TokenMetadata) -> Pubkey {
+        let mint = self
+            .create_mint_account(Keypair::new(), &spl_token::ID, 0, &[], vec![])
+            .await;
+        self.mint_to(&mint, owner, &spl_token::ID, 1).await;
+        self.set_metadata(&mint, metadata).await;
+        mint
+    }
+
+    //Writes the Token Metadata account of `mint`. The Metaplex program is not loaded, escrow2 only reads its accounts.
+    pub async fn set_metadata(&mut self, mint: &Pubkey, metadata: TokenMetadata) {
+        let data = TokenMetadata {
+            mint: *mint,
+            ..metadata
+        }
+        .try_to_vec()
+        .unwrap();
+        let rent = self.ctx.banks_client.get_rent().await.unwrap();
+        let account = Account {
+            lamports: rent.minimum_balance(data.len()),
+            data,
+            owner: TOKEN_METADATA_PROGRAM_ID,
+            executable: false,
+            rent_epoch: 0,
+        };
+        self.ctx
+            .set_account(&metadata_address(mint), &account.into());
+    }
+
+    //Writes a Pyth price account at `feed` with a trading aggregate price. The Pyth program is not loaded either.
+    pub async fn set_price_feed(
+        &mut self,
+        feed: &Pubkey,
+        price: i64,
+        confidence: u64,
+        exponent: i32,
+        publish_time: i64,
+    ) {
+        let mut data = vec![0u8; PYTH_PRICE_ACCOUNT_MIN_LEN];
+        let mut write =
+            |offset: usize, bytes: &[u8]| data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        write(0, &PYTH_MAGIC.to_le_bytes());
+        write(4, &PYTH_VERSION.to_le_bytes());
+        write(8, &PYTH_PRICE_ACCOUNT_TYPE.to_le_bytes());
+        write(PYTH_EXPONENT_OFFSET, &exponent.to_le_bytes());
+        write(PYTH_PUBLISH_TIME_OFFSET, &publish_time.to_le_bytes());
+        write(PYTH_AGGREGATE_OFFSET, &price.to_le_bytes());
+        write(PYTH_AGGREGATE_OFFSET + 8, &confidence.to_le_bytes());
+        write(
+            PYTH_AGGREGATE_OFFSET + 16,
+            &PYTH_STATUS_TRADING.to_le_bytes(),
+        );
+        let rent = self.ctx.banks_client.get_rent().await.unwrap();
+        let account = Account {
+            lamports: rent.minimum_balance(data.len()),
+            data,
+            owner: PYTH_ORACLE_PROGRAM_ID,
+            executable: false,
+            rent_epoch: 0,
+        };
+        self.ctx.set_account(feed, &account.into());
+    }
+
+    pub async fn create_ata(
+        &mut self,
+        owner: &Pubkey,
+        mint: &Pubkey,
+        token_program: &Pubkey,
+    ) -> Pubkey {
+        let address = ata(owner, mint, token_program);
+        let ix = Instruction {
+            program_id: associated_token::ID,
+            accounts: vec![
+                AccountMeta::new(self.payer(), true),
+                AccountMeta::new(address, false),
+                AccountMeta::new_readonly(*owner, false),
+                AccountMeta::new_readonly(*mint, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+                AccountMeta::new_readonly(*token_program, false),
+            ],
+            data: vec![0], //Create
+        };
+        self.send(&[ix], &[]).await.unwrap();
+        address
+    }
+
+    //Creates the owner's token account and mints amount to it.
+    pub async fn mint_to(
+        &mut self,
+        mint: &Pubkey,
+        owner: &Pubkey,
+        token_program: &Pubkey,
+        amount: u64,
+    ) -> Pubkey {
+        let address = self.create_ata(owner, mint, token_program).await;
+        let ix = spl_token_2022::instruction::mint_to(
+            token_program,
+            mint,
+            &address,
+            &self.payer(),
+            &[],
+            amount,
+        )
+        .unwrap();
+        self.send(&[ix], &[]).await.unwrap();
+        address
+    }
+
+    pub async fn freeze(&mut self, account: &Pubkey, mint: &Pubkey, token_program: &Pubkey) {
+        let ix = spl_token_2022::instruction::freeze_account(
+            token_program,
+            account,
+            mint,
+            &self.payer(),
+            &[],
+        )
+        .unwrap();
+        self.send(&[ix], &[]).await.unwrap();
+    }
+
+    //Sends `amount` of mint_a straight to the vault of `offer`, like anyone can once the vault exists.
+    pub async fn donate(&mut self, offer: &Offer, amount: u64) {
+        let ixs = if is_native(&offer.mint_a) {
+            vec![
+                system_instruction::transfer(&self.payer(), &offer.vault, amount),
+                spl_token_2022::instruction::sync_native(&offer.token_program_a, &offer.vault)
+                    .unwrap(),
+            ]
+        } else {
+            vec![spl_token_2022::instruction::mint_to(
+                &offer.token_program_a,
+                &offer.mint_a,
+                &offer.vault,
+                &self.payer(),
+                &[],
+                amount,
+            )
+            .unwrap()]
+        };
+        self.send(&ixs, &[]).await.unwrap();
+    }
+
+    pub async fn account(&mut self, address: &Pubkey) -> Option<Account> {
+        self.ctx.banks_client.get_account(*address).await.unwrap()
+    }
+
+    pub async fn exists(&mut self, address: &Pubkey) -> bool {
+        self.account(address).await.is_some()
+    }
+
+    pub async fn lamports(&mut self, address: &Pubkey) -> u64 {
+        self.account(address)
+            .await
+            .map_or(0, |account| account.lamports)
+    }
+
+    pub async fn token_balance(&mut self, address: &Pubkey) -> u64 {
+        let account = self
+            .account(address)
+            .await
+            .expect("token account does not exist");
+        StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data)
+            .unwrap()
+            .base
+            .amount
+    }
+
+    pub async fn escrow(&mut self, address: &Pubkey) -> Escrow {
+        let account = self.account(address).await.expect("escrow does not exist");
+        Escrow::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    pub async fn bundle(&mut self, address: &Pubkey) -> Bundle {
+        let account = self.account(address).await.expect("bundle does not exist");
+        Bundle::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    pub async fn now(&mut self) -> i64 {
+        self.ctx
+            .banks_client
+            .get_sysvar::<Clock>()
+            .await
+            .unwrap()
+            .unix_timestamp
+    }
+
+    //Moves to a new slot first, so a transaction that failed before the clock change is not rejected as a duplicate when retried.
+    pub async fn set_time(&mut self, unix_timestamp: i64) {
+        let slot = self
+            .ctx
+            .banks_client
+            .get_sysvar::<Clock>()
+            .await
+            .unwrap()
+            .slot;
+        self.ctx.warp_to_slot(slot + 1).unwrap();
+        let mut clock = self.ctx.banks_client.get_sysvar::<Clock>().await.unwrap();
+        clock.unix_timestamp = unix_timestamp;
+        self.ctx.set_sysvar(&clock);
+    }
+
+    //Creates a funded maker and the two mints of an offer. The maker holds `balance` of mint_a.
+    pub async fn offer(
+        &mut self,
+        token_program_a: Pubkey,
+        token_program_b: Pubkey,
+        balance: u64,
+    ) -> Offer {
+        let mint_a = self.create_mint(&token_program_a).await;
+        let mint_b = self.create_mint(&token_program_b).await;
+        self.offer_with_mints(mint_a, token_program_a, mint_b, token_program_b, balance)
+            .await
+    }
+
+    pub async fn offer_with_mints(
+        &mut self,
+        mint_a: Pubkey,
+        token_program_a: Pubkey,
+        mint_b: Pubkey,
+        token_program_b: Pubkey,
+        balance: u64,
+    ) -> Offer {
+        let maker = self.create_user().await;
+        if balance > 0 {
+            self.mint_to(&mint_a, &maker.pubkey(), &token_program_a, balance)
+                .await;
+        }
+        Offer::new(maker, mint_a, token_program_a, mint_b, token_program_b, 1)
+    }
+
+    //Creates a funded taker holding `balance` of the offer's mint_b.
+    pub async fn taker(&mut self, offer: &Offer, balance: u64) -> Keypair {
+        let taker = self.create_user().await;
+        self.mint_to(
+            &offer.mint_b,
+            &taker.pubkey(),
+            &offer.token_program_b,
+            balance,
+        )
+        .await;
+        taker
+    }
+
+    pub async fn make(
+        &mut self,
+        offer: &Offer,
+        deposit: u64,
+        receive: u64,
+    ) -> Result<(), BanksClientError> {
+        self.make_with(offer, deposit, receive, NEVER, None).await
+    }
+
+    pub async fn make_with(
+        &mut self,
+        offer: &Offer,
+        deposit: u64,
+        receive: u64,
+        expires_at: i64,
+        allowed_taker: Option<Pubkey>,
+    ) -> Result<(), BanksClientError> {
+        let args = MakeArgs {
+            expires_at,
+            allowed_taker,
+            ..offer.make_args(deposit, receive)
+        };
+        self.send(
+            &[offer.make_ix(offer.make_accounts(), args)],
+            &[&offer.maker],
+        )
+        .await
+    }
+
+    //Makes a wrapped SOL offer paid straight from the maker's lamports, without a maker token account.
+    pub async fn make_native(
+        &mut self,
+        offer: &Offer,
+        deposit: u64,
+        receive: u64,
+    ) -> Result<(), BanksClientError> {
+        let mut accounts = offer.make_accounts();
+        accounts.maker_ata_a = None;
+        let args = offer.make_args(deposit, receive);
+        self.send(&[offer.make_ix(accounts, args)], &[&offer.maker])
+            .await
+    }
+
+    pub async fn take(&mut self, offer: &Offer, taker: &Keypair) -> Result<(), BanksClientError> {
+        let accounts = offer.take_accounts(&taker.pubkey(), &self.treasury.pubkey());
+        self.send(&[ix(accounts, TAKE_ANY)], &[taker]).await
+    }
+
+    pub async fn take_partial(
+        &mut self,
+        offer: &Offer,
+        taker: &Keypair,
+        amount_b: u64,
+    ) -> Result<(), BanksClientError> {
+        let accounts = offer.take_accounts(&taker.pubkey(), &self.treasury.pubkey());
+        self.send(&[ix(accounts, take_partial_any(amount_b))], &[taker])
+            .await
+    }
+
+    pub async fn refund(&mut self, offer: &Offer) -> Result<(), BanksClientError> {
+        self.send(
+            &[ix(offer.refund_accounts(), escrow2::instruction::Refund {})],
+            &[&offer.maker],
+        )
+        .await
+    }
+
+    //Creates a funded maker holding `balance` of a new mint for every offered token program, and a new mint for every requested one.
+    pub async fn bundle_offer(
+        &mut self,
+        offered: &[Pubkey],
+        requested: &[Pubkey],
+        balance: u64,
+    ) -> BundleOffer {
+        let maker = self.create_user().await;
+        let mut offered_mints = Vec::new();
+        for token_program in offered {
+            let mint = self.create_mint(token_program).await;
+            self.mint_to(&mint, &maker.pubkey(), token_program, balance)
+                .await;
+            offered_mints.push((mint, *token_program));
+        }
+        let mut requested_mints = Vec::new();
+        for token_program in requested {
+            requested_mints.push((self.create_mint(token_program).await, *token_program));
+        }
+        BundleOffer::new(maker, 1, offered_mints, requested_mints)
+    }
+
+    //Creates a funded taker holding `balance` of every requested mint of the bundle.
+    pub async fn bundle_taker(&mut self, bundle: &BundleOffer, balance: u64) -> Keypair {
+        let taker = self.create_user().await;
+        for (mint, token_program) in &bundle.requested {
+            self.mint_to(mint, &taker.pubkey(), token_program, balance)
+                .await;
+        }
+        taker
+    }
+
+    //Makes the bundle with one deposit per offered mint, asking for `receive` of every requested mint.
+    pub async fn make_bundle(
+        &mut self,
+        bundle: &BundleOffer,
+        deposit: u64,
+        receive: u64,
+    ) -> Result<(), BanksClientError> {
+        let deposits = vec![deposit; bundle.offered.len()];
+        let ix = bundle.make_ix(deposits, bundle.requested_legs(receive), NEVER, None);
+        self.send(&[ix], &[&bundle.maker]).await
+    }
+
+    pub async fn take_bundle(
+        &mut self,
+        bundle: &BundleOffer,
+        taker: &Keypair,
+    ) -> Result<(), BanksClientError> {
+        let ix = bundle.take_ix(&taker.pubkey(), &self.treasury.pubkey());
+        self.send(&[ix], &[taker]).await
+    }
+
+    pub async fn refund_bundle(&mut self, bundle: &BundleOffer) -> Result<(), BanksClientError> {
+        let ix = bundle.refund_ix();
+        self.send(&[ix], &[&bundle.maker]).await
+    }
+}
+
+//A maker and the mints of one of its bundles, each with the token program that owns it.
+pub struct BundleOffer {
+    pub maker: Keypair,
+    pub seed: u64,
+    pub bundle: Pubkey,
+    pub offered: Vec<(Pubkey, Pubkey)>,
+    pub requested: Vec<(Pubkey, Pubkey)>,
+}
+
+impl BundleOffer {
+    pub fn new(
+        maker: Keypair,
+        seed: u64,
+        offered: Vec<(Pubkey, Pubkey)>,
+        requested: Vec<(Pubkey, Pubkey)>,
+    ) -> Self {
+        Self {
+            bundle: bundle_address(&maker.pubkey(), seed),
+            maker,
+            seed,
+            offered,
+            requested,
+        }
+    }
+
+    pub fn maker_key(&self) -> Pubkey {
+        self.maker.pubkey()
+    }
+
+    pub fn vault(&self, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+        ata(&self.bundle, mint, token_program)
+    }
+
+    pub fn requested_legs(&self, amount: u64) -> Vec<BundleLeg> {
+        self.requested
+            .iter()
+            .map(|(mint, _)| BundleLeg {
+                mint: *mint,
+                amount,
+            })
+            .collect()
+    }
+
+    //Mint, token program, vault and the receiver's token account of every offered leg.
+    pub fn offered_metas(&self, receiver: &Pubkey) -> Vec<AccountMeta> {
+        self.offered
+            .iter()
+            .flat_map(|(mint, token_program)| {
+                [
+                    AccountMeta::new(*mint, false),
+                    AccountMeta::new_readonly(*token_program, false),
+                    AccountMeta::new(self.vault(mint, token_program), false),
+                    AccountMeta::new(ata(receiver, mint, token_program), false),
+                ]
+            })
+            .collect()
+    }
+
+    //The offered leg accounts of take_bundle, followed by the maker's token account that receives any surplus.
+    pub fn taken_metas(&self, taker: &Pubkey) -> Vec<AccountMeta> {
+        self.offered
+            .iter()
+            .flat_map(|(mint, token_program)| {
+                [
+                    AccountMeta::new(*mint, false),
+                    AccountMeta::new_readonly(*token_program, false),
+                    AccountMeta::new(self.vault(mint, token_program), false),
+                    AccountMeta::new(ata(taker, mint, token_program), false),
+                    AccountMeta::new(ata(&self.maker_key(), mint, token_program), false),
+                ]
+            })
+            .collect()
+    }
+
+    //Mint, token program, taker, maker and treasury token accounts of every requested leg.
+    pub fn requested_metas(&self, taker: &Pubkey, treasury: &Pubkey) -> Vec<AccountMeta> {
+        self.requested
+            .iter()
+            .flat_map(|(mint, token_program)| {
+                [
+                    AccountMeta::new_readonly(*mint, false),
+                    AccountMeta::new_readonly(*token_program, false),
+                    AccountMeta::new(ata(taker, mint, token_program), false),
+                    AccountMeta::new(ata(&self.maker_key(), mint, token_program), false),
+                    AccountMeta::new(ata(treasury, mint, token_program), false),
+                ]
+            })
+            .collect()
+    }
+
+    pub fn make_ix(
+        &self,
+        deposits: Vec<u64>,
+        requested: Vec<BundleLeg>,
+        expires_at: i64,
+        allowed_taker: Option<Pubkey>,
+    ) -> Instruction {
+        let accounts = escrow2::accounts::MakeBundle {
+            maker: self.maker_key(),
+            bundle: self.bundle,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        };
+        let args = escrow2::instruction::MakeBundle {
+            seed: self.seed,
+            deposits,
+            requested,
+            expires_at,
+            allowed_taker,
+        };
+        let mut ix = ix(accounts, args);
+        ix.accounts.extend(self.offered_metas(&self.maker_key()));
+        ix
+    }
+
+    pub fn take_ix(&self, taker: &Pubkey, treasury: &Pubkey) -> Instruction {
+        let accounts = escrow2::accounts::TakeBundle {
+            taker: *taker,
+            maker: self.maker_key(),
+            config: config_address(),
+            treasury: *treasury,
+            bundle: self.bundle,
+            system_program: system_program::ID,
+            associated_token_program: associated_token::ID,
+        };
+        let mut ix = ix(accounts, escrow2::instruction::TakeBundle {});
+        ix.accounts.extend(self.taken_metas(taker));
+        ix.accounts.extend(self.requested_metas(taker, treasury));
+        ix
+    }
+
+    pub fn refund_ix(&self) -> Instruction {
+        let accounts = escrow2::accounts::RefundBundle {
+            maker: self.maker_key(),
+            bundle: self.bundle,
+            system_program: system_program::ID,
+            associated_token_program: associated_token::ID,
+        };
+        let mut ix = ix(accounts, escrow2::instruction::RefundBundle {});
+        ix.accounts.extend(self.offered_metas(&self.maker_key()));
+        ix
+    }
+
+    pub fn refund_expired_ix(&self, payer: &Pubkey) -> Instruction {
+        let accounts = escrow2::accounts::RefundBundleExpired {
+            payer: *payer,
+            maker: self.maker_key(),
+            bundle: self.bundle,
+            system_program: system_program::ID,
+            associated_token_program: associated_token::ID,
+        };
+        let mut ix = ix(accounts, escrow2::instruction::RefundBundleExpired {});
+        ix.accounts.extend(self.offered_metas(&self.maker_key()));
+        ix
+    }
+}
+
+//A maker and the accounts of one of its offers.
+pub struct Offer {
+    pub maker: Keypair,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub token_program_a: Pubkey,
+    pub token_program_b: Pubkey,
+    pub seed: u64,
+    pub page: u32, //Market page the offer is listed on.
+    pub escrow: Pubkey,
+    pub vault: Pubkey,
+}
+
+impl Offer {
+    pub fn new(
+        maker: Keypair,
+        mint_a: Pubkey,
+        token_program_a: Pubkey,
+        mint_b: Pubkey,
+        token_program_b: Pubkey,
+        seed: u64,
+    ) -> Self {
+        let escrow = escrow_address(&maker.pubkey(), seed);
+        Self {
+            vault: ata(&escrow, &mint_a, &token_program_a),
+            maker,
+            mint_a,
+            mint_b,
+            token_program_a,
+            token_program_b,
+            seed,
+            page: 0,
+            escrow,
+        }
+    }
+
+    pub fn market_page(&self) -> Pubkey {
+        market_page_address(&self.mint_a, &self.mint_b, self.page)
+    }
+
+    pub fn maker_key(&self) -> Pubkey {
+        self.maker.pubkey()
+    }
+
+    pub fn maker_ata_a(&self) -> Pubkey {
+        ata(&self.maker_key(), &self.mint_a, &self.token_program_a)
+    }
+
+    pub fn maker_ata_b(&self) -> Pubkey {
+        ata(&self.maker_key(), &self.mint_b, &self.token_program_b)
+    }
+
+    //Terms of a public fixed price offer of this offer's mints that never expires.
+    pub fn make_args(&self, deposit: u64, receive: u64) -> MakeArgs {
+        MakeArgs {
+            seed: self.seed,
+            deposit,
+            receive,
+            expires_at: NEVER,
+            allowed_taker: None,
+            page: self.page,
+            collection: None,
+            kind: OfferKind::Fixed,
+            vesting: None,
+        }
+    }
+
+    pub fn make_ix(&self, accounts: escrow2::accounts::Make, args: MakeArgs) -> Instruction {
+        ix(accounts, escrow2::instruction::Make { args })
+    }
+
+    pub fn make_accounts(&self) -> escrow2::accounts::Make {
+        escrow2::accounts::Make {
+            maker: self.maker_key(),
+            mint_a: self.mint_a,
+            mint_b: self.mint_b,
+            maker_ata_a: Some(self.maker_ata_a()),
+            maker_state: maker_state_address(&self.maker_key()),
+            maker_stats: stats_address(&self.maker_key()),
+            escrow: self.escrow,
+            vault: self.vault,
+            market_page: self.market_page(),
+            metadata: None,
+            associated_token_program: associated_token::ID,
+            token_program: self.token_program_a,
+            system_program: system_program::ID,
+        }
+    }
+
+    pub fn take_accounts(&self, taker: &Pubkey, treasury: &Pubkey) -> escrow2::accounts::Take {
+        escrow2::accounts::Take {
+            taker: *taker,
+            maker: self.maker_key(),
+            mint_a: self.mint_a,
+            mint_b: self.mint_b,
+            taker_ata_a: ata(taker, &self.mint_a, &self.token_program_a),
+            taker_ata_b: Some(ata(taker, &self.mint_b, &self.token_program_b)),
+            maker_ata_b: Some(self.maker_ata_b()),
+            maker_ata_a: (!is_native(&self.mint_a)).then(|| self.maker_ata_a()), //Surplus wrapped SOL goes back as native SOL.
+            maker_stats: stats_address(&self.maker_key()),
+            taker_stats: stats_address(taker),
+            config: config_address(),
+            treasury: *treasury,
+            treasury_ata_b: Some(ata(treasury, &self.mint_b, &self.token_program_b)),
+            escrow: self.escrow,
+            vault: self.vault,
+            vesting: None,
+            vesting_vault: None,
+            market_page: Some(self.market_page()),
+            metadata: None,
+            price_feed: None,
+            system_program: system_program::ID,
+            token_program_a: self.token_program_a,
+            token_program_b: self.token_program_b,
+            associated_token_program: associated_token::ID,
+        }
+    }
+
+    pub fn refund_accounts(&self) -> escrow2::accounts::Refund {
+        escrow2::accounts::Refund {
+            maker: self.maker_key(),
+            mint_a: self.mint_a,
+            maker_ata_a: Some(self.maker_ata_a()),
+            escrow: self.escrow,
+            vault: self.vault,
+            market_page: Some(self.market_page()),
+            maker_stats: stats_address(&self.maker_key()),
+            system_program: system_program::ID,
+            token_program: self.token_program_a,
+            associated_token_program: associated_token::ID,
+        }
+    }
+
+    pub fn refund_expired_accounts(&self, payer: &Pubkey) -> escrow2::accounts::RefundExpired {
+        escrow2::accounts::RefundExpired {
+            payer: *payer,
+            maker: self.maker_key(),
+            mint_a: self.mint_a,
+            maker_ata_a: Some(self.maker_ata_a()),
+            escrow: self.escrow,
+            vault: self.vault,
+            market_page: Some(self.market_page()),
+            maker_stats: stats_address(&self.maker_key()),
+            system_program: system_program::ID,
+            token_program: self.token_program_a,
+            associated_token_program: associated_token::ID,
+        }
+    }
+
+    pub fn update_accounts(&self) -> escrow2::accounts::Update {
+        escrow2::accounts::Update {
+            maker: self.maker_key(),
+            mint_a: self.mint_a,
+            maker_ata_a: self.maker_ata_a(),
+            escrow: self.escrow,
+            vault: self.vault,
+            system_program: system_program::ID,
+            token_program: self.token_program_a,
+            associated_token_program: associated_token::ID,
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use anchor_lang::{
+    error::ErrorCode, prelude::Pubkey, solana_program::instruction::Instruction, AccountDeserialize,
+};
+use escrow2::{constants::MAX_FEE_BPS, error::EscrowError, state::Config};
+use solana_sdk::signature::{Keypair, Signer};
+
+use crate::common::*;
+
+async fn config(env: &mut Env) -> Config {
+    let account = env.account(&config_address()).await.unwrap();
+    Config::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+fn update_config_ix(
+    admin: &Keypair,
+    new_admin: Pubkey,
+    fee_bps: u16,
+    treasury: Pubkey,
+) -> Instruction {
+    let accounts = escrow2::accounts::UpdateConfig {
+        admin: admin.pubkey(),
+        config: config_address(),
+    };
+    ix(
+        accounts,
+        escrow2::instruction::UpdateConfig {
+            admin: new_admin,
+            fee_bps,
+            treasury,
+        },
+    )
+}
+
+#[tokio::test]
+async fn initialize_config_stores_admin_fee_and_treasury() {
+    let mut env = Env::with_fee(25).await;
+
+    let config = config(&mut env).await;
+    assert_eq!(config.admin, env.payer());
+    assert_eq!(config.fee_bps, 25);
+    assert_eq!(config.treasury, env.treasury.pubkey());
+}
+
+#[tokio::test]
+async fn initialize_config_rejects_fee_above_the_cap() {
+    let mut env = Env::without_config().await;
+
+    let result = env.initialize_config(MAX_FEE_BPS + 1).await;
+    assert_escrow_error(result, EscrowError::InvalidFee);
+    assert!(!env.exists(&config_address()).await);
+}
+
+#[tokio::test]
+async fn initialize_config_is_reserved_to_the_upgrade_authority() {
+    let mut env = Env::without_config().await;
+
+    for upgrade_authority in [Some(Pubkey::new_unique()), None] {
+        env.set_upgrade_authority(upgrade_authority);
+        assert_escrow_error(
+            env.initialize_config(0).await,
+            EscrowError::NotUpgradeAuthority,
+        );
+    }
+    assert!(!env.exists(&config_address()).await);
+}
+
+#[tokio::test]
+async fn update_config_hands_over_admin() {
+    let mut env = Env::new().await;
+    let new_admin = env.create_user().await;
+    let new_treasury = Pubkey::new_unique();
+
+    let admin = Keypair::from_bytes(&env.ctx.payer.to_bytes()).unwrap();
+    let ix = update_config_ix(&admin, new_admin.pubkey(), 100, new_treasury);
+    env.send(&[ix], &[]).await.unwrap();
+
+    let config = config(&mut env).await;
+    assert_eq!(config.admin, new_admin.pubkey());
+    assert_eq!(config.fee_bps, 100);
+    assert_eq!(config.treasury, new_treasury);
+
+    //The previous admin has no say anymore.
+    let ix = update_config_ix(&admin, admin.pubkey(), 0, new_treasury);
+    assert_custom_error(
+        env.send(&[ix], &[]).await,
+        ErrorCode::ConstraintHasOne.into(),
+    );
+}
+
+#[tokio::test]
+async fn update_config_rejects_fee_above_the_cap() {
+    let mut env = Env::new().await;
+
+    let admin = Keypair::from_bytes(&env.ctx.payer.to_bytes()).unwrap();
+    let ix = update_config_ix(
+        &admin,
+        admin.pubkey(),
+        MAX_FEE_BPS + 1,
+        env.treasury.pubkey(),
+    );
+    assert_escrow_error(env.send(&[ix], &[]).await, EscrowError::InvalidFee);
+}
@@ -0,0 +1,323 @@
+use anchor_lang::{error::ErrorCode, prelude::Pubkey, AccountDeserialize};
+use anchor_spl::token::spl_token;
+use escrow2::{
+    error::EscrowError,
+    state::{CounterOffer, MakeArgs, TraderStats, VestingTerms},
+};
+use solana_program_test::BanksClientError;
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    system_program,
+};
+
+use crate::common::*;
+
+fn counter_address(escrow: &Pubkey, proposer: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"counter", escrow.as_ref(), proposer.as_ref()],
+        &escrow2::ID,
+    )
+    .0
+}
+
+//The counter-offer of `proposer` on `offer` and its vault.
+fn counter(offer: &Offer, proposer: &Pubkey) -> (Pubkey, Pubkey) {
+    let counter = counter_address(&offer.escrow, proposer);
+    (
+        counter,
+        ata(&counter, &offer.mint_b, &offer.token_program_b),
+    )
+}
+
+async fn propose(
+    env: &mut Env,
+    offer: &Offer,
+    proposer: &Keypair,
+    receive: u64,
+) -> Result<(), BanksClientError> {
+    let (counter_offer, counter_vault) = counter(offer, &proposer.pubkey());
+    let accounts = escrow2::accounts::ProposeCounter {
+        proposer: proposer.pubkey(),
+        mint_b: offer.mint_b,
+        proposer_ata_b: ata(&proposer.pubkey(), &offer.mint_b, &offer.token_program_b),
+        escrow: offer.escrow,
+        counter_offer,
+        counter_vault,
+        token_program: offer.token_program_b,
+        associated_token_program: anchor_spl::associated_token::ID,
+        system_program: system_program::ID,
+    };
+    let args = escrow2::instruction::CounterOffer { receive };
+    env.send(&[ix(accounts, args)], &[proposer]).await
+}
+
+//Accepts the counter-offer of `proposer`, signed by `signer` as the maker.
+async fn accept(
+    env: &mut Env,
+    offer: &Offer,
+    proposer: &Pubkey,
+    signer: &Keypair,
+) -> Result<(), BanksClientError> {
+    let (counter_offer, counter_vault) = counter(offer, proposer);
+    let treasury = env.treasury.pubkey();
+    let accounts = escrow2::accounts::AcceptCounter {
+        maker: signer.pubkey(),
+        proposer: *proposer,
+        mint_a: offer.mint_a,
+        mint_b: offer.mint_b,
+        proposer_ata_a: ata(proposer, &offer.mint_a, &offer.token_program_a),
+        maker_ata_b: ata(&signer.pubkey(), &offer.mint_b, &offer.token_program_b),
+        maker_ata_a: Some(ata(&signer.pubkey(), &offer.mint_a, &offer.token_program_a)),
+        escrow: offer.escrow,
+        vault: offer.vault,
+        counter_offer,
+        counter_vault,
+        config: config_address(),
+        treasury,
+        treasury_ata_b: Some(ata(&treasury, &offer.mint_b, &offer.token_program_b)),
+        market_page: Some(offer.market_page()),
+        maker_stats: stats_address(&signer.pubkey()),
+        proposer_stats: stats_address(proposer),
+        token_program_a: offer.token_program_a,
+        token_program_b: offer.token_program_b,
+        associated_token_program: anchor_spl::associated_token::ID,
+        system_program: system_program::ID,
+    };
+    env.send(
+        &[ix(accounts, escrow2::instruction::AcceptCounter {})],
+        &[signer],
+    )
+    .await
+}
+
+async fn cancel(env: &mut Env, offer: &Offer, proposer: &Keypair) -> Result<(), BanksClientError> {
+    let (counter_offer, counter_vault) = counter(offer, &proposer.pubkey());
+    let accounts = escrow2::accounts::CancelCounter {
+        proposer: proposer.pubkey(),
+        mint_b: offer.mint_b,
+        proposer_ata_b: ata(&proposer.pubkey(), &offer.mint_b, &offer.token_program_b),
+        counter_offer,
+        counter_vault,
+        token_program: offer.token_program_b,
+        associated_token_program: anchor_spl::associated_token::ID,
+        system_program: system_program::ID,
+    };
+    env.send(
+        &[ix(accounts, escrow2::instruction::CancelCounter {})],
+        &[proposer],
+    )
+    .await
+}
+
+#[tokio::test]
+async fn counter_offer_locks_the_deposit() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 1_000, 500).await.unwrap();
+    let proposer = env.taker(&offer, 500).await;
+
+    propose(&mut env, &offer, &proposer, 400).await.unwrap();
+
+    let (counter_offer, counter_vault) = counter(&offer, &proposer.pubkey());
+    assert_eq!(env.token_balance(&counter_vault).await, 400);
+    let proposer_ata_b = ata(&proposer.pubkey(), &offer.mint_b, &spl_token::ID);
+    assert_eq!(env.token_balance(&proposer_ata_b).await, 100);
+    let account = env.account(&counter_offer).await.unwrap();
+    let stored = CounterOffer::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!(stored.escrow, offer.escrow);
+    assert_eq!(stored.receive, 400);
+    assert_eq!(stored.remaining_a, 1_000);
+
+    let other = env.taker(&offer, 500).await;
+    assert_escrow_error(
+        propose(&mut env, &offer, &other, 0).await,
+        EscrowError::ZeroAmount,
+    );
+}
+
+#[tokio::test]
+async fn accept_counter_settles_both_sides() {
+    let mut env = Env::with_fee(100).await; //1%
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 1_000, 500).await.unwrap();
+    let proposer = env.taker(&offer, 400).await;
+    propose(&mut env, &offer, &proposer, 400).await.unwrap();
+
+    accept(&mut env, &offer, &proposer.pubkey(), &offer.maker)
+        .await
+        .unwrap();
+
+    let proposer_ata_a = ata(&proposer.pubkey(), &offer.mint_a, &spl_token::ID);
+    assert_eq!(env.token_balance(&proposer_ata_a).await, 1_000);
+    assert_eq!(env.token_balance(&offer.maker_ata_b()).await, 396);
+    let treasury_ata = ata(&env.treasury.pubkey(), &offer.mint_b, &spl_token::ID);
+    assert_eq!(env.token_balance(&treasury_ata).await, 4);
+    let (counter_offer, counter_vault) = counter(&offer, &proposer.pubkey());
+    for closed in [offer.escrow, offer.vault, counter_offer, counter_vault] {
+        assert!(!env.exists(&closed).await);
+    }
+    let page = env.account(&offer.market_page()).await.unwrap();
+    let page = escrow2::state::MarketPage::try_deserialize(&mut page.data.as_slice()).unwrap();
+    assert!(page.escrows.is_empty());
+}
+
+#[tokio::test]
+async fn accept_counter_records_the_fill_for_both_parties() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 1_000, 500).await.unwrap();
+    let proposer = env.taker(&offer, 400).await;
+    propose(&mut env, &offer, &proposer, 400).await.unwrap();
+
+    accept(&mut env, &offer, &proposer.pubkey(), &offer.maker)
+        .await
+        .unwrap();
+
+    for (wallet, filled, taken) in [(offer.maker_key(), 1, 0), (proposer.pubkey(), 0, 1)] {
+        let account = env.account(&stats_address(&wallet)).await.unwrap();
+        let stats = TraderStats::try_deserialize(&mut account.data.as_slice()).unwrap();
+        assert_eq!(stats.wallet, wallet);
+        assert_eq!(stats.offers_filled, filled);
+        assert_eq!(stats.fills_taken, taken);
+        let volume: Vec<_> = stats.volume.iter().map(|v| (v.mint, v.amount)).collect();
+        assert_eq!(volume, vec![(offer.mint_a, 1_000), (offer.mint_b, 400)]);
+    }
+}
+
+#[tokio::test]
+async fn accept_counter_returns_surplus_to_the_maker() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 1_000, 500).await.unwrap();
+    let proposer = env.taker(&offer, 400).await;
+    propose(&mut env, &offer, &proposer, 400).await.unwrap();
+    env.donate(&offer, 9).await;
+
+    accept(&mut env, &offer, &proposer.pubkey(), &offer.maker)
+        .await
+        .unwrap();
+
+    let proposer_ata_a = ata(&proposer.pubkey(), &offer.mint_a, &spl_token::ID);
+    assert_eq!(env.token_balance(&proposer_ata_a).await, 1_000);
+    assert_eq!(env.token_balance(&offer.maker_ata_a()).await, 9);
+}
+
+#[tokio::test]
+async fn accept_counter_is_maker_only() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 1_000, 500).await.unwrap();
+    let proposer = env.taker(&offer, 400).await;
+    propose(&mut env, &offer, &proposer, 400).await.unwrap();
+
+    let intruder = env.create_user().await;
+    assert_custom_error(
+        accept(&mut env, &offer, &proposer.pubkey(), &intruder).await,
+        ErrorCode::ConstraintSeeds.into(),
+    ); //The escrow is not derived from the intruder.
+}
+
+#[tokio::test]
+async fn counter_offer_goes_stale_when_the_escrow_changes() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 1_000, 500).await.unwrap();
+    let proposer = env.taker(&offer, 400).await;
+    propose(&mut env, &offer, &proposer, 400).await.unwrap();
+    let taker = env.taker(&offer, 100).await;
+    env.take_partial(&offer, &taker, 100).await.unwrap();
+
+    assert_escrow_error(
+        accept(&mut env, &offer, &proposer.pubkey(), &offer.maker).await,
+        EscrowError::StaleCounterOffer,
+    );
+}
+
+#[tokio::test]
+async fn cancel_counter_works_after_the_escrow_is_gone() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 1_000, 500).await.unwrap();
+    let proposer = env.taker(&offer, 400).await;
+    propose(&mut env, &offer, &proposer, 400).await.unwrap();
+    env.refund(&offer).await.unwrap();
+
+    cancel(&mut env, &offer, &proposer).await.unwrap();
+
+    let proposer_ata_b = ata(&proposer.pubkey(), &offer.mint_b, &spl_token::ID);
+    assert_eq!(env.token_balance(&proposer_ata_b).await, 400);
+    let (counter_offer, counter_vault) = counter(&offer, &proposer.pubkey());
+    assert!(!env.exists(&counter_offer).await);
+    assert!(!env.exists(&counter_vault).await);
+}
+
+#[tokio::test]
+async fn counter_offer_respects_allowed_taker_and_expiry() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    let allowed = env.taker(&offer, 400).await;
+    let other = env.taker(&offer, 400).await;
+    let expires_at = env.now().await + 60;
+    env.make_with(&offer, 1_000, 500, expires_at, Some(allowed.pubkey()))
+        .await
+        .unwrap();
+
+    assert_escrow_error(
+        propose(&mut env, &offer, &other, 400).await,
+        EscrowError::UnauthorizedTaker,
+    );
+
+    env.set_time(expires_at + 1).await;
+    assert_escrow_error(
+        propose(&mut env, &offer, &allowed, 400).await,
+        EscrowError::OfferExpired,
+    );
+}
+
+#[tokio::test]
+async fn counter_offer_rejects_nft_offers() {
+    let mut env = Env::new().await;
+    let maker = env.create_user().await;
+    let nft = env
+        .create_nft(&maker.pubkey(), nft_metadata(500, vec![], None))
+        .await;
+    let mint_b = env.create_mint(&spl_token::ID).await;
+    let offer = Offer::new(maker, nft, spl_token::ID, mint_b, spl_token::ID, 1);
+    let mut accounts = offer.make_accounts();
+    accounts.metadata = Some(metadata_address(&offer.mint_a));
+    let args = offer.make_args(1, 1_000);
+    env.send(&[offer.make_ix(accounts, args)], &[&offer.maker])
+        .await
+        .unwrap();
+    let proposer = env.taker(&offer, 400).await;
+
+    assert_escrow_error(
+        propose(&mut env, &offer, &proposer, 400).await,
+        EscrowError::NftCounterOffer,
+    ); //Accepting would skip the creators' royalties.
+}
+
+#[tokio::test]
+async fn counter_offer_rejects_vested_offers() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    let args = MakeArgs {
+        vesting: Some(VestingTerms {
+            cliff: 0,
+            duration: 1_000,
+        }),
+        ..offer.make_args(1_000, 500)
+    };
+    env.send(
+        &[offer.make_ix(offer.make_accounts(), args)],
+        &[&offer.maker],
+    )
+    .await
+    .unwrap();
+    let proposer = env.taker(&offer, 400).await;
+
+    assert_escrow_error(
+        propose(&mut env, &offer, &proposer, 400).await,
+        EscrowError::VestedCounterOffer,
+    ); //Accepting would skip the vesting schedule.
+}
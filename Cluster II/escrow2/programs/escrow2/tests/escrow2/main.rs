@@ -0,0 +1,24 @@
+//Integration tests for escrow2. Every test starts its own in-process validator with SPL Token, Token-2022 and the
+//associated token program loaded, so the suite runs offline with cargo test, or against the compiled program with cargo test-sbf.
+//
+//CloseVaultFailed is not covered because no instruction can reach it: every vault is emptied and its withheld fees harvested
+//before it is closed, and a frozen vault already fails the transfer that empties it, with the error of that transfer.
+
+mod common;
+
+mod arbiter;
+mod auction;
+mod bundle;
+mod config;
+mod counter;
+mod make;
+mod market;
+mod migrate;
+mod nft;
+mod oracle;
+mod refund;
+mod refund_many;
+mod stats;
+mod take;
+mod update;
+mod vesting;
@@ -0,0 +1,203 @@
+use anchor_lang::{prelude::Pubkey, AccountDeserialize};
+use anchor_spl::{token::spl_token, token_2022::spl_token_2022};
+use escrow2::{error::EscrowError, state::MakerState};
+use solana_sdk::signature::{Keypair, Signer};
+
+use crate::common::*;
+
+#[tokio::test]
+async fn make_moves_deposit_into_vault() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+
+    env.make(&offer, 600, 300).await.unwrap();
+
+    assert_eq!(env.token_balance(&offer.maker_ata_a()).await, 400);
+    assert_eq!(env.token_balance(&offer.vault).await, 600);
+
+    let escrow = env.escrow(&offer.escrow).await;
+    assert_eq!(escrow.maker, offer.maker_key());
+    assert_eq!(escrow.mint_a, offer.mint_a);
+    assert_eq!(escrow.mint_b, offer.mint_b);
+    assert_eq!(escrow.offer_amount, 300);
+    assert_eq!(escrow.seed, offer.seed);
+    assert_eq!(escrow.remaining_a, 600);
+    assert_eq!(escrow.remaining_b, 300);
+    assert_eq!(escrow.expires_at, NEVER);
+    assert_eq!(escrow.allowed_taker, None);
+}
+
+#[tokio::test]
+async fn make_accepts_token_2022_mints() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token_2022::ID, spl_token::ID, 1_000).await;
+
+    env.make(&offer, 1_000, 10).await.unwrap();
+
+    assert_eq!(env.token_balance(&offer.maker_ata_a()).await, 0);
+    assert_eq!(env.token_balance(&offer.vault).await, 1_000);
+    assert_eq!(
+        env.account(&offer.vault).await.unwrap().owner,
+        spl_token_2022::ID
+    );
+}
+
+#[tokio::test]
+async fn make_offers_what_reaches_the_vault_after_transfer_fees() {
+    let mut env = Env::new().await;
+    let mint_a = env.create_fee_mint(100, u64::MAX).await; //1% on every transfer.
+    let mint_b = env.create_mint(&spl_token::ID).await;
+    let offer = env
+        .offer_with_mints(mint_a, spl_token_2022::ID, mint_b, spl_token::ID, 1_000)
+        .await;
+
+    env.make(&offer, 1_000, 10).await.unwrap();
+
+    assert_eq!(env.token_balance(&offer.vault).await, 990);
+    assert_eq!(env.escrow(&offer.escrow).await.remaining_a, 990);
+}
+
+#[tokio::test]
+async fn make_wraps_native_sol() {
+    let mut env = Env::new().await;
+    let mint_b = env.create_mint(&spl_token::ID).await;
+    let offer = env
+        .offer_with_mints(
+            spl_token::native_mint::ID,
+            spl_token::ID,
+            mint_b,
+            spl_token::ID,
+            0,
+        )
+        .await;
+    let before = env.lamports(&offer.maker_key()).await;
+
+    env.make_native(&offer, 1_000_000_000, 50).await.unwrap();
+
+    assert_eq!(env.token_balance(&offer.vault).await, 1_000_000_000);
+    assert!(env.lamports(&offer.maker_key()).await < before - 1_000_000_000);
+    assert!(!env.exists(&offer.maker_ata_a()).await);
+}
+
+#[tokio::test]
+async fn make_rejects_zero_amounts() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+
+    assert_escrow_error(env.make(&offer, 0, 10).await, EscrowError::ZeroAmount);
+    assert_escrow_error(env.make(&offer, 10, 0).await, EscrowError::ZeroAmount);
+    assert!(!env.exists(&offer.escrow).await);
+}
+
+#[tokio::test]
+async fn make_rejects_same_mint() {
+    let mut env = Env::new().await;
+    let mint = env.create_mint(&spl_token::ID).await;
+    let offer = env
+        .offer_with_mints(mint, spl_token::ID, mint, spl_token::ID, 1_000)
+        .await;
+
+    assert_escrow_error(env.make(&offer, 100, 100).await, EscrowError::SameMint);
+}
+
+#[tokio::test]
+async fn make_rejects_deposit_above_balance() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+
+    assert_escrow_error(
+        env.make(&offer, 1_001, 10).await,
+        EscrowError::InsufficientBalance,
+    );
+}
+
+#[tokio::test]
+async fn make_rejects_expiry_in_the_past() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    let now = env.now().await;
+
+    let result = env.make_with(&offer, 100, 10, now, None).await;
+    assert_escrow_error(result, EscrowError::InvalidExpiry);
+}
+
+#[tokio::test]
+async fn make_requires_token_account_for_non_native_mint() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+
+    let result = env.make_native(&offer, 100, 10).await;
+    assert_escrow_error(result, EscrowError::NativeMintRequired);
+}
+
+#[tokio::test]
+async fn make_stores_private_taker_and_expiry() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    let taker = env.create_user().await;
+    let expires_at = env.now().await + 3_600;
+
+    env.make_with(&offer, 100, 10, expires_at, Some(taker.pubkey()))
+        .await
+        .unwrap();
+
+    let escrow = env.escrow(&offer.escrow).await;
+    assert_eq!(escrow.expires_at, expires_at);
+    assert_eq!(escrow.allowed_taker, Some(taker.pubkey()));
+}
+
+//Another offer of the same maker on the same mints.
+fn with_seed(offer: &Offer, seed: u64) -> Offer {
+    Offer::new(
+        Keypair::from_bytes(&offer.maker.to_bytes()).unwrap(),
+        offer.mint_a,
+        offer.token_program_a,
+        offer.mint_b,
+        offer.token_program_b,
+        seed,
+    )
+}
+
+async fn next_seed(env: &mut Env, maker: &Pubkey) -> u64 {
+    let account = env.account(&maker_state_address(maker)).await.unwrap();
+    MakerState::try_deserialize(&mut account.data.as_slice())
+        .unwrap()
+        .next_seed
+}
+
+#[tokio::test]
+async fn make_advances_the_maker_seed() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+
+    env.make(&offer, 100, 10).await.unwrap();
+    assert_eq!(
+        next_seed(&mut env, &offer.maker_key()).await,
+        offer.seed + 1
+    );
+
+    let skipped = with_seed(&offer, 5);
+    env.make(&skipped, 100, 10).await.unwrap();
+    assert_eq!(next_seed(&mut env, &offer.maker_key()).await, 6);
+}
+
+#[tokio::test]
+async fn make_rejects_seeds_below_the_next_one() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 100, 10).await.unwrap();
+
+    assert_escrow_error(
+        env.make(&offer, 100, 10).await,
+        EscrowError::SeedAlreadyUsed,
+    ); //The escrow is still open.
+    env.refund(&offer).await.unwrap();
+    assert_escrow_error(
+        env.make(&offer, 100, 10).await,
+        EscrowError::SeedAlreadyUsed,
+    ); //Closed escrows do not give their seed back.
+    assert_escrow_error(
+        env.make(&with_seed(&offer, 0), 100, 10).await,
+        EscrowError::SeedAlreadyUsed,
+    ); //Nor do skipped seeds.
+}
@@ -0,0 +1,174 @@
+use anchor_lang::{prelude::Pubkey, AccountDeserialize};
+use anchor_spl::token::spl_token;
+use escrow2::{constants::MARKET_PAGE_CAPACITY, error::EscrowError, state::MarketPage};
+use solana_sdk::signature::{Keypair, Signer};
+
+use crate::common::*;
+
+async fn market_page(env: &mut Env, address: &Pubkey) -> MarketPage {
+    let account = env
+        .account(address)
+        .await
+        .expect("market page does not exist");
+    MarketPage::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+//Another offer by the same maker on the same mint pair.
+fn next_offer(offer: &Offer, seed: u64, page: u32) -> Offer {
+    let mut next = Offer::new(
+        Keypair::from_bytes(&offer.maker.to_bytes()).unwrap(),
+        offer.mint_a,
+        offer.token_program_a,
+        offer.mint_b,
+        offer.token_program_b,
+        seed,
+    );
+    next.page = page;
+    next
+}
+
+#[tokio::test]
+async fn make_lists_offers_of_a_mint_pair() {
+    let mut env = Env::new().await;
+    let first = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    let second = env
+        .offer_with_mints(
+            first.mint_a,
+            spl_token::ID,
+            first.mint_b,
+            spl_token::ID,
+            1_000,
+        )
+        .await;
+
+    env.make(&first, 100, 10).await.unwrap();
+    env.make(&second, 200, 20).await.unwrap();
+
+    let page = market_page(&mut env, &first.market_page()).await;
+    assert_eq!(page.mint_a, first.mint_a);
+    assert_eq!(page.mint_b, first.mint_b);
+    assert_eq!(page.page, 0);
+    assert_eq!(page.escrows, vec![first.escrow, second.escrow]);
+    assert_eq!(env.escrow(&first.escrow).await.market_page, Some(0));
+}
+
+#[tokio::test]
+async fn reversed_pair_has_its_own_index() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    let reversed = env
+        .offer_with_mints(
+            offer.mint_b,
+            spl_token::ID,
+            offer.mint_a,
+            spl_token::ID,
+            1_000,
+        )
+        .await;
+
+    env.make(&offer, 100, 10).await.unwrap();
+    env.make(&reversed, 100, 10).await.unwrap();
+
+    assert_ne!(offer.market_page(), reversed.market_page());
+    assert_eq!(
+        market_page(&mut env, &offer.market_page()).await.escrows,
+        vec![offer.escrow]
+    );
+    assert_eq!(
+        market_page(&mut env, &reversed.market_page()).await.escrows,
+        vec![reversed.escrow]
+    );
+}
+
+#[tokio::test]
+async fn take_unlists_filled_offer_only() {
+    let mut env = Env::new().await;
+    let first = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    let second = next_offer(&first, 2, 0);
+    env.make(&first, 500, 100).await.unwrap();
+    env.make(&second, 500, 100).await.unwrap();
+    let taker = env.taker(&first, 200).await;
+
+    env.take_partial(&first, &taker, 50).await.unwrap();
+    let page = market_page(&mut env, &first.market_page()).await;
+    assert_eq!(page.escrows, vec![first.escrow, second.escrow]); //Partially filled offers stay listed.
+
+    env.take_partial(&first, &taker, 50).await.unwrap();
+    let page = market_page(&mut env, &first.market_page()).await;
+    assert_eq!(page.escrows, vec![second.escrow]);
+
+    env.take(&second, &taker).await.unwrap();
+    assert!(market_page(&mut env, &first.market_page())
+        .await
+        .escrows
+        .is_empty());
+}
+
+#[tokio::test]
+async fn refunds_unlist_the_offer() {
+    let mut env = Env::new().await;
+    let first = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    let second = next_offer(&first, 2, 0);
+    env.make(&first, 100, 10).await.unwrap();
+    let expires_at = env.now().await + 60;
+    env.make_with(&second, 100, 10, expires_at, None)
+        .await
+        .unwrap();
+
+    env.refund(&first).await.unwrap();
+    let page = market_page(&mut env, &first.market_page()).await;
+    assert_eq!(page.escrows, vec![second.escrow]);
+
+    env.set_time(expires_at + 1).await;
+    let accounts = second.refund_expired_accounts(&env.payer());
+    env.send(&[ix(accounts, escrow2::instruction::RefundExpired {})], &[])
+        .await
+        .unwrap();
+    assert!(market_page(&mut env, &first.market_page())
+        .await
+        .escrows
+        .is_empty());
+}
+
+#[tokio::test]
+async fn make_rejects_full_page() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    for seed in 1..=MARKET_PAGE_CAPACITY as u64 {
+        env.make(&next_offer(&offer, seed, 0), 10, 10)
+            .await
+            .unwrap();
+    }
+
+    let overflow = next_offer(&offer, 100, 0);
+    assert_escrow_error(
+        env.make(&overflow, 10, 10).await,
+        EscrowError::MarketPageFull,
+    );
+
+    let next_page = next_offer(&offer, 100, 1);
+    env.make(&next_page, 10, 10).await.unwrap();
+    let page = market_page(&mut env, &next_page.market_page()).await;
+    assert_eq!(page.page, 1);
+    assert_eq!(page.escrows, vec![next_page.escrow]);
+}
+
+#[tokio::test]
+async fn take_rejects_another_page() {
+    let mut env = Env::new().await;
+    let first = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    let second = next_offer(&first, 2, 1);
+    env.make(&first, 100, 10).await.unwrap();
+    env.make(&second, 100, 10).await.unwrap();
+    let taker = env.taker(&first, 10).await;
+
+    let mut accounts = first.take_accounts(&taker.pubkey(), &env.treasury.pubkey());
+    accounts.market_page = Some(second.market_page());
+    let result = env.send(&[ix(accounts, TAKE_ANY)], &[&taker]).await;
+    assert_escrow_error(result, EscrowError::MarketPageMismatch);
+
+    let mut accounts = first.take_accounts(&taker.pubkey(), &env.treasury.pubkey());
+    accounts.market_page = None;
+    let result = env.send(&[ix(accounts, TAKE_ANY)], &[&taker]).await;
+    assert_escrow_error(result, EscrowError::MarketPageMismatch);
+}
@@ -0,0 +1,210 @@
+use anchor_lang::{
+    error::ErrorCode,
+    prelude::{Pubkey, Rent},
+    solana_program::{program_option::COption, program_pack::Pack, system_program},
+    AnchorSerialize, Discriminator,
+};
+use anchor_spl::token::spl_token;
+use escrow2::{
+    error::EscrowError,
+    state::{Escrow, LegacyEscrow, LEGACY_ESCROW_LEN},
+};
+use solana_program_test::BanksClientError;
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+};
+
+use crate::common::*;
+
+fn packed_account(data: Vec<u8>, owner: Pubkey) -> Account {
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+fn migrate_ix(
+    env: &Env,
+    maker: Pubkey,
+    seed: u64,
+    escrow: Pubkey,
+    vault: Pubkey,
+    deposit: u64,
+) -> anchor_lang::solana_program::instruction::Instruction {
+    let accounts = escrow2::accounts::Migrate {
+        payer: env.payer(),
+        maker,
+        escrow,
+        vault,
+        system_program: system_program::ID,
+    };
+    ix(accounts, escrow2::instruction::Migrate { seed, deposit })
+}
+
+//An escrow written before the maker was stored, for an offer of 250 mint_b, with `vault_amount` in its vault.
+struct Legacy {
+    env: Env,
+    maker: Keypair,
+    mint_a: Pubkey,
+    mint_b: Pubkey,
+    seed: u64,
+    bump: u8,
+    escrow: Pubkey,
+    vault: Pubkey,
+}
+
+impl Legacy {
+    async fn start(vault_amount: u64) -> Self {
+        let maker = Keypair::new();
+        let mint_a = Pubkey::new_unique();
+        let mint_b = Pubkey::new_unique();
+        let seed: u64 = 7;
+        let (escrow, bump) = Pubkey::find_program_address(
+            &[b"escrow", maker.pubkey().as_ref(), &seed.to_le_bytes()],
+            &escrow2::ID,
+        );
+        let vault = ata(&escrow, &mint_a, &spl_token::ID);
+
+        let mut program_test = program_test();
+        let mut data = Escrow::DISCRIMINATOR.to_vec();
+        LegacyEscrow {
+            mint_a,
+            mint_b,
+            offer_amount: 250,
+            seed,
+            escrow_bump: bump,
+        }
+        .serialize(&mut data)
+        .unwrap();
+        data.resize(LEGACY_ESCROW_LEN, 0); //Legacy escrows were allocated with room for three pubkeys but only stored two.
+        program_test.add_account(escrow, packed_account(data, escrow2::ID));
+
+        let mut data = vec![0; spl_token::state::Mint::LEN];
+        spl_token::state::Mint {
+            mint_authority: COption::None,
+            supply: vault_amount,
+            decimals: DECIMALS,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        }
+        .pack_into_slice(&mut data);
+        program_test.add_account(mint_a, packed_account(data, spl_token::ID));
+
+        let mut data = vec![0; spl_token::state::Account::LEN];
+        spl_token::state::Account {
+            mint: mint_a,
+            owner: escrow,
+            amount: vault_amount,
+            state: spl_token::state::AccountState::Initialized,
+            ..Default::default()
+        }
+        .pack_into_slice(&mut data);
+        program_test.add_account(vault, packed_account(data, spl_token::ID));
+
+        Self {
+            env: Env::start(program_test).await,
+            maker,
+            mint_a,
+            mint_b,
+            seed,
+            bump,
+            escrow,
+            vault,
+        }
+    }
+
+    async fn migrate(&mut self, deposit: u64) -> Result<(), BanksClientError> {
+        let ix = migrate_ix(
+            &self.env,
+            self.maker.pubkey(),
+            self.seed,
+            self.escrow,
+            self.vault,
+            deposit,
+        );
+        self.env.send(&[ix], &[&self.maker]).await
+    }
+}
+
+#[tokio::test]
+async fn migrate_rewrites_legacy_escrow() {
+    let mut legacy = Legacy::start(700).await;
+    legacy.migrate(700).await.unwrap();
+
+    let account = legacy.env.account(&legacy.escrow).await.unwrap();
+    assert_eq!(account.data.len(), Escrow::LEN);
+    assert!(account.lamports >= Rent::default().minimum_balance(Escrow::LEN));
+
+    let migrated = legacy.env.escrow(&legacy.escrow).await;
+    assert_eq!(migrated.maker, legacy.maker.pubkey());
+    assert_eq!(migrated.mint_a, legacy.mint_a);
+    assert_eq!(migrated.mint_b, legacy.mint_b);
+    assert_eq!(migrated.offer_amount, 250);
+    assert_eq!(migrated.seed, legacy.seed);
+    assert_eq!(migrated.escrow_bump, legacy.bump);
+    assert_eq!(migrated.remaining_a, 700);
+    assert_eq!(migrated.remaining_b, 250);
+    assert_eq!(migrated.expires_at, i64::MAX);
+    assert_eq!(migrated.allowed_taker, None);
+    assert_eq!(migrated.market_page, None);
+}
+
+#[tokio::test]
+async fn migrate_does_not_offer_tokens_sent_to_the_vault_after_make() {
+    //The maker deposited 500, the other 200 were sent to the vault afterwards.
+    let mut legacy = Legacy::start(700).await;
+    legacy.migrate(500).await.unwrap();
+
+    let migrated = legacy.env.escrow(&legacy.escrow).await;
+    assert_eq!(migrated.remaining_a, 500);
+    assert_eq!(migrated.remaining_b, 250);
+}
+
+#[tokio::test]
+async fn migrate_rejects_a_deposit_the_vault_does_not_hold() {
+    let mut legacy = Legacy::start(700).await;
+    assert_escrow_error(legacy.migrate(701).await, EscrowError::DepositNotInVault);
+    assert_escrow_error(legacy.migrate(0).await, EscrowError::ZeroAmount);
+}
+
+#[tokio::test]
+async fn migrate_requires_the_maker_signature() {
+    let mut legacy = Legacy::start(700).await;
+    let mut ix = migrate_ix(
+        &legacy.env,
+        legacy.maker.pubkey(),
+        legacy.seed,
+        legacy.escrow,
+        legacy.vault,
+        700,
+    );
+    ix.accounts[1].is_signer = false; //Anyone else could otherwise state a smaller deposit for the maker.
+    assert_custom_error(
+        legacy.env.send(&[ix], &[]).await,
+        ErrorCode::AccountNotSigner.into(),
+    );
+}
+
+#[tokio::test]
+async fn migrate_rejects_current_escrow() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 600, 300).await.unwrap();
+
+    let ix = migrate_ix(
+        &env,
+        offer.maker_key(),
+        offer.seed,
+        offer.escrow,
+        offer.vault,
+        600,
+    );
+    assert_escrow_error(
+        env.send(&[ix], &[&offer.maker]).await,
+        EscrowError::NotLegacyEscrow,
+    );
+}
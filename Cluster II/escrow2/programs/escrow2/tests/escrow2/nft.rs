@@ -0,0 +1,278 @@
+use anchor_lang::{error::ErrorCode, prelude::Pubkey};
+use anchor_spl::token::spl_token;
+use escrow2::{
+    constants::METADATA_V1_KEY,
+    error::EscrowError,
+    state::{MakeArgs, MetadataCollection, MetadataCreator, TokenMetadata},
+};
+use solana_program_test::BanksClientError;
+use solana_sdk::{
+    instruction::AccountMeta,
+    signature::{Keypair, Signer},
+};
+
+use crate::common::*;
+
+fn creator(share: u8) -> MetadataCreator {
+    MetadataCreator {
+        address: Keypair::new().pubkey(),
+        verified: true,
+        share,
+    }
+}
+
+//A maker holding a new NFT described by `metadata`, offered for a new SPL Token mint.
+async fn nft_offer(env: &mut Env, metadata: TokenMetadata) -> Offer {
+    let maker = env.create_user().await;
+    let nft = env.create_nft(&maker.pubkey(), metadata).await;
+    let mint_b = env.create_mint(&spl_token::ID).await;
+    Offer::new(maker, nft, spl_token::ID, mint_b, spl_token::ID, 1)
+}
+
+async fn make_nft(
+    env: &mut Env,
+    offer: &Offer,
+    receive: u64,
+    collection: Option<Pubkey>,
+) -> Result<(), BanksClientError> {
+    let mut accounts = offer.make_accounts();
+    accounts.metadata = Some(metadata_address(&offer.mint_a));
+    let args = MakeArgs {
+        collection,
+        ..offer.make_args(1, receive)
+    };
+    env.send(&[offer.make_ix(accounts, args)], &[&offer.maker])
+        .await
+}
+
+//Takes an NFT offer paying `creators`, given as the remaining accounts of each creator.
+async fn take_nft(
+    env: &mut Env,
+    offer: &Offer,
+    taker: &Keypair,
+    creators: &[Pubkey],
+) -> Result<(), BanksClientError> {
+    let mut accounts = offer.take_accounts(&taker.pubkey(), &env.treasury.pubkey());
+    accounts.metadata = Some(metadata_address(&offer.mint_a));
+    let mut take = ix(accounts, TAKE_ANY);
+    for creator in creators {
+        take.accounts.push(AccountMeta::new(*creator, false));
+        take.accounts.push(AccountMeta::new(
+            ata(creator, &offer.mint_b, &offer.token_program_b),
+            false,
+        ));
+    }
+    env.send(&[take], &[taker]).await
+}
+
+#[tokio::test]
+async fn make_nft_lists_an_nft_offer() {
+    let mut env = Env::new().await;
+    let offer = nft_offer(&mut env, nft_metadata(500, vec![creator(100)], None)).await;
+
+    make_nft(&mut env, &offer, 1_000, None).await.unwrap();
+
+    let escrow = env.escrow(&offer.escrow).await;
+    assert!(escrow.nft);
+    assert_eq!(escrow.remaining_a, 1);
+    assert_eq!(env.token_balance(&offer.vault).await, 1);
+}
+
+#[tokio::test]
+async fn make_nft_rejects_fungible_mint() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.set_metadata(&offer.mint_a, nft_metadata(0, vec![], None))
+        .await;
+
+    assert_escrow_error(
+        make_nft(&mut env, &offer, 1_000, None).await,
+        EscrowError::NotAnNft,
+    );
+}
+
+#[tokio::test]
+async fn make_nft_checks_collection() {
+    let mut env = Env::new().await;
+    let collection = Pubkey::new_unique();
+    let verified = Some(MetadataCollection {
+        verified: true,
+        key: collection,
+    });
+    let unverified = Some(MetadataCollection {
+        verified: false,
+        key: collection,
+    });
+
+    let offer = nft_offer(&mut env, nft_metadata(0, vec![], verified)).await;
+    assert_escrow_error(
+        make_nft(&mut env, &offer, 1_000, Some(Pubkey::new_unique())).await,
+        EscrowError::CollectionMismatch,
+    );
+    make_nft(&mut env, &offer, 1_000, Some(collection))
+        .await
+        .unwrap();
+
+    let offer = nft_offer(&mut env, nft_metadata(0, vec![], unverified)).await;
+    assert_escrow_error(
+        make_nft(&mut env, &offer, 1_000, Some(collection)).await,
+        EscrowError::CollectionMismatch,
+    );
+}
+
+#[tokio::test]
+async fn make_rejects_collection_without_metadata() {
+    let mut env = Env::new().await;
+    let offer = nft_offer(&mut env, nft_metadata(0, vec![], None)).await;
+
+    let args = MakeArgs {
+        collection: Some(Pubkey::new_unique()),
+        ..offer.make_args(1, 1_000)
+    };
+    let result = env
+        .send(
+            &[offer.make_ix(offer.make_accounts(), args)],
+            &[&offer.maker],
+        )
+        .await;
+    assert_escrow_error(result, EscrowError::NftMetadataRequired);
+}
+
+#[tokio::test]
+async fn make_nft_rejects_invalid_metadata() {
+    let mut env = Env::new().await;
+    let offer = nft_offer(&mut env, nft_metadata(0, vec![], None)).await;
+    let address = metadata_address(&offer.mint_a);
+    let valid = env.account(&address).await.unwrap();
+
+    let mut truncated = valid.clone();
+    truncated.data.truncate(40);
+    let other = nft_offer(&mut env, nft_metadata(0, vec![], None)).await;
+    let other_mint = env.account(&metadata_address(&other.mint_a)).await.unwrap();
+    for metadata in [truncated, other_mint] {
+        env.ctx.set_account(&address, &metadata.into());
+        assert_escrow_error(
+            make_nft(&mut env, &offer, 1_000, None).await,
+            EscrowError::InvalidMetadata,
+        );
+    }
+
+    let not_metadata = TokenMetadata {
+        key: METADATA_V1_KEY + 1,
+        ..nft_metadata(0, vec![], None)
+    };
+    env.set_metadata(&offer.mint_a, not_metadata).await;
+    assert_escrow_error(
+        make_nft(&mut env, &offer, 1_000, None).await,
+        EscrowError::InvalidMetadata,
+    );
+}
+
+#[tokio::test]
+async fn make_nft_rejects_metadata_not_owned_by_metaplex() {
+    let mut env = Env::new().await;
+    let offer = nft_offer(&mut env, nft_metadata(0, vec![], None)).await;
+    let mut fake = env.account(&metadata_address(&offer.mint_a)).await.unwrap();
+    fake.owner = escrow2::ID;
+    env.ctx
+        .set_account(&metadata_address(&offer.mint_a), &fake.into());
+
+    assert_custom_error(
+        make_nft(&mut env, &offer, 1_000, None).await,
+        ErrorCode::ConstraintOwner.into(),
+    );
+}
+
+#[tokio::test]
+async fn take_nft_pays_royalties_to_creators() {
+    let mut env = Env::with_fee(100).await; //1%
+    let unpaid = creator(0);
+    let first = creator(70);
+    let second = creator(30);
+    let metadata = nft_metadata(500, vec![unpaid, first, second], None); //5%
+    let offer = nft_offer(&mut env, metadata).await;
+    make_nft(&mut env, &offer, 1_000, None).await.unwrap();
+    let taker = env.taker(&offer, 1_000).await;
+
+    take_nft(&mut env, &offer, &taker, &[first.address, second.address])
+        .await
+        .unwrap();
+
+    let royalty = |creator: &MetadataCreator| ata(&creator.address, &offer.mint_b, &spl_token::ID);
+    assert_eq!(env.token_balance(&royalty(&first)).await, 35);
+    assert_eq!(env.token_balance(&royalty(&second)).await, 15);
+    assert!(!env.exists(&royalty(&unpaid)).await); //Creators without a share are left out.
+    let treasury_ata = ata(&env.treasury.pubkey(), &offer.mint_b, &spl_token::ID);
+    assert_eq!(env.token_balance(&treasury_ata).await, 10);
+    assert_eq!(env.token_balance(&offer.maker_ata_b()).await, 940);
+    let taker_nft = ata(&taker.pubkey(), &offer.mint_a, &spl_token::ID);
+    assert_eq!(env.token_balance(&taker_nft).await, 1);
+}
+
+#[tokio::test]
+async fn take_nft_pays_native_royalties_to_wallets() {
+    let mut env = Env::new().await;
+    let first = creator(60);
+    let second = creator(40);
+    let maker = env.create_user().await;
+    let nft = env
+        .create_nft(
+            &maker.pubkey(),
+            nft_metadata(1_000, vec![first, second], None),
+        )
+        .await; //10%
+    let offer = Offer::new(
+        maker,
+        nft,
+        spl_token::ID,
+        spl_token::native_mint::ID,
+        spl_token::ID,
+        1,
+    );
+    make_nft(&mut env, &offer, 1_000_000_000, None)
+        .await
+        .unwrap();
+    let taker = env.create_user().await;
+    let maker_lamports = env.lamports(&offer.maker_key()).await;
+    let escrow_rent = env.lamports(&offer.escrow).await;
+
+    let mut accounts = offer.take_accounts(&taker.pubkey(), &env.treasury.pubkey());
+    accounts.taker_ata_b = None;
+    accounts.maker_ata_b = None;
+    accounts.treasury_ata_b = None;
+    accounts.metadata = Some(metadata_address(&offer.mint_a));
+    let mut take = ix(accounts, TAKE_ANY);
+    take.accounts.push(AccountMeta::new(first.address, false));
+    take.accounts.push(AccountMeta::new(second.address, false));
+    env.send(&[take], &[&taker]).await.unwrap();
+
+    assert_eq!(env.lamports(&first.address).await, 60_000_000);
+    assert_eq!(env.lamports(&second.address).await, 40_000_000);
+    assert_eq!(
+        env.lamports(&offer.maker_key()).await,
+        maker_lamports + 900_000_000 + escrow_rent
+    );
+}
+
+#[tokio::test]
+async fn take_nft_rejects_missing_or_wrong_creators() {
+    let mut env = Env::new().await;
+    let first = creator(50);
+    let second = creator(50);
+    let offer = nft_offer(&mut env, nft_metadata(500, vec![first, second], None)).await;
+    make_nft(&mut env, &offer, 1_000, None).await.unwrap();
+    let taker = env.taker(&offer, 1_000).await;
+
+    assert_escrow_error(
+        take_nft(&mut env, &offer, &taker, &[first.address]).await,
+        EscrowError::RoyaltyAccountsMismatch,
+    );
+    assert_escrow_error(
+        take_nft(&mut env, &offer, &taker, &[second.address, first.address]).await,
+        EscrowError::RoyaltyAccountsMismatch,
+    );
+    assert_escrow_error(
+        env.take(&offer, &taker).await,
+        EscrowError::NftMetadataRequired,
+    ); //Leaving the metadata out does not skip the royalty.
+}
@@ -0,0 +1,208 @@
+use anchor_lang::{error::ErrorCode, prelude::Pubkey};
+use anchor_spl::token::spl_token;
+use escrow2::{
+    error::EscrowError,
+    state::{MakeArgs, OfferKind, OraclePeg},
+};
+use solana_program_test::BanksClientError;
+use solana_sdk::signature::{Keypair, Signer};
+
+use crate::common::*;
+
+//2.50 mint_b per mint_a, both mints have DECIMALS.
+const PRICE: i64 = 250;
+const EXPONENT: i32 = -2;
+
+fn peg(price_feed: Pubkey) -> OraclePeg {
+    OraclePeg {
+        price_feed,
+        spread_bps: 100, //1% above the feed.
+        max_staleness: 60,
+        max_confidence_bps: 100,
+    }
+}
+
+//A maker holding 1_000_000 of mint_a and a fresh price feed published now.
+async fn pegged_offer(env: &mut Env) -> (Offer, Pubkey) {
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000_000).await;
+    let feed = Pubkey::new_unique();
+    let now = env.now().await;
+    env.set_price_feed(&feed, PRICE, 1, EXPONENT, now).await;
+    (offer, feed)
+}
+
+async fn make_pegged(
+    env: &mut Env,
+    offer: &Offer,
+    oracle: OraclePeg,
+) -> Result<(), BanksClientError> {
+    let args = MakeArgs {
+        kind: OfferKind::Pegged(oracle),
+        ..offer.make_args(1_000_000, 1) //Only the starting ask, takes use the feed.
+    };
+    env.send(
+        &[offer.make_ix(offer.make_accounts(), args)],
+        &[&offer.maker],
+    )
+    .await
+}
+
+//Takes the whole offer, or amount_b of it, passing `price_feed`.
+async fn take_pegged(
+    env: &mut Env,
+    offer: &Offer,
+    taker: &Keypair,
+    price_feed: Option<Pubkey>,
+    amount_b: Option<u64>,
+) -> Result<(), BanksClientError> {
+    let mut accounts = offer.take_accounts(&taker.pubkey(), &env.treasury.pubkey());
+    accounts.price_feed = price_feed;
+    let take = match amount_b {
+        Some(amount_b) => ix(accounts, take_partial_any(amount_b)),
+        None => ix(accounts, TAKE_ANY),
+    };
+    env.send(&[take], &[taker]).await
+}
+
+#[tokio::test]
+async fn take_pays_the_feed_price_with_spread() {
+    let mut env = Env::new().await;
+    let (offer, feed) = pegged_offer(&mut env).await;
+    make_pegged(&mut env, &offer, peg(feed)).await.unwrap();
+    assert_eq!(env.escrow(&offer.escrow).await.oracle, Some(peg(feed)));
+    let taker = env.taker(&offer, 5_000_000).await;
+
+    take_pegged(&mut env, &offer, &taker, Some(feed), None)
+        .await
+        .unwrap();
+
+    assert_eq!(env.token_balance(&offer.maker_ata_b()).await, 2_525_000);
+    let taker_ata_a = ata(&taker.pubkey(), &offer.mint_a, &spl_token::ID);
+    assert_eq!(env.token_balance(&taker_ata_a).await, 1_000_000);
+}
+
+#[tokio::test]
+async fn take_follows_the_feed_between_fills() {
+    let mut env = Env::new().await;
+    let (offer, feed) = pegged_offer(&mut env).await;
+    make_pegged(&mut env, &offer, peg(feed)).await.unwrap();
+    let taker = env.taker(&offer, 5_000_000).await;
+
+    take_pegged(&mut env, &offer, &taker, Some(feed), Some(1_262_500))
+        .await
+        .unwrap(); //Half of the offer at 2.525.
+    let escrow = env.escrow(&offer.escrow).await;
+    assert_eq!(escrow.remaining_a, 500_000);
+
+    let now = env.now().await;
+    env.set_price_feed(&feed, 300, 1, EXPONENT, now).await;
+    take_pegged(&mut env, &offer, &taker, Some(feed), None)
+        .await
+        .unwrap(); //The other half at 3.03.
+
+    assert_eq!(
+        env.token_balance(&offer.maker_ata_b()).await,
+        1_262_500 + 1_515_000
+    );
+    assert!(!env.exists(&offer.escrow).await);
+}
+
+#[tokio::test]
+async fn take_rejects_stale_or_uncertain_prices() {
+    let mut env = Env::new().await;
+    let (offer, feed) = pegged_offer(&mut env).await;
+    make_pegged(&mut env, &offer, peg(feed)).await.unwrap();
+    let taker = env.taker(&offer, 5_000_000).await;
+    let now = env.now().await;
+
+    env.set_price_feed(&feed, PRICE, 1, EXPONENT, now - 61)
+        .await;
+    assert_escrow_error(
+        take_pegged(&mut env, &offer, &taker, Some(feed), None).await,
+        EscrowError::StalePrice,
+    );
+
+    env.set_price_feed(&feed, PRICE, 3, EXPONENT, now).await; //1.2% of the price.
+    assert_escrow_error(
+        take_pegged(&mut env, &offer, &taker, Some(feed), None).await,
+        EscrowError::PriceConfidenceTooWide,
+    );
+
+    env.set_price_feed(&feed, 0, 0, EXPONENT, now).await;
+    assert_escrow_error(
+        take_pegged(&mut env, &offer, &taker, Some(feed), None).await,
+        EscrowError::InvalidPriceFeed,
+    );
+}
+
+#[tokio::test]
+async fn take_rejects_missing_or_foreign_feeds() {
+    let mut env = Env::new().await;
+    let (offer, feed) = pegged_offer(&mut env).await;
+    make_pegged(&mut env, &offer, peg(feed)).await.unwrap();
+    let taker = env.taker(&offer, 5_000_000).await;
+
+    assert_escrow_error(
+        take_pegged(&mut env, &offer, &taker, None, None).await,
+        EscrowError::PriceFeedMismatch,
+    );
+
+    let other = Pubkey::new_unique();
+    let now = env.now().await;
+    env.set_price_feed(&other, 1, 0, EXPONENT, now).await;
+    assert_escrow_error(
+        take_pegged(&mut env, &offer, &taker, Some(other), None).await,
+        EscrowError::PriceFeedMismatch,
+    );
+
+    let mut fake = env.account(&feed).await.unwrap();
+    fake.owner = escrow2::ID;
+    env.ctx.set_account(&feed, &fake.into());
+    assert_custom_error(
+        take_pegged(&mut env, &offer, &taker, Some(feed), None).await,
+        ErrorCode::ConstraintOwner.into(),
+    );
+}
+
+#[tokio::test]
+async fn make_rejects_invalid_oracle_terms() {
+    let mut env = Env::new().await;
+    let (offer, feed) = pegged_offer(&mut env).await;
+
+    for oracle in [
+        OraclePeg {
+            spread_bps: -10_000,
+            ..peg(feed)
+        },
+        OraclePeg {
+            max_staleness: 0,
+            ..peg(feed)
+        },
+        OraclePeg {
+            max_confidence_bps: 10_001,
+            ..peg(feed)
+        },
+    ] {
+        assert_escrow_error(
+            make_pegged(&mut env, &offer, oracle).await,
+            EscrowError::InvalidOracleTerms,
+        );
+    }
+}
+
+#[tokio::test]
+async fn update_rejects_pegged_offers() {
+    let mut env = Env::new().await;
+    let (offer, feed) = pegged_offer(&mut env).await;
+    make_pegged(&mut env, &offer, peg(feed)).await.unwrap();
+
+    let update = escrow2::instruction::Update {
+        receive: 1,
+        deposit: 500_000,
+    };
+    assert_escrow_error(
+        env.send(&[ix(offer.update_accounts(), update)], &[&offer.maker])
+            .await,
+        EscrowError::PricedOfferUpdate,
+    ); //Every take asks the feed price, so a new receive would never apply.
+}
@@ -0,0 +1,169 @@
+use anchor_lang::error::ErrorCode;
+use anchor_spl::token::spl_token;
+use escrow2::error::EscrowError;
+use solana_sdk::signature::Signer;
+
+use crate::common::*;
+
+#[tokio::test]
+async fn refund_returns_deposit_and_closes_escrow() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 600, 300).await.unwrap();
+    let rent = env.lamports(&offer.escrow).await + env.lamports(&offer.vault).await;
+    let maker_lamports = env.lamports(&offer.maker_key()).await;
+
+    env.refund(&offer).await.unwrap();
+
+    assert_eq!(env.token_balance(&offer.maker_ata_a()).await, 1_000);
+    assert!(!env.exists(&offer.vault).await);
+    assert!(!env.exists(&offer.escrow).await);
+    assert_eq!(
+        env.lamports(&offer.maker_key()).await,
+        maker_lamports + rent
+    );
+}
+
+#[tokio::test]
+async fn refund_returns_tokens_sent_to_the_vault() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 600, 300).await.unwrap();
+    env.donate(&offer, 7).await;
+
+    env.refund(&offer).await.unwrap();
+
+    assert_eq!(env.token_balance(&offer.maker_ata_a()).await, 1_000 + 7);
+    assert!(!env.exists(&offer.vault).await);
+}
+
+#[tokio::test]
+async fn refund_after_partial_fill_returns_what_is_left() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 1_000, 100).await.unwrap();
+    let taker = env.taker(&offer, 100).await;
+    env.take_partial(&offer, &taker, 40).await.unwrap();
+
+    env.refund(&offer).await.unwrap();
+
+    assert_eq!(env.token_balance(&offer.maker_ata_a()).await, 600);
+    assert!(!env.exists(&offer.escrow).await);
+}
+
+#[tokio::test]
+async fn refund_unwraps_native_sol() {
+    let mut env = Env::new().await;
+    let mint_b = env.create_mint(&spl_token::ID).await;
+    let offer = env
+        .offer_with_mints(
+            spl_token::native_mint::ID,
+            spl_token::ID,
+            mint_b,
+            spl_token::ID,
+            0,
+        )
+        .await;
+    env.make_native(&offer, 1_000_000_000, 50).await.unwrap();
+    let locked = env.lamports(&offer.escrow).await + env.lamports(&offer.vault).await;
+    let maker_lamports = env.lamports(&offer.maker_key()).await;
+
+    let mut accounts = offer.refund_accounts();
+    accounts.maker_ata_a = None;
+    env.send(
+        &[ix(accounts, escrow2::instruction::Refund {})],
+        &[&offer.maker],
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        env.lamports(&offer.maker_key()).await,
+        maker_lamports + locked
+    );
+    assert!(!env.exists(&offer.maker_ata_a()).await);
+    assert!(!env.exists(&offer.escrow).await);
+}
+
+#[tokio::test]
+async fn refund_requires_the_maker() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 600, 300).await.unwrap();
+    let other = env.create_user().await;
+
+    let mut accounts = offer.refund_accounts();
+    accounts.maker = other.pubkey();
+    accounts.maker_ata_a = Some(ata(&other.pubkey(), &offer.mint_a, &offer.token_program_a));
+    let result = env
+        .send(&[ix(accounts, escrow2::instruction::Refund {})], &[&other])
+        .await;
+    assert_custom_error(result, ErrorCode::ConstraintSeeds.into());
+}
+
+#[tokio::test]
+async fn refund_fails_when_maker_account_is_frozen() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 600, 300).await.unwrap();
+    env.freeze(&offer.maker_ata_a(), &offer.mint_a, &offer.token_program_a)
+        .await;
+
+    assert_escrow_error(env.refund(&offer).await, EscrowError::RefundFailed);
+    assert_eq!(env.token_balance(&offer.vault).await, 600);
+}
+
+#[tokio::test]
+async fn refund_requires_token_account_for_non_native_mint() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 600, 300).await.unwrap();
+
+    let mut accounts = offer.refund_accounts();
+    accounts.maker_ata_a = None;
+    let result = env
+        .send(
+            &[ix(accounts, escrow2::instruction::Refund {})],
+            &[&offer.maker],
+        )
+        .await;
+    assert_escrow_error(result, EscrowError::NativeMintRequired);
+}
+
+#[tokio::test]
+async fn refund_expired_can_be_sent_by_anyone_after_expiry() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    let expires_at = env.now().await + 60;
+    env.make_with(&offer, 600, 300, expires_at, None)
+        .await
+        .unwrap();
+    let crank = env.create_user().await;
+    let rent = env.lamports(&offer.escrow).await + env.lamports(&offer.vault).await;
+    let maker_lamports = env.lamports(&offer.maker_key()).await;
+
+    let accounts = offer.refund_expired_accounts(&crank.pubkey());
+    let result = env
+        .send(
+            &[ix(accounts, escrow2::instruction::RefundExpired {})],
+            &[&crank],
+        )
+        .await;
+    assert_escrow_error(result, EscrowError::OfferNotExpired);
+
+    env.set_time(expires_at + 1).await;
+    let accounts = offer.refund_expired_accounts(&crank.pubkey());
+    env.send(
+        &[ix(accounts, escrow2::instruction::RefundExpired {})],
+        &[&crank],
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(env.token_balance(&offer.maker_ata_a()).await, 1_000);
+    assert!(!env.exists(&offer.escrow).await);
+    assert_eq!(
+        env.lamports(&offer.maker_key()).await,
+        maker_lamports + rent
+    ); //Rent goes to the maker, not the crank.
+}
@@ -0,0 +1,133 @@
+use anchor_lang::{prelude::Pubkey, AccountDeserialize};
+use anchor_spl::{token::spl_token, token_2022::spl_token_2022};
+use escrow2::{error::EscrowError, state::MarketPage};
+use solana_program_test::BanksClientError;
+use solana_sdk::{
+    instruction::AccountMeta,
+    signature::{Keypair, Signer},
+    system_program,
+};
+
+use crate::common::*;
+
+//Another offer by the same maker, `mint_a` owned by `token_program_a`.
+fn same_maker(offer: &Offer, mint_a: Pubkey, token_program_a: Pubkey, seed: u64) -> Offer {
+    Offer::new(
+        Keypair::from_bytes(&offer.maker.to_bytes()).unwrap(),
+        mint_a,
+        token_program_a,
+        offer.mint_b,
+        offer.token_program_b,
+        seed,
+    )
+}
+
+//Sends refund_many for `offers`, signed by `maker`.
+async fn refund_many(
+    env: &mut Env,
+    maker: &Keypair,
+    offers: &[&Offer],
+) -> Result<(), BanksClientError> {
+    let accounts = escrow2::accounts::RefundMany {
+        maker: maker.pubkey(),
+        maker_stats: stats_address(&maker.pubkey()),
+        system_program: system_program::ID,
+        associated_token_program: anchor_spl::associated_token::ID,
+    };
+    let mut refund = ix(accounts, escrow2::instruction::RefundMany {});
+    for offer in offers {
+        refund.accounts.extend([
+            AccountMeta::new(offer.escrow, false),
+            AccountMeta::new(offer.vault, false),
+            AccountMeta::new(offer.mint_a, false),
+            AccountMeta::new_readonly(offer.token_program_a, false),
+            AccountMeta::new(
+                ata(&maker.pubkey(), &offer.mint_a, &offer.token_program_a),
+                false,
+            ),
+            AccountMeta::new(offer.market_page(), false),
+        ]);
+    }
+    env.send(&[refund], &[maker]).await
+}
+
+#[tokio::test]
+async fn refund_many_refunds_every_escrow() {
+    let mut env = Env::new().await;
+    let first = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    let second = same_maker(&first, first.mint_a, spl_token::ID, 2); //Listed on the same market page.
+    let mint_2022 = env.create_mint(&spl_token_2022::ID).await;
+    env.mint_to(&mint_2022, &first.maker_key(), &spl_token_2022::ID, 500)
+        .await;
+    let third = same_maker(&first, mint_2022, spl_token_2022::ID, 3);
+    env.make(&first, 600, 60).await.unwrap();
+    env.make(&second, 400, 40).await.unwrap();
+    env.make(&third, 500, 50).await.unwrap();
+
+    refund_many(&mut env, &first.maker, &[&first, &second, &third])
+        .await
+        .unwrap();
+
+    assert_eq!(env.token_balance(&first.maker_ata_a()).await, 1_000);
+    assert_eq!(env.token_balance(&third.maker_ata_a()).await, 500);
+    for offer in [&first, &second, &third] {
+        assert!(!env.exists(&offer.escrow).await);
+        assert!(!env.exists(&offer.vault).await);
+    }
+    let page = env.account(&first.market_page()).await.unwrap();
+    let page = MarketPage::try_deserialize(&mut page.data.as_slice()).unwrap();
+    assert!(page.escrows.is_empty());
+}
+
+#[tokio::test]
+async fn refund_many_skips_escrows_already_closed() {
+    let mut env = Env::new().await;
+    let first = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    let second = same_maker(&first, first.mint_a, spl_token::ID, 2);
+    env.make(&first, 600, 60).await.unwrap();
+    env.make(&second, 400, 40).await.unwrap();
+    let taker = env.taker(&first, 60).await;
+    env.take(&first, &taker).await.unwrap();
+
+    refund_many(&mut env, &first.maker, &[&first, &second])
+        .await
+        .unwrap();
+
+    assert_eq!(env.token_balance(&second.maker_ata_a()).await, 400);
+    assert!(!env.exists(&second.escrow).await);
+}
+
+#[tokio::test]
+async fn refund_many_rejects_escrows_of_other_makers() {
+    let mut env = Env::new().await;
+    let own = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    let other = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&own, 600, 60).await.unwrap();
+    env.make(&other, 600, 60).await.unwrap();
+
+    assert_escrow_error(
+        refund_many(&mut env, &own.maker, &[&own, &other]).await,
+        EscrowError::RefundAccountsMismatch,
+    );
+    assert_escrow_error(
+        refund_many(&mut env, &own.maker, &[]).await,
+        EscrowError::RefundAccountsMismatch,
+    );
+    assert_eq!(env.token_balance(&own.vault).await, 600); //Nothing is refunded when one escrow fails.
+}
+
+#[tokio::test]
+async fn refund_many_checks_the_market_page() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    let unrelated = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 600, 60).await.unwrap();
+    env.make(&unrelated, 600, 60).await.unwrap();
+
+    let mut wrong_page = same_maker(&offer, offer.mint_a, spl_token::ID, offer.seed);
+    wrong_page.mint_b = unrelated.mint_b; //Points the market page slot at another pair's page.
+    assert_escrow_error(
+        refund_many(&mut env, &offer.maker, &[&wrong_page]).await,
+        EscrowError::MarketPageMismatch,
+    );
+}
@@ -0,0 +1,125 @@
+use anchor_lang::{prelude::Pubkey, AccountDeserialize};
+use anchor_spl::token::spl_token;
+use escrow2::state::{MintVolume, TraderStats};
+use solana_sdk::signature::{Keypair, Signer};
+
+use crate::common::*;
+
+async fn stats(env: &mut Env, wallet: &Pubkey) -> TraderStats {
+    let account = env.account(&stats_address(wallet)).await.unwrap();
+    TraderStats::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+fn volume(stats: &TraderStats, mint: &Pubkey) -> u64 {
+    stats
+        .volume
+        .iter()
+        .find(|volume| volume.mint == *mint)
+        .map_or(0, |volume| volume.amount)
+}
+
+#[tokio::test]
+async fn make_take_and_refund_update_the_maker_stats() {
+    let mut env = Env::new().await;
+    let first = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    let second = Offer::new(
+        Keypair::from_bytes(&first.maker.to_bytes()).unwrap(),
+        first.mint_a,
+        spl_token::ID,
+        first.mint_b,
+        spl_token::ID,
+        2,
+    );
+    env.make(&first, 600, 300).await.unwrap();
+    env.make(&second, 400, 200).await.unwrap();
+    let taker = env.taker(&first, 300).await;
+
+    env.take(&first, &taker).await.unwrap();
+    env.refund(&second).await.unwrap();
+
+    let maker = stats(&mut env, &first.maker_key()).await;
+    assert_eq!(maker.wallet, first.maker_key());
+    assert_eq!(maker.offers_created, 2);
+    assert_eq!(maker.offers_filled, 1);
+    assert_eq!(maker.offers_refunded, 1);
+    assert_eq!(maker.fills_taken, 0);
+    assert_eq!(
+        maker.volume,
+        vec![
+            MintVolume {
+                mint: first.mint_a,
+                amount: 600
+            },
+            MintVolume {
+                mint: first.mint_b,
+                amount: 300
+            },
+        ]
+    ); //Refunds move no volume.
+}
+
+#[tokio::test]
+async fn take_partial_counts_every_fill_and_the_filled_offer_once() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 1_000, 500).await.unwrap();
+    let taker = env.taker(&offer, 500).await;
+
+    env.take_partial(&offer, &taker, 200).await.unwrap();
+    let maker = stats(&mut env, &offer.maker_key()).await;
+    assert_eq!(maker.offers_filled, 0);
+    env.take_partial(&offer, &taker, 300).await.unwrap();
+
+    let maker = stats(&mut env, &offer.maker_key()).await;
+    assert_eq!(maker.offers_filled, 1);
+    let taker = stats(&mut env, &taker.pubkey()).await;
+    assert_eq!(taker.fills_taken, 2);
+    assert_eq!(taker.offers_created, 0);
+    assert_eq!(volume(&taker, &offer.mint_a), 1_000);
+    assert_eq!(volume(&taker, &offer.mint_b), 500);
+}
+
+#[tokio::test]
+async fn refund_expired_counts_the_refund_for_the_maker() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    let expires_at = env.now().await + 60;
+    env.make_with(&offer, 1_000, 500, expires_at, None)
+        .await
+        .unwrap();
+    env.set_time(expires_at + 1).await;
+    let crank = env.create_user().await;
+
+    let accounts = offer.refund_expired_accounts(&crank.pubkey());
+    env.send(
+        &[ix(accounts, escrow2::instruction::RefundExpired {})],
+        &[&crank],
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(stats(&mut env, &offer.maker_key()).await.offers_refunded, 1);
+    assert!(!env.exists(&stats_address(&crank.pubkey())).await);
+}
+
+#[tokio::test]
+async fn taking_your_own_offer_records_both_sides() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 1_000, 500).await.unwrap();
+    env.mint_to(
+        &offer.mint_b,
+        &offer.maker_key(),
+        &offer.token_program_b,
+        500,
+    )
+    .await;
+
+    env.take(&offer, &offer.maker).await.unwrap();
+
+    let stats = stats(&mut env, &offer.maker_key()).await;
+    assert_eq!(stats.offers_created, 1);
+    assert_eq!(stats.offers_filled, 1);
+    assert_eq!(stats.fills_taken, 1);
+    assert_eq!(volume(&stats, &offer.mint_a), 2_000); //Once as the maker, once as the taker.
+}
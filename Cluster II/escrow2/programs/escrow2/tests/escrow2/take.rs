@@ -0,0 +1,538 @@
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::{token::spl_token, token_2022::spl_token_2022};
+use escrow2::error::EscrowError;
+use solana_program_test::BanksClientError;
+use solana_sdk::signature::{Keypair, Signer};
+
+use crate::common::*;
+
+async fn take_with_limits(
+    env: &mut Env,
+    offer: &Offer,
+    taker: &Keypair,
+    expected_receive_a: u64,
+    max_pay_b: u64,
+) -> Result<(), BanksClientError> {
+    let accounts = offer.take_accounts(&taker.pubkey(), &env.treasury.pubkey());
+    let args = escrow2::instruction::Take {
+        expected_receive_a,
+        max_pay_b,
+    };
+    env.send(&[ix(accounts, args)], &[taker]).await
+}
+
+async fn take_partial_with_limits(
+    env: &mut Env,
+    offer: &Offer,
+    taker: &Keypair,
+    amount_b: u64,
+    min_receive_a: u64,
+    max_pay_b: u64,
+) -> Result<(), BanksClientError> {
+    let accounts = offer.take_accounts(&taker.pubkey(), &env.treasury.pubkey());
+    let args = escrow2::instruction::TakePartial {
+        amount_b,
+        min_receive_a,
+        max_pay_b,
+    };
+    env.send(&[ix(accounts, args)], &[taker]).await
+}
+
+#[tokio::test]
+async fn take_swaps_both_legs_and_closes_escrow() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 1_000, 500).await.unwrap();
+    let taker = env.taker(&offer, 500).await;
+    let escrow_rent = env.lamports(&offer.escrow).await;
+    let maker_lamports = env.lamports(&offer.maker_key()).await;
+
+    env.take(&offer, &taker).await.unwrap();
+
+    let taker_ata_a = ata(&taker.pubkey(), &offer.mint_a, &offer.token_program_a);
+    let taker_ata_b = ata(&taker.pubkey(), &offer.mint_b, &offer.token_program_b);
+    assert_eq!(env.token_balance(&taker_ata_a).await, 1_000);
+    assert_eq!(env.token_balance(&taker_ata_b).await, 0);
+    assert_eq!(env.token_balance(&offer.maker_ata_b()).await, 500);
+    assert!(!env.exists(&offer.vault).await);
+    assert!(!env.exists(&offer.escrow).await);
+    assert_eq!(
+        env.lamports(&offer.maker_key()).await,
+        maker_lamports + escrow_rent
+    ); //The escrow rent goes back to the maker.
+}
+
+#[tokio::test]
+async fn take_works_across_token_programs() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token_2022::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 1_000, 500).await.unwrap();
+    let taker = env.taker(&offer, 500).await;
+
+    env.take(&offer, &taker).await.unwrap();
+
+    let taker_ata_a = ata(&taker.pubkey(), &offer.mint_a, &spl_token_2022::ID);
+    assert_eq!(env.token_balance(&taker_ata_a).await, 1_000);
+    assert_eq!(env.token_balance(&offer.maker_ata_b()).await, 500);
+    assert!(!env.exists(&offer.escrow).await);
+}
+
+#[tokio::test]
+async fn take_partial_fills_pro_rata_until_filled() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 1_000, 300).await.unwrap();
+    let taker = env.taker(&offer, 300).await;
+    let taker_ata_a = ata(&taker.pubkey(), &offer.mint_a, &offer.token_program_a);
+
+    env.take_partial(&offer, &taker, 100).await.unwrap();
+
+    assert_eq!(env.token_balance(&taker_ata_a).await, 333); //Rounded down in the maker's favor.
+    assert_eq!(env.token_balance(&offer.maker_ata_b()).await, 100);
+    let escrow = env.escrow(&offer.escrow).await;
+    assert_eq!(escrow.remaining_a, 667);
+    assert_eq!(escrow.remaining_b, 200);
+
+    env.take_partial(&offer, &taker, 200).await.unwrap();
+
+    assert_eq!(env.token_balance(&taker_ata_a).await, 1_000);
+    assert_eq!(env.token_balance(&offer.maker_ata_b()).await, 300);
+    assert!(!env.exists(&offer.vault).await);
+    assert!(!env.exists(&offer.escrow).await);
+}
+
+#[tokio::test]
+async fn take_routes_protocol_fee_to_treasury() {
+    let mut env = Env::with_fee(250).await; //2.5%
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 1_000, 400).await.unwrap();
+    let taker = env.taker(&offer, 400).await;
+
+    env.take(&offer, &taker).await.unwrap();
+
+    let treasury_ata_b = ata(
+        &env.treasury.pubkey(),
+        &offer.mint_b,
+        &offer.token_program_b,
+    );
+    assert_eq!(env.token_balance(&treasury_ata_b).await, 10);
+    assert_eq!(env.token_balance(&offer.maker_ata_b()).await, 390);
+}
+
+#[tokio::test]
+async fn take_pays_maker_in_full_despite_transfer_fee() {
+    let mut env = Env::new().await;
+    let mint_a = env.create_mint(&spl_token::ID).await;
+    let mint_b = env.create_fee_mint(100, u64::MAX).await; //1% on every transfer.
+    let offer = env
+        .offer_with_mints(mint_a, spl_token::ID, mint_b, spl_token_2022::ID, 1_000)
+        .await;
+    env.make(&offer, 1_000, 1_000).await.unwrap();
+    let taker = env.taker(&offer, 2_000).await;
+
+    env.take(&offer, &taker).await.unwrap();
+
+    assert_eq!(env.token_balance(&offer.maker_ata_b()).await, 1_000);
+    let taker_ata_b = ata(&taker.pubkey(), &mint_b, &spl_token_2022::ID);
+    assert_eq!(env.token_balance(&taker_ata_b).await, 2_000 - 1_011); //The taker covers the fee on top.
+}
+
+#[tokio::test]
+async fn take_fails_when_the_maker_changes_the_offer_first() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 600, 300).await.unwrap();
+    let taker = env.taker(&offer, 1_000).await;
+    let update = escrow2::instruction::Update {
+        receive: 400,
+        deposit: 500,
+    };
+    env.send(&[ix(offer.update_accounts(), update)], &[&offer.maker])
+        .await
+        .unwrap(); //Lands before the taker's transaction.
+
+    assert_escrow_error(
+        take_with_limits(&mut env, &offer, &taker, 500, 300).await,
+        EscrowError::SlippageExceeded,
+    );
+    assert_escrow_error(
+        take_with_limits(&mut env, &offer, &taker, 600, 400).await,
+        EscrowError::SlippageExceeded,
+    );
+    assert_eq!(env.token_balance(&offer.vault).await, 500);
+
+    take_with_limits(&mut env, &offer, &taker, 500, 400)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn take_partial_fails_when_the_maker_changes_the_rate_first() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 600, 300).await.unwrap();
+    let taker = env.taker(&offer, 1_000).await;
+    let update = escrow2::instruction::Update {
+        receive: 300,
+        deposit: 150,
+    };
+    env.send(&[ix(offer.update_accounts(), update)], &[&offer.maker])
+        .await
+        .unwrap(); //A quarter of mint_a for the same amount_b, landing before the taker's transaction.
+
+    assert_escrow_error(
+        take_partial_with_limits(&mut env, &offer, &taker, 100, 200, 100).await,
+        EscrowError::SlippageExceeded,
+    );
+    assert_escrow_error(
+        take_partial_with_limits(&mut env, &offer, &taker, 100, 50, 99).await,
+        EscrowError::SlippageExceeded,
+    );
+    assert_eq!(env.token_balance(&offer.vault).await, 150);
+
+    take_partial_with_limits(&mut env, &offer, &taker, 100, 50, 100)
+        .await
+        .unwrap();
+    let taker_ata_a = ata(&taker.pubkey(), &offer.mint_a, &spl_token::ID);
+    assert_eq!(env.token_balance(&taker_ata_a).await, 50);
+}
+
+#[tokio::test]
+async fn take_limits_count_transfer_fees() {
+    let mut env = Env::new().await;
+    let mint_a = env.create_mint(&spl_token::ID).await;
+    let mint_b = env.create_fee_mint(100, u64::MAX).await; //1% on every transfer.
+    let offer = env
+        .offer_with_mints(mint_a, spl_token::ID, mint_b, spl_token_2022::ID, 1_000)
+        .await;
+    env.make(&offer, 1_000, 1_000).await.unwrap();
+    let taker = env.taker(&offer, 2_000).await;
+
+    assert_escrow_error(
+        take_with_limits(&mut env, &offer, &taker, 1_000, 1_000).await,
+        EscrowError::SlippageExceeded,
+    ); //The taker pays 1_011 to cover the fee.
+    take_with_limits(&mut env, &offer, &taker, 1_000, 1_011)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn take_limits_count_the_transfer_fee_on_mint_a() {
+    let mut env = Env::new().await;
+    let mint_a = env.create_fee_mint(100, u64::MAX).await;
+    let mint_b = env.create_mint(&spl_token::ID).await;
+    let offer = env
+        .offer_with_mints(mint_a, spl_token_2022::ID, mint_b, spl_token::ID, 1_000)
+        .await;
+    env.make(&offer, 1_000, 100).await.unwrap();
+    let taker = env.taker(&offer, 100).await;
+    let remaining_a = env.escrow(&offer.escrow).await.remaining_a;
+
+    assert_escrow_error(
+        take_with_limits(&mut env, &offer, &taker, remaining_a, 100).await,
+        EscrowError::SlippageExceeded,
+    ); //Part of the release is withheld by the mint.
+    take_with_limits(&mut env, &offer, &taker, remaining_a * 98 / 100, 100)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn take_pays_and_receives_native_sol() {
+    let mut env = Env::new().await;
+    let mint_a = env.create_mint(&spl_token::ID).await;
+    let offer = env
+        .offer_with_mints(
+            mint_a,
+            spl_token::ID,
+            spl_token::native_mint::ID,
+            spl_token::ID,
+            1_000,
+        )
+        .await;
+    env.make(&offer, 1_000, 2_000_000_000).await.unwrap();
+    let taker = env.create_user().await;
+    let maker_lamports = env.lamports(&offer.maker_key()).await;
+    let escrow_rent = env.lamports(&offer.escrow).await;
+
+    let mut accounts = offer.take_accounts(&taker.pubkey(), &env.treasury.pubkey());
+    accounts.taker_ata_b = None;
+    accounts.maker_ata_b = None;
+    accounts.treasury_ata_b = None;
+    env.send(&[ix(accounts, TAKE_ANY)], &[&taker])
+        .await
+        .unwrap();
+
+    assert_eq!(
+        env.lamports(&offer.maker_key()).await,
+        maker_lamports + 2_000_000_000 + escrow_rent
+    );
+    assert!(!env.exists(&offer.maker_ata_b()).await);
+    assert!(!env.exists(&offer.escrow).await);
+}
+
+#[tokio::test]
+async fn take_unwraps_native_sol_deposit() {
+    let mut env = Env::new().await;
+    let mint_b = env.create_mint(&spl_token::ID).await;
+    let offer = env
+        .offer_with_mints(
+            spl_token::native_mint::ID,
+            spl_token::ID,
+            mint_b,
+            spl_token::ID,
+            0,
+        )
+        .await;
+    env.make_native(&offer, 1_000_000_000, 50).await.unwrap();
+    let taker = env.taker(&offer, 50).await;
+    //So the taker's balance only moves by what the escrow pays out.
+    env.create_ata(&offer.maker_key(), &mint_b, &spl_token::ID)
+        .await;
+    let treasury = env.treasury.pubkey();
+    env.create_ata(&treasury, &mint_b, &spl_token::ID).await;
+    let taker_lamports = env.lamports(&taker.pubkey()).await;
+    let vault_rent = env.lamports(&offer.vault).await - 1_000_000_000;
+
+    env.take(&offer, &taker).await.unwrap();
+
+    let taker_ata_a = ata(&taker.pubkey(), &offer.mint_a, &offer.token_program_a);
+    assert!(!env.exists(&taker_ata_a).await); //Closed again once the SOL is unwrapped.
+    let stats_rent = env.lamports(&stats_address(&taker.pubkey())).await; //The taker's first fill opens its stats.
+    assert_eq!(
+        env.lamports(&taker.pubkey()).await,
+        taker_lamports + 1_000_000_000 + vault_rent - stats_rent
+    );
+}
+
+#[tokio::test]
+async fn take_returns_tokens_sent_to_the_vault_to_the_maker() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 600, 300).await.unwrap();
+    env.donate(&offer, 7).await;
+    let taker = env.taker(&offer, 300).await;
+
+    env.take(&offer, &taker).await.unwrap();
+
+    let taker_ata_a = ata(&taker.pubkey(), &offer.mint_a, &offer.token_program_a);
+    assert_eq!(env.token_balance(&taker_ata_a).await, 600);
+    assert_eq!(env.token_balance(&offer.maker_ata_a()).await, 400 + 7);
+    assert!(!env.exists(&offer.vault).await);
+}
+
+#[tokio::test]
+async fn take_partial_leaves_surplus_out_of_every_fill() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 1_000, 100).await.unwrap();
+    env.donate(&offer, 50).await;
+    let taker = env.taker(&offer, 100).await;
+    let taker_ata_a = ata(&taker.pubkey(), &offer.mint_a, &offer.token_program_a);
+
+    env.take_partial(&offer, &taker, 40).await.unwrap();
+    assert_eq!(env.token_balance(&taker_ata_a).await, 400);
+    assert_eq!(env.token_balance(&offer.vault).await, 600 + 50);
+
+    env.take(&offer, &taker).await.unwrap();
+    assert_eq!(env.token_balance(&taker_ata_a).await, 1_000);
+    assert_eq!(env.token_balance(&offer.maker_ata_a()).await, 50);
+}
+
+#[tokio::test]
+async fn take_returns_native_sol_surplus_to_the_maker() {
+    let mut env = Env::new().await;
+    let mint_b = env.create_mint(&spl_token::ID).await;
+    let offer = env
+        .offer_with_mints(
+            spl_token::native_mint::ID,
+            spl_token::ID,
+            mint_b,
+            spl_token::ID,
+            0,
+        )
+        .await;
+    env.make_native(&offer, 1_000_000_000, 50).await.unwrap();
+    env.donate(&offer, 5_000).await;
+    let taker = env.taker(&offer, 50).await;
+    env.create_ata(&offer.maker_key(), &mint_b, &spl_token::ID)
+        .await;
+    let treasury = env.treasury.pubkey();
+    env.create_ata(&treasury, &mint_b, &spl_token::ID).await;
+    let taker_lamports = env.lamports(&taker.pubkey()).await;
+    let maker_lamports = env.lamports(&offer.maker_key()).await;
+    let escrow_rent = env.lamports(&offer.escrow).await;
+    let vault_rent = env.lamports(&offer.vault).await - 1_000_000_000 - 5_000;
+
+    env.take(&offer, &taker).await.unwrap();
+
+    let stats_rent = env.lamports(&stats_address(&taker.pubkey())).await; //The taker's first fill opens its stats.
+    assert_eq!(
+        env.lamports(&taker.pubkey()).await,
+        taker_lamports + 1_000_000_000 + vault_rent - stats_rent
+    );
+    assert_eq!(
+        env.lamports(&offer.maker_key()).await,
+        maker_lamports + escrow_rent + 5_000
+    );
+}
+
+#[tokio::test]
+async fn take_fails_when_taker_cannot_pay() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 1_000, 500).await.unwrap();
+    let taker = env.taker(&offer, 499).await;
+
+    assert_escrow_error(env.take(&offer, &taker).await, EscrowError::DepositFailed);
+    assert_eq!(env.token_balance(&offer.vault).await, 1_000);
+}
+
+#[tokio::test]
+async fn take_fails_when_vault_cannot_pay_out() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 1_000, 500).await.unwrap();
+    let taker = env.taker(&offer, 500).await;
+    let taker_ata_a = env
+        .create_ata(&taker.pubkey(), &offer.mint_a, &offer.token_program_a)
+        .await;
+    env.freeze(&taker_ata_a, &offer.mint_a, &offer.token_program_a)
+        .await;
+
+    assert_escrow_error(env.take(&offer, &taker).await, EscrowError::WithdrawFailed);
+}
+
+#[tokio::test]
+async fn take_partial_rejects_zero_and_overfills() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 1_000, 500).await.unwrap();
+    let taker = env.taker(&offer, 1_000).await;
+
+    assert_escrow_error(
+        env.take_partial(&offer, &taker, 0).await,
+        EscrowError::InvalidFillAmount,
+    );
+    assert_escrow_error(
+        env.take_partial(&offer, &taker, 501).await,
+        EscrowError::InvalidFillAmount,
+    );
+}
+
+#[tokio::test]
+async fn take_partial_rejects_fill_that_releases_nothing() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1).await;
+    env.make(&offer, 1, 10).await.unwrap();
+    let taker = env.taker(&offer, 10).await;
+
+    assert_escrow_error(
+        env.take_partial(&offer, &taker, 1).await,
+        EscrowError::FillTooSmall,
+    );
+}
+
+#[tokio::test]
+async fn take_rejects_expired_offer() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    let expires_at = env.now().await + 60;
+    env.make_with(&offer, 1_000, 500, expires_at, None)
+        .await
+        .unwrap();
+    let taker = env.taker(&offer, 500).await;
+
+    env.set_time(expires_at + 1).await;
+
+    assert_escrow_error(env.take(&offer, &taker).await, EscrowError::OfferExpired);
+}
+
+#[tokio::test]
+async fn take_rejects_other_takers_on_private_offer() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    let allowed = env.taker(&offer, 500).await;
+    env.make_with(&offer, 1_000, 500, NEVER, Some(allowed.pubkey()))
+        .await
+        .unwrap();
+    let other = env.taker(&offer, 500).await;
+
+    assert_escrow_error(
+        env.take(&offer, &other).await,
+        EscrowError::UnauthorizedTaker,
+    );
+    env.take(&offer, &allowed).await.unwrap();
+}
+
+#[tokio::test]
+async fn take_fails_when_transfer_fee_cannot_be_grossed_up() {
+    let mut env = Env::new().await;
+    let mint_a = env.create_mint(&spl_token::ID).await;
+    let mint_b = env.create_fee_mint(9_999, u64::MAX).await; //Grossing up by 10_000x overflows a u64 for large fills.
+    let offer = env
+        .offer_with_mints(mint_a, spl_token::ID, mint_b, spl_token_2022::ID, 1_000)
+        .await;
+    env.make(&offer, 1_000, u64::MAX / 1_000).await.unwrap();
+    let taker = env.taker(&offer, 500).await;
+
+    assert_escrow_error(
+        env.take(&offer, &taker).await,
+        EscrowError::TransferFeeCalculationFailed,
+    );
+}
+
+#[tokio::test]
+async fn take_requires_treasury_account_when_fee_is_charged() {
+    let mut env = Env::with_fee(100).await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 1_000, 500).await.unwrap();
+    let taker = env.taker(&offer, 500).await;
+
+    let mut accounts = offer.take_accounts(&taker.pubkey(), &env.treasury.pubkey());
+    accounts.treasury_ata_b = None;
+    let result = env.send(&[ix(accounts, TAKE_ANY)], &[&taker]).await;
+    assert_escrow_error(result, EscrowError::TreasuryAccountRequired);
+}
+
+#[tokio::test]
+async fn take_is_free_until_the_config_is_initialized() {
+    let mut env = Env::without_config().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 1_000, 500).await.unwrap();
+    let taker = env.taker(&offer, 500).await;
+
+    let mut accounts = offer.take_accounts(&taker.pubkey(), &Pubkey::new_unique());
+    accounts.treasury_ata_b = None; //There is no treasury to pay yet.
+    env.send(&[ix(accounts, TAKE_ANY)], &[&taker])
+        .await
+        .unwrap();
+
+    assert_eq!(env.token_balance(&offer.maker_ata_b()).await, 500);
+}
+
+#[tokio::test]
+async fn take_rejects_another_treasury_than_the_config() {
+    let mut env = Env::with_fee(100).await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 1_000, 500).await.unwrap();
+    let taker = env.taker(&offer, 500).await;
+
+    let accounts = offer.take_accounts(&taker.pubkey(), &taker.pubkey());
+    let result = env.send(&[ix(accounts, TAKE_ANY)], &[&taker]).await;
+    assert_escrow_error(result, EscrowError::TreasuryMismatch);
+}
+
+#[tokio::test]
+async fn take_requires_both_token_accounts_for_non_native_mint() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 1_000, 500).await.unwrap();
+    let taker = env.taker(&offer, 500).await;
+
+    let mut accounts = offer.take_accounts(&taker.pubkey(), &env.treasury.pubkey());
+    accounts.taker_ata_b = None;
+    let result = env.send(&[ix(accounts, TAKE_ANY)], &[&taker]).await;
+    assert_escrow_error(result, EscrowError::NativeMintRequired);
+}
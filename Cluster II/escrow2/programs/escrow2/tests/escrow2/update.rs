@@ -0,0 +1,72 @@
+use anchor_spl::token::spl_token;
+use escrow2::error::EscrowError;
+use solana_program_test::BanksClientError;
+
+use crate::common::*;
+
+async fn update(
+    env: &mut Env,
+    offer: &Offer,
+    receive: u64,
+    deposit: u64,
+) -> Result<(), BanksClientError> {
+    let args = escrow2::instruction::Update { receive, deposit };
+    env.send(&[ix(offer.update_accounts(), args)], &[&offer.maker])
+        .await
+}
+
+#[tokio::test]
+async fn update_tops_up_the_vault() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 600, 300).await.unwrap();
+
+    update(&mut env, &offer, 500, 1_000).await.unwrap();
+
+    assert_eq!(env.token_balance(&offer.vault).await, 1_000);
+    assert_eq!(env.token_balance(&offer.maker_ata_a()).await, 0);
+    let escrow = env.escrow(&offer.escrow).await;
+    assert_eq!(escrow.remaining_a, 1_000);
+    assert_eq!(escrow.remaining_b, 500);
+}
+
+#[tokio::test]
+async fn update_withdraws_from_the_vault() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 600, 300).await.unwrap();
+
+    update(&mut env, &offer, 100, 200).await.unwrap();
+
+    assert_eq!(env.token_balance(&offer.vault).await, 200);
+    assert_eq!(env.token_balance(&offer.maker_ata_a()).await, 800);
+    let escrow = env.escrow(&offer.escrow).await;
+    assert_eq!(escrow.remaining_a, 200);
+    assert_eq!(escrow.remaining_b, 100);
+}
+
+#[tokio::test]
+async fn update_rejects_zero_amounts() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 600, 300).await.unwrap();
+
+    assert_escrow_error(
+        update(&mut env, &offer, 0, 600).await,
+        EscrowError::ZeroAmount,
+    );
+    assert_escrow_error(
+        update(&mut env, &offer, 300, 0).await,
+        EscrowError::ZeroAmount,
+    );
+}
+
+#[tokio::test]
+async fn update_rejects_top_up_above_balance() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 600, 300).await.unwrap();
+
+    let result = update(&mut env, &offer, 300, 1_001).await;
+    assert_escrow_error(result, EscrowError::InsufficientBalance);
+}
@@ -0,0 +1,208 @@
+use anchor_lang::{prelude::Pubkey, AccountDeserialize};
+use anchor_spl::token::spl_token;
+use escrow2::{
+    error::EscrowError,
+    state::{MakeArgs, Vesting, VestingTerms},
+};
+use solana_program_test::BanksClientError;
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    system_program,
+};
+
+use crate::common::*;
+
+//Nothing unlocks for 100 seconds, everything has unlocked 1_000 seconds after the take.
+const TERMS: VestingTerms = VestingTerms {
+    cliff: 100,
+    duration: 1_000,
+};
+
+fn vesting_address(offer: &Offer, taker: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"vesting", offer.escrow.as_ref(), taker.as_ref()],
+        &escrow2::ID,
+    )
+    .0
+}
+
+//The vesting of `taker` on `offer` and its vault.
+fn vesting(offer: &Offer, taker: &Pubkey) -> (Pubkey, Pubkey) {
+    let vesting = vesting_address(offer, taker);
+    (
+        vesting,
+        ata(&vesting, &offer.mint_a, &offer.token_program_a),
+    )
+}
+
+async fn make_vested(
+    env: &mut Env,
+    offer: &Offer,
+    vesting: VestingTerms,
+) -> Result<(), BanksClientError> {
+    let args = MakeArgs {
+        vesting: Some(vesting),
+        ..offer.make_args(1_000, 500)
+    };
+    env.send(
+        &[offer.make_ix(offer.make_accounts(), args)],
+        &[&offer.maker],
+    )
+    .await
+}
+
+//Takes the whole offer, or amount_b of it, passing the taker's vesting accounts when `with_vesting` is set.
+async fn take_vested(
+    env: &mut Env,
+    offer: &Offer,
+    taker: &Keypair,
+    with_vesting: bool,
+    amount_b: Option<u64>,
+) -> Result<(), BanksClientError> {
+    let mut accounts = offer.take_accounts(&taker.pubkey(), &env.treasury.pubkey());
+    if with_vesting {
+        let (vesting, vesting_vault) = vesting(offer, &taker.pubkey());
+        accounts.vesting = Some(vesting);
+        accounts.vesting_vault = Some(vesting_vault);
+    }
+    let take = match amount_b {
+        Some(amount_b) => ix(accounts, take_partial_any(amount_b)),
+        None => ix(accounts, TAKE_ANY),
+    };
+    env.send(&[take], &[taker]).await
+}
+
+async fn claim(env: &mut Env, offer: &Offer, taker: &Keypair) -> Result<(), BanksClientError> {
+    let (vesting, vesting_vault) = vesting(offer, &taker.pubkey());
+    let accounts = escrow2::accounts::ClaimVested {
+        beneficiary: taker.pubkey(),
+        mint: offer.mint_a,
+        beneficiary_ata: ata(&taker.pubkey(), &offer.mint_a, &offer.token_program_a),
+        vesting,
+        vesting_vault,
+        token_program: offer.token_program_a,
+        associated_token_program: anchor_spl::associated_token::ID,
+        system_program: system_program::ID,
+    };
+    env.send(
+        &[ix(accounts, escrow2::instruction::ClaimVested {})],
+        &[taker],
+    )
+    .await
+}
+
+//A vested offer of 1_000 for 500, taken by a new taker. Returns the taker and the time of the take.
+async fn taken(env: &mut Env) -> (Offer, Keypair, i64) {
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    make_vested(env, &offer, TERMS).await.unwrap();
+    let taker = env.taker(&offer, 500).await;
+    let now = env.now().await;
+    take_vested(env, &offer, &taker, true, None).await.unwrap();
+    (offer, taker, now)
+}
+
+#[tokio::test]
+async fn take_locks_mint_a_in_the_vesting_vault() {
+    let mut env = Env::new().await;
+    let (offer, taker, now) = taken(&mut env).await;
+
+    let (vesting, vesting_vault) = vesting(&offer, &taker.pubkey());
+    assert_eq!(env.token_balance(&vesting_vault).await, 1_000);
+    let taker_ata_a = ata(&taker.pubkey(), &offer.mint_a, &spl_token::ID);
+    assert_eq!(env.token_balance(&taker_ata_a).await, 0);
+    assert_eq!(env.token_balance(&offer.maker_ata_b()).await, 500); //The maker is paid right away.
+    let account = env.account(&vesting).await.unwrap();
+    let stored = Vesting::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!(stored.beneficiary, taker.pubkey());
+    assert_eq!(stored.escrow, offer.escrow);
+    assert_eq!(stored.total, 1_000);
+    assert_eq!(stored.claimed, 0);
+    assert_eq!(stored.cliff_at, now + 100);
+    assert_eq!(stored.end_at, now + 1_000);
+}
+
+#[tokio::test]
+async fn claim_vested_releases_linearly_after_the_cliff() {
+    let mut env = Env::new().await;
+    let (offer, taker, now) = taken(&mut env).await;
+    let taker_ata_a = ata(&taker.pubkey(), &offer.mint_a, &spl_token::ID);
+
+    env.set_time(now + 99).await;
+    assert_escrow_error(
+        claim(&mut env, &offer, &taker).await,
+        EscrowError::NothingToClaim,
+    );
+
+    env.set_time(now + 250).await;
+    claim(&mut env, &offer, &taker).await.unwrap();
+    assert_eq!(env.token_balance(&taker_ata_a).await, 250);
+
+    env.set_time(now + 600).await;
+    claim(&mut env, &offer, &taker).await.unwrap();
+    assert_eq!(env.token_balance(&taker_ata_a).await, 600);
+
+    env.set_time(now + 2_000).await;
+    claim(&mut env, &offer, &taker).await.unwrap();
+    assert_eq!(env.token_balance(&taker_ata_a).await, 1_000);
+    let (vesting, vesting_vault) = vesting(&offer, &taker.pubkey());
+    assert!(!env.exists(&vesting).await);
+    assert!(!env.exists(&vesting_vault).await);
+}
+
+#[tokio::test]
+async fn vested_offers_are_only_filled_whole_through_a_vesting() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    make_vested(&mut env, &offer, TERMS).await.unwrap();
+    let taker = env.taker(&offer, 500).await;
+
+    assert_escrow_error(
+        take_vested(&mut env, &offer, &taker, true, Some(100)).await,
+        EscrowError::VestedPartialFill,
+    );
+    assert_escrow_error(
+        take_vested(&mut env, &offer, &taker, false, None).await,
+        EscrowError::VestingAccountsMismatch,
+    );
+
+    take_vested(&mut env, &offer, &taker, true, Some(500))
+        .await
+        .unwrap(); //A partial fill of everything is a whole fill.
+    assert!(env.exists(&vesting_address(&offer, &taker.pubkey())).await);
+}
+
+#[tokio::test]
+async fn take_rejects_vesting_accounts_for_other_offers() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+    env.make(&offer, 1_000, 500).await.unwrap();
+    let taker = env.taker(&offer, 500).await;
+
+    assert_escrow_error(
+        take_vested(&mut env, &offer, &taker, true, None).await,
+        EscrowError::VestingAccountsMismatch,
+    );
+}
+
+#[tokio::test]
+async fn make_rejects_invalid_vesting_terms() {
+    let mut env = Env::new().await;
+    let offer = env.offer(spl_token::ID, spl_token::ID, 1_000).await;
+
+    for invalid in [
+        VestingTerms {
+            cliff: 0,
+            duration: 0,
+        },
+        VestingTerms { cliff: -1, ..TERMS },
+        VestingTerms {
+            cliff: 1_001,
+            ..TERMS
+        },
+    ] {
+        assert_escrow_error(
+            make_vested(&mut env, &offer, invalid).await,
+            EscrowError::InvalidVestingTerms,
+        );
+    }
+}